@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use crate::grid::{Cell, Grid};
+
+// How lit a wire looks, easing towards its signal instead of snapping
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WireAnimation {
+    // 0.0 when fully off, 1.0 when fully on
+    pub level: f32,
+    // Progress of the pulse travelling along the wire, wrapping at 1.0. Only
+    // advances while the wire is lit, so a newly lit wire starts a new pulse.
+    pub phase: f32,
+}
+
+// Per-cell wire animations, kept in step with the grid's size. Cells that
+// aren't wires stay at the default.
+#[derive(Debug, Default)]
+pub struct WireAnimations {
+    width: usize,
+    cells: Vec<WireAnimation>,
+}
+
+impl WireAnimations {
+    // Seconds for a wire to fade fully on or off
+    const FADE_TIME: f32 = 0.25;
+    // Pulses per second
+    const PULSE_SPEED: f32 = 0.75;
+
+    pub fn update(&mut self, grid: &Grid, dt: Duration) {
+        // A resized grid starts with every wire unlit
+        if self.width != grid.width() || self.cells.len() != grid.width() * grid.height() {
+            self.width = grid.width();
+            self.cells = vec![WireAnimation::default(); grid.width() * grid.height()];
+        }
+
+        let dt = dt.as_secs_f32();
+        for (i, animation) in self.cells.iter_mut().enumerate() {
+            let (x, y) = (i % self.width, i / self.width);
+            let target = match grid.get(x, y) {
+                Some(Cell::Wire) if grid.signal(x, y) => 1.0,
+                _ => 0.0,
+            };
+
+            let step = dt / Self::FADE_TIME;
+            animation.level = if target > animation.level {
+                (animation.level + step).min(target)
+            } else {
+                (animation.level - step).max(target)
+            };
+
+            if animation.level == 0.0 {
+                animation.phase = 0.0;
+            } else {
+                animation.phase = (animation.phase + dt * Self::PULSE_SPEED).fract();
+            }
+        }
+    }
+
+    // Jumps every wire straight to its signal, for a single frame of a
+    // circuit that should look like it's been running for a while
+    pub fn settle(&mut self, grid: &Grid) {
+        self.update(grid, Duration::from_secs_f32(Self::FADE_TIME));
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> WireAnimation {
+        self.cells
+            .get(y * self.width + x)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wires_fade_in_and_out() {
+        let mut grid = Grid::new(2, 1);
+        grid.set(0, 0, Cell::Source);
+        grid.set(1, 0, Cell::Wire);
+        grid.evaluate();
+
+        let mut animations = WireAnimations::default();
+        let frame = Duration::from_millis(50);
+        animations.update(&grid, frame);
+        let level = animations.get(1, 0).level;
+        assert!(level > 0.0 && level < 1.0);
+        assert_eq!(animations.get(0, 0), WireAnimation::default());
+
+        for _ in 0..10 {
+            animations.update(&grid, frame);
+        }
+        assert_eq!(animations.get(1, 0).level, 1.0);
+
+        grid.set(0, 0, Cell::Empty);
+        grid.evaluate();
+        animations.update(&grid, frame);
+        assert!(animations.get(1, 0).level < 1.0);
+        for _ in 0..10 {
+            animations.update(&grid, frame);
+        }
+        assert_eq!(animations.get(1, 0), WireAnimation::default());
+
+        grid.set(0, 0, Cell::Source);
+        grid.evaluate();
+        animations.settle(&grid);
+        assert_eq!(animations.get(1, 0).level, 1.0);
+    }
+}
@@ -0,0 +1,107 @@
+use std::{path::PathBuf, str::FromStr};
+
+// Command-line flags, parsed by hand to avoid pulling in a dependency. Both
+// `--flag value` and `--flag=value` are accepted; invalid values are reported
+// and ignored so the defaults apply.
+#[derive(Debug, Default)]
+pub struct Args {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fullscreen: bool,
+    pub seed: Option<u64>,
+    pub fps: Option<u32>,
+    // Index into the adapters listed at startup
+    pub gpu: Option<usize>,
+    // Exits after rendering this many frames, or never when 0. With `--bench`
+    // it's how many frames each stage is timed over instead.
+    pub frames: u64,
+    pub bench: bool,
+    // Writes every game input to this file, one JSON object per line
+    pub record: Option<PathBuf>,
+    // Plays a file written by `--record` back instead of live input. It only
+    // reproduces the session when started from the same game, so pass the
+    // same `--seed` as the recording.
+    pub replay: Option<PathBuf>,
+    // Writes each frame's CPU time, present mode, frame latency and whether
+    // the surface was reconfigured to this CSV file
+    pub trace: Option<PathBuf>,
+    // Renders a PNG of the circuit next to it each time it's saved
+    pub thumbnails: bool,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Self {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || inline_value.clone().or_else(|| args.next());
+
+            match flag.as_str() {
+                "--width" => parsed.width = parse_positive(&flag, value()),
+                "--height" => parsed.height = parse_positive(&flag, value()),
+                "--fullscreen" => parsed.fullscreen = true,
+                "--seed" => parsed.seed = parse_unsigned(&flag, value()),
+                "--fps" => parsed.fps = parse_positive(&flag, value()),
+                "--gpu" => parsed.gpu = parse_unsigned(&flag, value()),
+                "--frames" => parsed.frames = parse_unsigned(&flag, value()).unwrap_or(0),
+                "--bench" => parsed.bench = true,
+                "--record" => parsed.record = parse_path(&flag, value()),
+                "--replay" => parsed.replay = parse_path(&flag, value()),
+                "--trace" => parsed.trace = parse_path(&flag, value()),
+                "--thumbnails" => parsed.thumbnails = true,
+                _ => log::warn!("Ignoring unknown argument {}", flag),
+            }
+        }
+
+        parsed
+    }
+}
+
+fn parse_path(flag: &str, value: Option<String>) -> Option<PathBuf> {
+    if value.is_none() {
+        log::warn!("{} expects a path, ignoring it", flag);
+    }
+    value.map(PathBuf::from)
+}
+
+fn parse_positive(flag: &str, value: Option<String>) -> Option<u32> {
+    let Some(value) = value else {
+        log::warn!("{} expects a value, using the default", flag);
+        return None;
+    };
+
+    match value.parse::<u32>() {
+        Ok(value) if value > 0 => Some(value),
+        _ => {
+            log::warn!(
+                "{} expects a positive integer but got {:?}, using the default",
+                flag,
+                value
+            );
+            None
+        }
+    }
+}
+
+pub fn parse_unsigned<T: FromStr>(flag: &str, value: Option<String>) -> Option<T> {
+    let Some(value) = value else {
+        log::warn!("{} expects a value, ignoring it", flag);
+        return None;
+    };
+
+    value
+        .parse()
+        .map_err(|_| {
+            log::warn!(
+                "{} expects an unsigned integer but got {:?}, ignoring it",
+                flag,
+                value
+            )
+        })
+        .ok()
+}
@@ -0,0 +1,87 @@
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadState {
+    Loading,
+    Ready,
+    // The loading thread panicked, so the asset will never arrive
+    Failed,
+}
+
+// An asset being produced on a background thread, e.g. an image being
+// decoded, so slow loads never stall the render loop. Poll it once a frame
+// and take the result when it's ready. Browsers can't spawn threads, so there
+// it's loaded up front and ready on the first poll.
+pub struct AssetHandle<T> {
+    receiver: Receiver<T>,
+    asset: Option<T>,
+    state: LoadState,
+}
+
+impl<T: Send + 'static> AssetHandle<T> {
+    pub fn spawn(name: &str, load: impl FnOnce() -> T + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let name = name.to_string();
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            log::debug!("Loading {}", name);
+            let _ = sender.send(load());
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        thread::Builder::new()
+            .name(format!("asset loader ({})", name))
+            .spawn(move || {
+                log::debug!("Loading {}", name);
+                let _ = sender.send(load());
+            })
+            .expect("An error occured while spawning an asset loading thread");
+
+        Self {
+            receiver,
+            asset: None,
+            state: LoadState::Loading,
+        }
+    }
+}
+
+impl<T> AssetHandle<T> {
+    pub fn state(&mut self) -> LoadState {
+        if self.state == LoadState::Loading {
+            match self.receiver.try_recv() {
+                Ok(asset) => {
+                    self.asset = Some(asset);
+                    self.state = LoadState::Ready;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.state = LoadState::Failed,
+            }
+        }
+
+        self.state
+    }
+
+    // Blocks until the asset has loaded or failed to, for one-off renders
+    // that can't wait a frame
+    pub fn wait(&mut self) -> LoadState {
+        if self.state == LoadState::Loading {
+            match self.receiver.recv() {
+                Ok(asset) => {
+                    self.asset = Some(asset);
+                    self.state = LoadState::Ready;
+                }
+                Err(_) => self.state = LoadState::Failed,
+            }
+        }
+
+        self.state
+    }
+
+    // Hands over the asset the first time it's called after it's ready
+    pub fn take(&mut self) -> Option<T> {
+        self.state();
+        self.asset.take()
+    }
+}
@@ -0,0 +1,204 @@
+use std::time::Duration;
+
+use web_time::Instant;
+
+use crate::{
+    game::Game,
+    grid::{Cell, Grid},
+    render_stats::RenderStats,
+};
+
+// The average frame time at one instance count
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResult {
+    pub instances: usize,
+    pub frame_time: Duration,
+    // GPU buffers allocated over the timed frames, which should stay at 0
+    // once the instance data fits
+    pub buffer_allocations: u64,
+    // What the last timed frame drew, which is the same every frame of a stage
+    pub draw_calls: u32,
+    pub triangles: u32,
+    // The average CPU time spent encoding a frame
+    pub encode_time: Duration,
+}
+
+impl BenchResult {
+    pub const CSV_HEADER: &'static str =
+        "instances,avg_frame_ms,fps,buffer_allocations,draw_calls,triangles,avg_encode_ms";
+
+    pub fn fps(&self) -> f64 {
+        1.0 / self.frame_time.as_secs_f64()
+    }
+
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{},{:.3},{:.1},{},{},{},{:.3}",
+            self.instances,
+            self.frame_time.as_secs_f64() * 1000.0,
+            self.fps(),
+            self.buffer_allocations,
+            self.draw_calls,
+            self.triangles,
+            self.encode_time.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+// Renders square grids of cells, one sprite instance each, with twice as many
+// cells every stage, and times their frames. It stops after the largest grid
+// or once a stage drops below `MIN_FPS`, well past the point of interest.
+#[derive(Debug)]
+pub struct Bench {
+    frames_per_stage: u64,
+    stage: u32,
+    // Rendered in this stage so far, including the warmup
+    frames: u64,
+    elapsed: Duration,
+    encode_time: Duration,
+    last_frame: Option<Instant>,
+    // The state's running total once the warmup was over
+    warm_buffer_allocations: u64,
+    finished: bool,
+}
+
+impl Bench {
+    pub const DEFAULT_FRAMES_PER_STAGE: u64 = 120;
+    // Skipped at the start of every stage, since those include growing the
+    // instance buffer
+    const WARMUP_FRAMES: u64 = 10;
+    const FIRST_STAGE_INSTANCES: usize = 64;
+    const MIN_FPS: f64 = 10.0;
+
+    pub fn new(frames_per_stage: u64) -> Self {
+        Bench {
+            frames_per_stage: frames_per_stage.max(1),
+            stage: 0,
+            frames: 0,
+            elapsed: Duration::ZERO,
+            encode_time: Duration::ZERO,
+            last_frame: None,
+            warm_buffer_allocations: 0,
+            finished: false,
+        }
+    }
+
+    // The side of the current stage's grid, rounded so the instance count
+    // only roughly doubles
+    fn side(&self) -> Option<usize> {
+        let instances = Self::FIRST_STAGE_INSTANCES.checked_shl(self.stage)?;
+        let side = (instances as f64).sqrt().round() as usize;
+        (side <= Grid::MAX_SIZE).then_some(side)
+    }
+
+    // The grid to render for the current stage, or `None` once it's over.
+    // Signals are left off, since settling a grid this size would take far
+    // longer than drawing it.
+    pub fn game(&self) -> Option<Game> {
+        if self.finished {
+            return None;
+        }
+
+        let side = self.side()?;
+        let cells = [
+            Cell::Wire,
+            Cell::And,
+            Cell::Wire,
+            Cell::Or,
+            Cell::Wire,
+            Cell::Not,
+            Cell::Source,
+            Cell::Sink,
+            Cell::Empty,
+        ];
+        let mut game = Game::new(side, side);
+        for y in 0..side {
+            for x in 0..side {
+                game.grid.set(x, y, cells[(x + y) % cells.len()]);
+            }
+        }
+        Some(game)
+    }
+
+    // Call once a frame has been rendered. Returns the stage's result once it
+    // has rendered enough frames, after which `game` has the next stage's grid.
+    // `buffer_allocations` is the state's running total, and `stats` what the
+    // frame's render returned.
+    pub fn frame_rendered(
+        &mut self,
+        now: Instant,
+        buffer_allocations: u64,
+        stats: RenderStats,
+    ) -> Option<BenchResult> {
+        let last_frame = self.last_frame.replace(now);
+        self.frames += 1;
+        if self.frames <= Self::WARMUP_FRAMES {
+            self.warm_buffer_allocations = buffer_allocations;
+            return None;
+        }
+        self.elapsed += now.duration_since(last_frame?);
+        self.encode_time += stats.encode_time;
+        if self.frames < Self::WARMUP_FRAMES + self.frames_per_stage {
+            return None;
+        }
+
+        let side = self.side()?;
+        let result = BenchResult {
+            instances: side * side,
+            frame_time: self.elapsed / self.frames_per_stage as u32,
+            buffer_allocations: buffer_allocations - self.warm_buffer_allocations,
+            draw_calls: stats.draw_calls,
+            triangles: stats.triangles,
+            encode_time: self.encode_time / self.frames_per_stage as u32,
+        };
+
+        self.stage += 1;
+        self.frames = 0;
+        self.elapsed = Duration::ZERO;
+        self.encode_time = Duration::ZERO;
+        self.finished = result.fps() < Self::MIN_FPS || self.side().is_none();
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages_double_the_instances_until_too_slow() {
+        let mut bench = Bench::new(2);
+        let mut now = Instant::now();
+        // One allocation a frame during the warmup, then one more during the
+        // timed frames
+        let mut allocations = 0;
+        let mut run_stage = |bench: &mut Bench, frame_time: Duration| loop {
+            now += frame_time;
+            if bench.frames < Bench::WARMUP_FRAMES + 1 {
+                allocations += 1;
+            }
+            let stats = RenderStats {
+                draw_calls: 2,
+                instances: 65,
+                triangles: 130,
+                encode_time: Duration::from_millis(1),
+            };
+            if let Some(result) = bench.frame_rendered(now, allocations, stats) {
+                return result;
+            }
+        };
+
+        assert_eq!(bench.game().unwrap().grid.width(), 8);
+        let result = run_stage(&mut bench, Duration::from_millis(4));
+        assert_eq!(result.instances, 64);
+        assert_eq!(result.frame_time, Duration::from_millis(4));
+        assert_eq!(result.buffer_allocations, 1);
+        assert_eq!(result.csv_row(), "64,4.000,250.0,1,2,130,1.000");
+
+        // 128 cells round to an 11x11 grid
+        assert_eq!(bench.game().unwrap().grid.width(), 11);
+        let result = run_stage(&mut bench, Duration::from_millis(200));
+        assert_eq!(result.instances, 121);
+        assert!(bench.game().is_none());
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Identity of something a bind group entry points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceId {
+    Buffer {
+        id: wgpu::Id<wgpu::Buffer>,
+        offset: wgpu::BufferAddress,
+        size: Option<wgpu::BufferSize>,
+    },
+    TextureView(wgpu::Id<wgpu::TextureView>),
+    Sampler(wgpu::Id<wgpu::Sampler>),
+}
+
+impl ResourceId {
+    // Resource kinds this doesn't know about can't be told apart, so bind
+    // groups using them aren't cached
+    fn from_resource(resource: &wgpu::BindingResource) -> Option<Vec<Self>> {
+        let buffer = |binding: &wgpu::BufferBinding| ResourceId::Buffer {
+            id: binding.buffer.global_id(),
+            offset: binding.offset,
+            size: binding.size,
+        };
+
+        let ids = match resource {
+            wgpu::BindingResource::Buffer(binding) => vec![buffer(binding)],
+            wgpu::BindingResource::BufferArray(bindings) => bindings.iter().map(buffer).collect(),
+            wgpu::BindingResource::TextureView(view) => {
+                vec![ResourceId::TextureView(view.global_id())]
+            }
+            wgpu::BindingResource::TextureViewArray(views) => views
+                .iter()
+                .map(|view| ResourceId::TextureView(view.global_id()))
+                .collect(),
+            wgpu::BindingResource::Sampler(sampler) => {
+                vec![ResourceId::Sampler(sampler.global_id())]
+            }
+            wgpu::BindingResource::SamplerArray(samplers) => samplers
+                .iter()
+                .map(|sampler| ResourceId::Sampler(sampler.global_id()))
+                .collect(),
+            _ => return None,
+        };
+        Some(ids)
+    }
+
+    // Matches any binding of the resource, whatever the offset and size
+    fn refers_to(&self, other: &ResourceId) -> bool {
+        match (self, other) {
+            (ResourceId::Buffer { id, .. }, ResourceId::Buffer { id: other, .. }) => id == other,
+            _ => self == other,
+        }
+    }
+}
+
+impl From<&wgpu::Buffer> for ResourceId {
+    fn from(buffer: &wgpu::Buffer) -> Self {
+        ResourceId::Buffer {
+            id: buffer.global_id(),
+            offset: 0,
+            size: None,
+        }
+    }
+}
+
+impl From<&wgpu::TextureView> for ResourceId {
+    fn from(view: &wgpu::TextureView) -> Self {
+        ResourceId::TextureView(view.global_id())
+    }
+}
+
+impl From<&wgpu::Sampler> for ResourceId {
+    fn from(sampler: &wgpu::Sampler) -> Self {
+        ResourceId::Sampler(sampler.global_id())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BindGroupKey {
+    layout: wgpu::Id<wgpu::BindGroupLayout>,
+    entries: Vec<(u32, Vec<ResourceId>)>,
+}
+
+// Hands out one shared bind group per layout and set of resources, so asking
+// for the same bindings again (every frame, or from several places) doesn't
+// create a new one.
+//
+// Groups are looked up by the identity of the layout and of every bound
+// buffer, texture view and sampler, never by their contents:
+// - Writing new data into a bound buffer or texture needs no invalidation,
+//   the cached group already sees it.
+// - Recreating a resource (e.g. a render target on resize, or swapping in a
+//   new texture) gives it a new identity, so the next lookup creates a fresh
+//   group on its own. The old group can never be returned for the new one.
+// - The cache keeps every group it hands out, and a group keeps everything it
+//   binds alive. Whoever replaces a resource must `invalidate` the old one,
+//   or it and its groups stay in memory until the cache is dropped.
+// Callers can keep the returned `Rc` after invalidating, it still refers to
+// the old resources and stays valid until dropped.
+#[derive(Debug, Default)]
+pub struct BindGroupCache {
+    groups: HashMap<BindGroupKey, Rc<wgpu::BindGroup>>,
+}
+
+impl BindGroupCache {
+    pub fn get_or_create(
+        &mut self,
+        device: &wgpu::Device,
+        descriptor: &wgpu::BindGroupDescriptor,
+    ) -> Rc<wgpu::BindGroup> {
+        let entries = descriptor
+            .entries
+            .iter()
+            .map(|entry| Some((entry.binding, ResourceId::from_resource(&entry.resource)?)))
+            .collect::<Option<_>>();
+        let Some(entries) = entries else {
+            return Rc::new(device.create_bind_group(descriptor));
+        };
+        let key = BindGroupKey {
+            layout: descriptor.layout.global_id(),
+            entries,
+        };
+
+        self.groups
+            .entry(key)
+            .or_insert_with(|| Rc::new(device.create_bind_group(descriptor)))
+            .clone()
+    }
+
+    // Drops every cached group that binds the resource, as a buffer at any
+    // offset or as a texture view or sampler
+    pub fn invalidate(&mut self, resource: impl Into<ResourceId>) {
+        let resource = resource.into();
+        self.groups.retain(|key, _| {
+            !key.entries
+                .iter()
+                .flat_map(|(_, resources)| resources)
+                .any(|cached| cached.refers_to(&resource))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pollster::FutureExt;
+    use wgpu::util::DeviceExt;
+
+    use super::*;
+
+    #[test]
+    fn groups_are_reused_until_invalidated() {
+        let instance = wgpu::Instance::default();
+        let Some(adapter) = instance
+            .request_adapter(&wgpu::RequestAdapterOptions::default())
+            .block_on()
+        else {
+            // Machines without any adapter (not even a software one) can't run this
+            return;
+        };
+        let (device, _queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .block_on()
+            .unwrap();
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let create_buffer = || {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: &[0; 16],
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+        };
+        let get = |cache: &mut BindGroupCache, buffer: &wgpu::Buffer| {
+            cache.get_or_create(
+                &device,
+                &wgpu::BindGroupDescriptor {
+                    label: None,
+                    layout: &layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                },
+            )
+        };
+
+        let mut cache = BindGroupCache::default();
+        let buffer = create_buffer();
+        let first = get(&mut cache, &buffer);
+        assert!(Rc::ptr_eq(&first, &get(&mut cache, &buffer)));
+        assert_eq!(cache.groups.len(), 1);
+
+        // A recreated buffer gets its own group, and invalidating the old one
+        // only drops the old group
+        let recreated = create_buffer();
+        let second = get(&mut cache, &recreated);
+        assert!(!Rc::ptr_eq(&first, &second));
+        cache.invalidate(&buffer);
+        assert_eq!(cache.groups.len(), 1);
+        assert!(Rc::ptr_eq(&second, &get(&mut cache, &recreated)));
+    }
+}
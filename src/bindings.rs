@@ -0,0 +1,276 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+use winit::keyboard::KeyCode;
+
+// Everything the keyboard can do that players can rebind. Ctrl shortcuts,
+// Escape and F11 stay fixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Place,
+    Rotate,
+    Clear,
+    Deselect,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    FitGrid,
+    Pause,
+    ToggleVsync,
+    FrameLatency,
+    Screenshot,
+    FrameGraph,
+    Wireframe,
+    Msaa,
+    Letterbox,
+    Console,
+    Palette,
+    StepMode,
+    StepSignals,
+    SpeedUp,
+    SlowDown,
+    Checkerboard,
+    Inspector,
+    Minimap,
+}
+
+// How an action treats its key being held down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    // Switches something on or off, so only the first press counts, and a
+    // press straight after the last one is taken for a bounce
+    Toggle,
+    // Runs once per press
+    Press,
+    // Runs again with each key repeat, like moving the selection
+    Repeat,
+    // Lasts for as long as the key is down, like panning
+    Held,
+}
+
+impl Action {
+    pub fn kind(self) -> ActionKind {
+        use Action::*;
+
+        match self {
+            MoveUp | MoveDown | MoveLeft | MoveRight => ActionKind::Repeat,
+            PanUp | PanDown | PanLeft | PanRight => ActionKind::Held,
+            Pause | ToggleVsync | FrameGraph | Wireframe | Msaa | Letterbox | Console
+            | StepMode | Checkerboard | Minimap => ActionKind::Toggle,
+            Place | Rotate | Clear | Deselect | FitGrid | FrameLatency | Screenshot | Palette
+            | StepSignals | SpeedUp | SlowDown | Inspector => ActionKind::Press,
+        }
+    }
+}
+
+// Lets each key through at most once per `interval`, so a double tap or a
+// bouncing switch doesn't flip a toggle straight back
+#[derive(Debug)]
+pub struct Debounce<K> {
+    pub interval: Duration,
+    last: HashMap<K, Instant>,
+}
+
+impl<K: Eq + Hash> Debounce<K> {
+    pub fn new(interval: Duration) -> Self {
+        Debounce {
+            interval,
+            last: HashMap::new(),
+        }
+    }
+
+    // Whether `key` pressed at `now` goes through. Presses that don't aren't
+    // remembered, so holding off for `interval` after the last one that did
+    // is always enough.
+    pub fn fire(&mut self, key: K, now: Instant) -> bool {
+        if let Some(&last) = self.last.get(&key) {
+            if now.saturating_duration_since(last) < self.interval {
+                return false;
+            }
+        }
+        self.last.insert(key, now);
+        true
+    }
+}
+
+// Maps each action to the keys that trigger it, stored in the settings file
+// as e.g. `"pause": ["KeyP", "F9"]`. Actions missing from the file keep
+// their default keys, and an empty list unbinds an action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    from = "BTreeMap<Action, Vec<KeyCode>>",
+    into = "BTreeMap<Action, Vec<KeyCode>>"
+)]
+pub struct KeyBindings {
+    keys: BTreeMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use Action::*;
+
+        let keys = [
+            (MoveUp, vec![KeyCode::KeyW]),
+            (MoveDown, vec![KeyCode::KeyS]),
+            (MoveLeft, vec![KeyCode::KeyA]),
+            (MoveRight, vec![KeyCode::KeyD]),
+            (
+                Place,
+                vec![KeyCode::Enter, KeyCode::NumpadEnter, KeyCode::Space],
+            ),
+            (Rotate, vec![KeyCode::KeyR]),
+            (Clear, vec![KeyCode::Delete]),
+            (Deselect, vec![KeyCode::Backspace]),
+            (PanUp, vec![KeyCode::ArrowUp]),
+            (PanDown, vec![KeyCode::ArrowDown]),
+            (PanLeft, vec![KeyCode::ArrowLeft]),
+            (PanRight, vec![KeyCode::ArrowRight]),
+            (FitGrid, vec![KeyCode::KeyF]),
+            (Pause, vec![KeyCode::KeyP]),
+            (ToggleVsync, vec![KeyCode::KeyV]),
+            (FrameLatency, vec![KeyCode::F6]),
+            (Screenshot, vec![KeyCode::F2]),
+            (FrameGraph, vec![KeyCode::F3]),
+            (Wireframe, vec![KeyCode::F4]),
+            (Msaa, vec![KeyCode::F5]),
+            (Letterbox, vec![KeyCode::KeyL]),
+            (Console, vec![KeyCode::Backquote]),
+            (Palette, vec![KeyCode::KeyC]),
+            (StepMode, vec![KeyCode::KeyM]),
+            (StepSignals, vec![KeyCode::Period]),
+            (SpeedUp, vec![KeyCode::Equal, KeyCode::NumpadAdd]),
+            (SlowDown, vec![KeyCode::Minus, KeyCode::NumpadSubtract]),
+            (Checkerboard, vec![KeyCode::KeyB]),
+            (Inspector, vec![KeyCode::KeyI]),
+            (Minimap, vec![KeyCode::KeyO]),
+        ];
+        KeyBindings {
+            keys: keys.into_iter().collect(),
+        }
+    }
+}
+
+impl From<BTreeMap<Action, Vec<KeyCode>>> for KeyBindings {
+    fn from(overrides: BTreeMap<Action, Vec<KeyCode>>) -> Self {
+        let mut bindings = KeyBindings::default();
+        bindings.keys.extend(overrides);
+        bindings
+    }
+}
+
+impl From<KeyBindings> for BTreeMap<Action, Vec<KeyCode>> {
+    fn from(bindings: KeyBindings) -> Self {
+        bindings.keys
+    }
+}
+
+impl KeyBindings {
+    // If a key is bound to several actions, the first in `Action` order wins
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        self.keys
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(&action, _)| action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    // Keys bound to more than one action, each with its actions in `Action`
+    // order, so the first is the one `action` picks
+    pub fn clashes(&self) -> Vec<(KeyCode, Vec<Action>)> {
+        let mut clashes: Vec<(KeyCode, Vec<Action>)> = Vec::new();
+        for (&action, keys) in &self.keys {
+            for &key in keys {
+                match clashes.iter_mut().find(|(bound, _)| *bound == key) {
+                    Some((_, actions)) => actions.push(action),
+                    None => clashes.push((key, vec![action])),
+                }
+            }
+        }
+        clashes.retain(|(_, actions)| actions.len() > 1);
+        clashes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_bindings_override_the_defaults() {
+        let json = r#"{
+            "move_up": ["ArrowUp", "KeyI"],
+            "pan_up": [],
+            "pause": ["Escape"]
+        }"#;
+        let bindings: KeyBindings = serde_json::from_str(json).unwrap();
+
+        let mut expected = KeyBindings::default().keys;
+        expected.insert(Action::MoveUp, vec![KeyCode::ArrowUp, KeyCode::KeyI]);
+        expected.insert(Action::PanUp, vec![]);
+        expected.insert(Action::Pause, vec![KeyCode::Escape]);
+        assert_eq!(bindings.keys, expected);
+
+        assert_eq!(bindings.action(KeyCode::ArrowUp), Some(Action::MoveUp));
+        assert_eq!(bindings.action(KeyCode::KeyW), None);
+        assert_eq!(bindings.action(KeyCode::KeyA), Some(Action::MoveLeft));
+        assert!(bindings.keys(Action::PanUp).is_empty());
+    }
+
+    #[test]
+    fn keys_bound_to_several_actions_are_found() {
+        assert!(KeyBindings::default().clashes().is_empty());
+
+        let json = r#"{ "step_mode": ["KeyO", "KeyM"], "letterbox": ["KeyM"] }"#;
+        let bindings: KeyBindings = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            bindings.clashes(),
+            vec![
+                (KeyCode::KeyM, vec![Action::Letterbox, Action::StepMode]),
+                (KeyCode::KeyO, vec![Action::StepMode, Action::Minimap]),
+            ]
+        );
+    }
+
+    #[test]
+    fn enter_and_space_cycle_the_selected_cell() {
+        let bindings = KeyBindings::default();
+        assert_eq!(bindings.action(KeyCode::Enter), Some(Action::Place));
+        assert_eq!(bindings.action(KeyCode::Space), Some(Action::Place));
+        assert_eq!(bindings.action(KeyCode::KeyP), Some(Action::Pause));
+    }
+
+    #[test]
+    fn toggles_ignore_presses_that_come_too_soon() {
+        let mut debounce = Debounce::new(Duration::from_millis(100));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        assert!(debounce.fire(Action::Pause, at(0)));
+        assert!(!debounce.fire(Action::Pause, at(50)));
+        assert!(debounce.fire(Action::ToggleVsync, at(50)));
+        assert!(debounce.fire(Action::Pause, at(100)));
+        assert!(!debounce.fire(Action::Pause, at(150)));
+
+        assert_eq!(Action::Pause.kind(), ActionKind::Toggle);
+        assert_eq!(Action::MoveUp.kind(), ActionKind::Repeat);
+        assert_eq!(Action::PanUp.kind(), ActionKind::Held);
+    }
+
+    #[test]
+    fn unknown_actions_are_rejected() {
+        assert!(serde_json::from_str::<KeyBindings>(r#"{ "fly": ["KeyF"] }"#).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+// `position` and `zoom` are what's on screen. Panning and zooming move the
+// targets instead, and `update` eases the view towards them.
+pub struct Camera2D {
+    pub position: [f32; 2],
+    pub zoom: f32,
+    pub target_position: [f32; 2],
+    pub target_zoom: f32,
+}
+
+impl Default for Camera2D {
+    fn default() -> Self {
+        Camera2D {
+            position: [0.0, 0.0],
+            zoom: 1.0,
+            target_position: [0.0, 0.0],
+            target_zoom: 1.0,
+        }
+    }
+}
+
+impl Camera2D {
+    // Far enough out to fit the largest grid, and close enough in for a
+    // single cell to fill the view
+    pub const MIN_ZOOM: f32 = 0.001;
+    pub const MAX_ZOOM: f32 = 2.0;
+    // How quickly the view catches up with its targets, per second
+    const SMOOTHING: f32 = 15.0;
+
+    // Moves the view straight to the given position and zoom
+    pub fn snap_to(&mut self, position: [f32; 2], zoom: f32) {
+        self.target_position = position;
+        self.target_zoom = zoom.clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        self.position = self.target_position;
+        self.zoom = self.target_zoom;
+    }
+
+    // Eases the view over to the given position and zoom
+    pub fn move_to(&mut self, position: [f32; 2], zoom: f32) {
+        self.target_position = position;
+        self.target_zoom = zoom.clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+    }
+
+    // The zoom at which a world-space `size` just fits a view of the given
+    // aspect ratio, along whichever axis is tighter
+    pub fn zoom_to_fit(size: [f32; 2], aspect: f32) -> f32 {
+        (2.0 / size[1]).min(2.0 * aspect / size[0])
+    }
+
+    pub fn pan(&mut self, delta: [f32; 2]) {
+        self.target_position[0] += delta[0];
+        self.target_position[1] += delta[1];
+    }
+
+    // Scales the target zoom by `factor` while keeping the world point under
+    // `ndc` in place, so zooming follows the cursor
+    pub fn zoom_towards(&mut self, ndc: (f32, f32), aspect: f32, factor: f32) {
+        let before = Self::unproject(self.target_position, self.target_zoom, ndc, aspect);
+        self.target_zoom = (self.target_zoom * factor).clamp(Self::MIN_ZOOM, Self::MAX_ZOOM);
+        let after = Self::unproject(self.target_position, self.target_zoom, ndc, aspect);
+        self.pan([before[0] - after[0], before[1] - after[1]]);
+    }
+
+    // Zoom is eased in log space so zooming in and out feel the same
+    pub fn update(&mut self, dt: f32) {
+        let t = 1.0 - (-Self::SMOOTHING * dt).exp();
+        for i in 0..2 {
+            self.position[i] += (self.target_position[i] - self.position[i]) * t;
+        }
+        self.zoom *= (self.target_zoom / self.zoom).powf(t);
+    }
+
+    // At a zoom of 1.0 the view spans -1..1 vertically and -aspect..aspect
+    // horizontally around the camera position. Depth is mapped from -1..1 in
+    // world space to wgpu's 0..1 range so layered content can use z.
+    pub fn build_view_projection_matrix(&self, aspect: f32) -> [[f32; 4]; 4] {
+        let scale_x = self.zoom / aspect;
+        let scale_y = self.zoom;
+
+        // Column-major, matching WGSL's mat4x4<f32>
+        [
+            [scale_x, 0.0, 0.0, 0.0],
+            [0.0, scale_y, 0.0, 0.0],
+            [0.0, 0.0, 0.5, 0.0],
+            [
+                -self.position[0] * scale_x,
+                -self.position[1] * scale_y,
+                0.5,
+                1.0,
+            ],
+        ]
+    }
+
+    // Inverse of `build_view_projection_matrix` for a point at z = 0
+    pub fn ndc_to_world(&self, ndc: (f32, f32), aspect: f32) -> [f32; 2] {
+        Self::unproject(self.position, self.zoom, ndc, aspect)
+    }
+
+    fn unproject(position: [f32; 2], zoom: f32, ndc: (f32, f32), aspect: f32) -> [f32; 2] {
+        [
+            ndc.0 * aspect / zoom + position[0],
+            ndc.1 / zoom + position[1],
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zooming_keeps_the_point_under_the_cursor() {
+        let mut camera = Camera2D::default();
+        camera.snap_to([3.0, -2.0], 0.5);
+        let (ndc, aspect) = ((0.4, -0.7), 1.5);
+        let before = camera.ndc_to_world(ndc, aspect);
+
+        camera.zoom_towards(ndc, aspect, 1.5);
+        for _ in 0..200 {
+            camera.update(1.0 / 60.0);
+        }
+
+        let after = camera.ndc_to_world(ndc, aspect);
+        assert!((camera.zoom - 0.75).abs() < 1e-4);
+        assert!((before[0] - after[0]).abs() < 1e-3);
+        assert!((before[1] - after[1]).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fitting_zooms_to_the_tighter_axis() {
+        // A wide view fits a square by its height, and a wide rectangle by
+        // its width
+        assert_eq!(Camera2D::zoom_to_fit([4.0, 4.0], 2.0), 0.5);
+        assert_eq!(Camera2D::zoom_to_fit([16.0, 4.0], 2.0), 0.25);
+
+        let mut camera = Camera2D::default();
+        camera.snap_to([0.0, 0.0], 1.0);
+        camera.move_to([2.0, 2.0], 0.5);
+        for _ in 0..200 {
+            camera.update(1.0 / 60.0);
+        }
+        let view_max = camera.ndc_to_world((1.0, 1.0), 2.0);
+        assert!((view_max[0] - 6.0).abs() < 1e-3);
+        assert!((view_max[1] - 4.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn zoom_is_clamped() {
+        let mut camera = Camera2D::default();
+        camera.zoom_towards((0.0, 0.0), 1.0, 1.0e6);
+        assert_eq!(camera.target_zoom, Camera2D::MAX_ZOOM);
+        camera.zoom_towards((0.0, 0.0), 1.0, 1.0e-9);
+        assert_eq!(camera.target_zoom, Camera2D::MIN_ZOOM);
+    }
+}
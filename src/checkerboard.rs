@@ -0,0 +1,155 @@
+use wgpu::util::DeviceExt;
+
+use crate::{render_stats::RenderStats, texture::Texture};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CheckerboardUniform {
+    colors: [[f32; 4]; 2],
+}
+
+// A checkerboard behind the world, one square per cell so the grid stays
+// visible where nothing is drawn. It's computed per pixel from the camera,
+// so it pans and zooms along with the cells without any geometry.
+pub struct Checkerboard {
+    shader: wgpu::ShaderModule,
+    layout: wgpu::PipelineLayout,
+    pipeline: wgpu::RenderPipeline,
+    format: wgpu::TextureFormat,
+    colors_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Checkerboard {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let colors_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("WGPU Checkerboard Buffer"),
+            contents: bytemuck::cast_slice(&[CheckerboardUniform {
+                colors: [[0.0; 4]; 2],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("WGPU Checkerboard Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("WGPU Checkerboard Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: colors_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("checkerboard.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WGPU Checkerboard Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(device, &layout, &shader, format, sample_count);
+
+        Self {
+            shader,
+            layout,
+            pipeline,
+            format,
+            colors_buffer,
+            bind_group,
+        }
+    }
+
+    // Drawn first in the world pass, so it has to match its depth attachment
+    // and sample count, but it never writes depth
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("WGPU Checkerboard Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipeline = Self::create_pipeline(
+            device,
+            &self.layout,
+            &self.shader,
+            self.format,
+            sample_count,
+        );
+    }
+
+    pub fn set_colors(&self, queue: &wgpu::Queue, colors: [[f32; 4]; 2]) {
+        queue.write_buffer(
+            &self.colors_buffer,
+            0,
+            bytemuck::cast_slice(&[CheckerboardUniform { colors }]),
+        );
+    }
+
+    pub fn render<'p>(
+        &'p self,
+        render_pass: &mut wgpu::RenderPass<'p>,
+        camera_bind_group: &'p wgpu::BindGroup,
+        stats: &mut RenderStats,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        stats.record_draw(3, 1);
+    }
+}
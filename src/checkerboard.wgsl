@@ -0,0 +1,36 @@
+@group(0) @binding(0)
+var<uniform> view_proj: mat4x4<f32>;
+
+struct Checkerboard {
+    colors: array<vec4<f32>, 2>,
+};
+@group(1) @binding(0)
+var<uniform> checkerboard: Checkerboard;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec2<f32>,
+};
+
+// A single triangle covering the whole viewport, with no vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let ndc = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u)) * 2.0 - 1.0;
+
+    // The camera only scales and translates, so that can be undone directly
+    let scale = vec2<f32>(view_proj[0][0], view_proj[1][1]);
+    let offset = view_proj[3].xy;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    out.world_position = (ndc - offset) / scale;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Cell (x, y) spans x..x + 1 and y..y + 1, like the grid
+    let cell = floor(in.world_position);
+    let odd = u32(abs(cell.x + cell.y)) % 2u;
+    return checkerboard.colors[odd];
+}
@@ -0,0 +1,222 @@
+use std::{collections::VecDeque, error::Error, fmt};
+
+use crate::{grid::Grid, puzzle::Challenge};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    // Clears the console's output
+    Clear,
+    // Prints the current frame rate
+    Fps,
+    // Generates a new puzzle from the seed, keeping the grid size
+    Seed(u64),
+    // Replaces the circuit with an empty grid of this size
+    Grid { width: usize, height: usize },
+    // Grows or shrinks the grid, keeping the circuit
+    Resize { width: usize, height: usize },
+    // Starts one of the built-in puzzles, by the gate it asks for
+    Challenge(Challenge),
+    // Prints the truth table of the selected region
+    Truth,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    Unknown(String),
+    MissingArgument(&'static str),
+    InvalidArgument { name: &'static str, value: String },
+    UnexpectedArgument(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Unknown(name) => write!(f, "Unknown command: {}", name),
+            CommandError::MissingArgument(name) => write!(f, "Missing argument: {}", name),
+            CommandError::InvalidArgument { name, value } => {
+                write!(f, "Invalid {}: {}", name, value)
+            }
+            CommandError::UnexpectedArgument(value) => {
+                write!(f, "Unexpected argument: {}", value)
+            }
+        }
+    }
+}
+
+impl Error for CommandError {}
+
+impl Command {
+    // Commands are a name followed by whitespace-separated arguments.
+    // Returns `None` for a blank line.
+    pub fn parse(line: &str) -> Option<Result<Command, CommandError>> {
+        let mut words = line.split_whitespace();
+        let name = words.next()?;
+
+        let command = match name {
+            "clear" => Ok(Command::Clear),
+            "fps" => Ok(Command::Fps),
+            "truth" => Ok(Command::Truth),
+            "seed" => Self::argument(&mut words, "seed", |_| true).map(Command::Seed),
+            "grid" => Self::argument(&mut words, "width", Self::valid_size).and_then(|width| {
+                Self::argument(&mut words, "height", Self::valid_size)
+                    .map(|height| Command::Grid { width, height })
+            }),
+            "resize" => Self::argument(&mut words, "width", Self::valid_size).and_then(|width| {
+                Self::argument(&mut words, "height", Self::valid_size)
+                    .map(|height| Command::Resize { width, height })
+            }),
+            "challenge" => {
+                Self::argument(&mut words, "challenge", |_| true).map(Command::Challenge)
+            }
+            _ => Err(CommandError::Unknown(name.to_string())),
+        };
+
+        Some(command.and_then(|command| match words.next() {
+            Some(extra) => Err(CommandError::UnexpectedArgument(extra.to_string())),
+            None => Ok(command),
+        }))
+    }
+
+    fn argument<'w, T: std::str::FromStr>(
+        words: &mut impl Iterator<Item = &'w str>,
+        name: &'static str,
+        valid: impl Fn(&T) -> bool,
+    ) -> Result<T, CommandError> {
+        let value = words.next().ok_or(CommandError::MissingArgument(name))?;
+        value
+            .parse()
+            .ok()
+            .filter(valid)
+            .ok_or_else(|| CommandError::InvalidArgument {
+                name,
+                value: value.to_string(),
+            })
+    }
+
+    fn valid_size(size: &usize) -> bool {
+        (1..=Grid::MAX_SIZE).contains(size)
+    }
+}
+
+// A drop-down console with one input line and a short scrollback of output.
+// `State` feeds it text while it's open and runs the commands it returns.
+#[derive(Debug, Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    output: VecDeque<String>,
+}
+
+impl Console {
+    // Older output lines are dropped
+    pub const MAX_OUTPUT_LINES: usize = 8;
+    pub const PROMPT: &'static str = "> ";
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    // Control characters (like the ones winit reports for Enter or
+    // Backspace) are ignored, as is the backtick that toggles the console
+    pub fn push_text(&mut self, text: &str) {
+        self.input
+            .extend(text.chars().filter(|c| !c.is_control() && *c != '`'));
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    // Echoes the input line to the output and parses it as a command
+    pub fn submit(&mut self) -> Option<Result<Command, CommandError>> {
+        let line = std::mem::take(&mut self.input);
+        if line.trim().is_empty() {
+            return None;
+        }
+
+        self.print(format!("{}{}", Self::PROMPT, line));
+        Command::parse(&line)
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        if self.output.len() == Self::MAX_OUTPUT_LINES {
+            self.output.pop_front();
+        }
+        self.output.push_back(line.into());
+    }
+
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn output(&self) -> impl Iterator<Item = &str> {
+        self.output.iter().map(String::as_str)
+    }
+
+    pub fn num_output_lines(&self) -> usize {
+        self.output.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!(Command::parse("  "), None);
+        assert_eq!(Command::parse("clear"), Some(Ok(Command::Clear)));
+        assert_eq!(Command::parse(" fps "), Some(Ok(Command::Fps)));
+        assert_eq!(Command::parse("seed 42"), Some(Ok(Command::Seed(42))));
+        assert_eq!(Command::parse("truth"), Some(Ok(Command::Truth)));
+        assert_eq!(
+            Command::parse("grid 32 16"),
+            Some(Ok(Command::Grid {
+                width: 32,
+                height: 16
+            }))
+        );
+        assert_eq!(
+            Command::parse("resize 8 4"),
+            Some(Ok(Command::Resize {
+                width: 8,
+                height: 4
+            }))
+        );
+        assert_eq!(
+            Command::parse("challenge xor"),
+            Some(Ok(Command::Challenge(Challenge::Xor)))
+        );
+    }
+
+    #[test]
+    fn bad_commands_are_rejected() {
+        assert_eq!(
+            Command::parse("jump"),
+            Some(Err(CommandError::Unknown("jump".to_string())))
+        );
+        assert_eq!(
+            Command::parse("seed"),
+            Some(Err(CommandError::MissingArgument("seed")))
+        );
+        assert_eq!(
+            Command::parse("grid 32 0"),
+            Some(Err(CommandError::InvalidArgument {
+                name: "height",
+                value: "0".to_string()
+            }))
+        );
+        assert_eq!(
+            Command::parse("fps now"),
+            Some(Err(CommandError::UnexpectedArgument("now".to_string())))
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use std::marker::PhantomData;
+
+// A GPU buffer for data whose length changes from frame to frame. It's only
+// reallocated once the data outgrows it, to the next power of two so a
+// growing queue settles after a few frames, and written in place otherwise.
+pub struct DynamicBuffer<T> {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    len: u32,
+    // Including the first one, so `--bench` can report the churn
+    allocations: u64,
+    _data: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> DynamicBuffer<T> {
+    // `COPY_DST` is added to `usage` for the writes
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        capacity: usize,
+    ) -> Self {
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        Self {
+            label,
+            usage,
+            buffer: Self::create_buffer(device, label, usage, capacity),
+            capacity,
+            len: 0,
+            allocations: 1,
+            _data: PhantomData,
+        }
+    }
+
+    fn create_buffer(
+        device: &wgpu::Device,
+        label: &str,
+        usage: wgpu::BufferUsages,
+        capacity: usize,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (capacity.max(1) * std::mem::size_of::<T>()) as wgpu::BufferAddress,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    // The capacity to reallocate to before `len` elements fit, if they don't
+    fn grown_capacity(capacity: usize, len: usize) -> Option<usize> {
+        (len > capacity).then(|| len.next_power_of_two())
+    }
+
+    // Replaces the contents with `data`
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[T]) {
+        if let Some(capacity) = Self::grown_capacity(self.capacity, data.len()) {
+            self.capacity = capacity;
+            self.buffer = Self::create_buffer(device, self.label, self.usage, capacity);
+            self.allocations += 1;
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(data));
+        self.len = data.len() as u32;
+    }
+
+    // How many elements the last write left in the buffer
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice(..)
+    }
+
+    pub fn allocations(&self) -> u64 {
+        self.allocations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_only_grow_when_the_data_outgrows_them() {
+        let grown = DynamicBuffer::<u32>::grown_capacity;
+        assert_eq!(grown(128, 0), None);
+        assert_eq!(grown(128, 128), None);
+        assert_eq!(grown(128, 129), Some(256));
+        assert_eq!(grown(0, 1), Some(1));
+        assert_eq!(grown(256, 1000), Some(1024));
+    }
+}
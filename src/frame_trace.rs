@@ -0,0 +1,103 @@
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+// Frame pacing written to a CSV file for `--trace`, to complement the frame
+// graph with something that can be looked at offline. Rows are formatted into
+// a reused line and buffered, so tracing adds next to nothing to a frame.
+pub struct FrameTrace<W: Write = File> {
+    writer: BufWriter<W>,
+    line: String,
+    frame: u64,
+}
+
+impl FrameTrace {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(File::create(path)?)
+    }
+}
+
+impl<W: Write> FrameTrace<W> {
+    pub const CSV_HEADER: &'static str =
+        "frame,cpu_frame_ms,reconfigured,present_mode,frame_latency";
+    // Roomy enough for any row, so formatting one never allocates
+    const LINE_CAPACITY: usize = 64;
+    const BUFFER_CAPACITY: usize = 64 * 1024;
+
+    pub fn new(writer: W) -> io::Result<Self> {
+        let mut writer = BufWriter::with_capacity(Self::BUFFER_CAPACITY, writer);
+        writeln!(writer, "{}", Self::CSV_HEADER)?;
+        Ok(FrameTrace {
+            writer,
+            line: String::with_capacity(Self::LINE_CAPACITY),
+            frame: 0,
+        })
+    }
+
+    // Frames are numbered from 0 in the order they're recorded
+    pub fn record(
+        &mut self,
+        frame_time: Duration,
+        reconfigured: bool,
+        present_mode: wgpu::PresentMode,
+        frame_latency: u32,
+    ) -> io::Result<()> {
+        self.line.clear();
+        // Writing to a `String` can't fail
+        let _ = writeln!(
+            self.line,
+            "{},{:.3},{},{:?},{}",
+            self.frame,
+            frame_time.as_secs_f64() * 1000.0,
+            reconfigured as u8,
+            present_mode,
+            frame_latency
+        );
+        self.writer.write_all(self.line.as_bytes())?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_written_as_csv_rows() {
+        let mut trace = FrameTrace::new(Vec::new()).unwrap();
+        trace
+            .record(
+                Duration::from_micros(16_667),
+                false,
+                wgpu::PresentMode::Fifo,
+                2,
+            )
+            .unwrap();
+        trace
+            .record(
+                Duration::from_millis(40),
+                true,
+                wgpu::PresentMode::Immediate,
+                1,
+            )
+            .unwrap();
+        trace.flush().unwrap();
+
+        let csv = String::from_utf8(trace.writer.get_ref().clone()).unwrap();
+        assert_eq!(
+            csv,
+            "frame,cpu_frame_ms,reconfigured,present_mode,frame_latency\n\
+             0,16.667,0,Fifo,2\n\
+             1,40.000,1,Immediate,1\n"
+        );
+    }
+}
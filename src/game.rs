@@ -0,0 +1,1087 @@
+use std::{collections::VecDeque, time::Duration};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use winit::event::MouseButton;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{grid::DecodeError, puzzle::PuzzleError};
+use crate::{
+    grid::{Cell, Evaluation, Grid},
+    puzzle::{Challenge, Puzzle},
+    rng::Rng,
+};
+
+// Input forwarded from `State` after it has been translated out of window
+// space, so the game never needs to know about the surface or camera
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameInput {
+    Click {
+        button: MouseButton,
+        position: [f32; 2],
+    },
+    // A left press with Shift held, starting a region selection instead of a
+    // wire stroke
+    SelectRegion {
+        position: [f32; 2],
+    },
+    // The cursor moved while the left button was held
+    Drag {
+        position: [f32; 2],
+    },
+    Release {
+        button: MouseButton,
+    },
+    // Moves the selection by one cell, selecting the bottom-left cell if
+    // nothing is selected yet
+    MoveSelection {
+        dx: isize,
+        dy: isize,
+    },
+    CycleSelected,
+    // Turns the hovered gate, or the selected one if the cursor is off the
+    // grid, a quarter turn clockwise
+    Rotate,
+    // Empties the selected cell
+    ClearSelected,
+    Deselect,
+    Undo,
+    Redo,
+    Save,
+    Load,
+    // Generates a puzzle from the seed in a grid of the current size
+    NewPuzzle(u64),
+    // Replaces the circuit with an empty one to solve a built-in puzzle in
+    StartChallenge(Challenge),
+    NewGrid {
+        width: usize,
+        height: usize,
+    },
+    ResizeGrid {
+        width: usize,
+        height: usize,
+    },
+    Copy,
+    // Pastes at the hovered cell, or the selected one if the cursor is off
+    // the grid
+    Paste,
+    ToggleStepMode,
+    // Step through `Game::SIM_SPEEDS`
+    SpeedUp,
+    SlowDown,
+    // Advances the signals by one step, switching to step mode first if needed
+    StepSignals,
+}
+
+pub const CIRCUIT_PATH: &str = "save.circuit";
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Decode(DecodeError),
+    Puzzle(PuzzleError),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "An error occured while reading the circuit: {}", e),
+            LoadError::Decode(e) => write!(f, "The circuit is corrupt: {}", e),
+            LoadError::Puzzle(e) => write!(f, "The circuit's puzzle is corrupt: {}", e),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Decode(e) => Some(e),
+            LoadError::Puzzle(e) => Some(e),
+        }
+    }
+}
+
+// A single cell change, with enough information to apply it in either
+// direction. Both sides are a cell and its rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Edit {
+    position: (usize, usize),
+    old: (Cell, u8),
+    new: (Cell, u8),
+}
+
+// A left-button drag across the grid. Releasing without ever leaving the
+// first cell counts as a click on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stroke {
+    last: (usize, usize),
+    // Applied to the grid as the stroke goes, and recorded as one undo step
+    // when it ends
+    edits: Vec<Edit>,
+    moved: bool,
+}
+
+// A rectangle of cells, between two opposite corners picked in any order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl Region {
+    // The bottom-left and top-right corners, both inclusive
+    pub fn bounds(&self) -> ((usize, usize), (usize, usize)) {
+        (
+            (self.start.0.min(self.end.0), self.start.1.min(self.end.1)),
+            (self.start.0.max(self.end.0), self.start.1.max(self.end.1)),
+        )
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize)> {
+        let ((x0, y0), (x1, y1)) = self.bounds();
+        (y0..=y1).flat_map(move |y| (x0..=x1).map(move |x| (x, y)))
+    }
+}
+
+// Copied cells and their rotations in row order, pasted with the bottom-left
+// one at the target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clipboard {
+    width: usize,
+    height: usize,
+    cells: Vec<(Cell, u8)>,
+}
+
+// Logic-game data, independent of any GPU resources. Each cell occupies a
+// 1x1 square in world space, with cell (0, 0) spanning (0, 0)..(1, 1).
+pub struct Game {
+    pub grid: Grid,
+    pub selected_cell: Option<(usize, usize)>,
+    // The cell under the cursor, kept up to date by `State::update`
+    pub hovered_cell: Option<(usize, usize)>,
+    pub score: u32,
+    pub elapsed: Duration,
+    pub ticks: u64,
+    // How many fixed steps are taken per step's worth of real time, one of
+    // `SIM_SPEEDS`. At 0 the simulation stands still. At any speed but 1x,
+    // signals propagate one step at a time with the simulation rather than
+    // settling as soon as the circuit changes, so it can be watched.
+    pub sim_speed: f32,
+    // Each entry is one undo step, with its edits in the order they were made
+    undo_stack: VecDeque<Vec<Edit>>,
+    redo_stack: Vec<Vec<Edit>>,
+    stroke: Option<Stroke>,
+    pub region: Option<Region>,
+    // Whether the left button is still held after starting `region`
+    selecting_region: bool,
+    clipboard: Option<Clipboard>,
+    // While set, edits leave the signals alone and they only change through
+    // `step_signals`, one step at a time
+    step_mode: bool,
+    // The cells whose signal changed in the last step
+    pub changed_cells: Vec<(usize, usize)>,
+    // What the circuit should do, if it's a challenge. Its inputs and outputs
+    // can't be edited.
+    pub puzzle: Option<Puzzle>,
+    // Whether the circuit solved `puzzle` when it was last edited
+    solved: bool,
+}
+
+impl Default for Game {
+    // An 8x8 grid with a small demo circuit: two sources feeding an AND gate,
+    // and a source feeding an OR gate followed by a NOT gate
+    fn default() -> Self {
+        let mut game = Game::new(8, 8);
+        let grid = &mut game.grid;
+
+        for y in [4, 6] {
+            grid.set(0, y, Cell::Source);
+            grid.set(1, y, Cell::Wire);
+            grid.set(2, y, Cell::Wire);
+        }
+        grid.set(2, 5, Cell::And);
+        grid.set(3, 5, Cell::Wire);
+        grid.set(4, 5, Cell::Sink);
+
+        grid.set(0, 2, Cell::Source);
+        grid.set(1, 2, Cell::Wire);
+        grid.set(2, 2, Cell::Or);
+        grid.set(3, 2, Cell::Wire);
+        grid.set(4, 2, Cell::Not);
+        grid.set(5, 2, Cell::Wire);
+        grid.set(6, 2, Cell::Sink);
+
+        grid.evaluate();
+        game
+    }
+}
+
+impl Game {
+    // Edits beyond this many are forgotten, oldest first
+    const MAX_HISTORY: usize = 256;
+    // The simulation always advances in steps of this size, independently of
+    // the frame rate, so the same input produces the same result
+    pub const FIXED_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+    pub const SIM_SPEEDS: [f32; 6] = [0.0, 0.25, 0.5, 1.0, 2.0, 4.0];
+    pub const MAX_SIM_SPEED: f32 = 4.0;
+    // Fixed steps between each step of propagating signals, which at 1x would
+    // be 15 a second
+    const PROPAGATION_TICKS: u64 = 4;
+
+    pub fn new(width: usize, height: usize) -> Self {
+        Game {
+            grid: Grid::new(width, height),
+            selected_cell: None,
+            hovered_cell: None,
+            score: 0,
+            elapsed: Duration::ZERO,
+            ticks: 0,
+            sim_speed: 1.0,
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            stroke: None,
+            region: None,
+            selecting_region: false,
+            clipboard: None,
+            step_mode: false,
+            changed_cells: Vec::new(),
+            puzzle: None,
+            solved: false,
+        }
+    }
+
+    // An 8x8 puzzle generated from `seed`, which players can share to play the
+    // same puzzle
+    pub fn from_seed(seed: u64) -> Self {
+        let mut game = Game::new(8, 8);
+        game.grid.generate_puzzle(&mut Rng::new(seed));
+        game
+    }
+
+    pub fn center(&self) -> [f32; 2] {
+        [
+            self.grid.width() as f32 / 2.0,
+            self.grid.height() as f32 / 2.0,
+        ]
+    }
+
+    pub fn step(&mut self) {
+        self.ticks += 1;
+        self.elapsed += Self::FIXED_STEP;
+        if self.propagating() && self.ticks.is_multiple_of(Self::PROPAGATION_TICKS) {
+            self.grid.step_evaluate();
+        }
+    }
+
+    // Whether signals move with the simulation instead of settling after
+    // each edit. Step mode leaves them to `step_signals` instead.
+    fn propagating(&self) -> bool {
+        !self.step_mode && self.sim_speed != 1.0
+    }
+
+    // Stops at the slowest and fastest speeds
+    fn change_sim_speed(&mut self, faster: bool) {
+        let speed = if faster {
+            Self::SIM_SPEEDS
+                .into_iter()
+                .find(|&speed| speed > self.sim_speed)
+                .unwrap_or(Self::MAX_SIM_SPEED)
+        } else {
+            Self::SIM_SPEEDS
+                .into_iter()
+                .rev()
+                .find(|&speed| speed < self.sim_speed)
+                .unwrap_or(0.0)
+        };
+        self.sim_speed = speed;
+        // Whatever was still propagating settles straight away at 1x
+        if !self.propagating() {
+            self.evaluate();
+        }
+        log::info!("Running the simulation at {}x", speed);
+    }
+
+    pub fn step_mode(&self) -> bool {
+        self.step_mode
+    }
+
+    // Entering step mode turns every signal off so the whole circuit can be
+    // stepped through from the start. Leaving it settles the signals again.
+    pub fn set_step_mode(&mut self, step_mode: bool) {
+        self.step_mode = step_mode;
+        self.changed_cells.clear();
+        if step_mode {
+            self.grid.clear_signals();
+        } else {
+            self.grid.evaluate();
+        }
+        log::info!("{} step mode", if step_mode { "Entered" } else { "Left" });
+    }
+
+    pub fn step_signals(&mut self) {
+        if !self.step_mode {
+            self.set_step_mode(true);
+        }
+
+        let (width, height) = (self.grid.width(), self.grid.height());
+        let cells = || (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)));
+        let before: Vec<bool> = cells().map(|(x, y)| self.grid.signal(x, y)).collect();
+        if !self.grid.step_evaluate() {
+            log::info!("The circuit is stable");
+        }
+        self.changed_cells = cells()
+            .zip(before)
+            .filter(|&((x, y), was)| self.grid.signal(x, y) != was)
+            .map(|(cell, _)| cell)
+            .collect();
+    }
+
+    // Settles the signals after an edit, unless they're being stepped through
+    // or propagated. The puzzle is checked either way.
+    fn evaluate(&mut self) {
+        if !self.step_mode && !self.propagating() && self.grid.evaluate() == Evaluation::Oscillating
+        {
+            log::debug!("The circuit has a feedback loop that never settles");
+        }
+        self.update_solved();
+    }
+
+    // Whether the circuit gives the puzzle's expected outputs for all of its
+    // inputs. Always false without a puzzle.
+    pub fn check_solution(&self) -> bool {
+        self.puzzle
+            .as_ref()
+            .is_some_and(|puzzle| puzzle.is_solved_by(&self.grid))
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.solved
+    }
+
+    fn update_solved(&mut self) {
+        let solved = self.check_solution();
+        if solved && !self.solved {
+            log::info!("Solved the puzzle");
+        }
+        self.solved = solved;
+    }
+
+    pub fn start_challenge(&mut self, challenge: Challenge) {
+        let (grid, puzzle) = challenge.build();
+        self.replace_grid(grid);
+        self.puzzle = Some(puzzle);
+        self.evaluate();
+        log::info!("Started the {:?} challenge", challenge);
+    }
+
+    pub fn handle_input(&mut self, input: GameInput) {
+        match input {
+            GameInput::Click {
+                button: MouseButton::Left,
+                position: [x, y],
+            } => self.start_stroke(x, y),
+            GameInput::SelectRegion { position: [x, y] } => self.start_region(x, y),
+            GameInput::Drag { position: [x, y] } if self.selecting_region => {
+                self.continue_region(x, y)
+            }
+            GameInput::Drag { position: [x, y] } => self.continue_stroke(x, y),
+            GameInput::Release {
+                button: MouseButton::Left,
+            } => {
+                self.selecting_region = false;
+                self.end_stroke();
+            }
+            GameInput::Click {
+                button: MouseButton::Right,
+                ..
+            }
+            | GameInput::Deselect => {
+                self.selected_cell = None;
+                self.region = None;
+            }
+            GameInput::MoveSelection { dx, dy } => self.move_selection(dx, dy),
+            GameInput::CycleSelected => self.cycle_selected(),
+            GameInput::Rotate => self.rotate(),
+            GameInput::ClearSelected => {
+                if let Some((x, y)) = self.selected_cell {
+                    self.set_cell(x, y, Cell::Empty);
+                }
+            }
+            GameInput::Undo => self.undo(),
+            GameInput::Redo => self.redo(),
+            #[cfg(not(target_arch = "wasm32"))]
+            GameInput::Save => match self.save(CIRCUIT_PATH) {
+                Ok(()) => log::info!("Saved the circuit to {}", CIRCUIT_PATH),
+                Err(e) => log::error!("An error occured while saving the circuit: {}", e),
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            GameInput::Load => match self.load(CIRCUIT_PATH) {
+                Ok(()) => log::info!("Loaded the circuit from {}", CIRCUIT_PATH),
+                Err(e) => log::error!("{}", e),
+            },
+            #[cfg(target_arch = "wasm32")]
+            GameInput::Save | GameInput::Load => {
+                log::warn!("Circuits can't be saved or loaded in the browser")
+            }
+            GameInput::NewPuzzle(seed) => {
+                let mut grid = Grid::new(self.grid.width(), self.grid.height());
+                grid.generate_puzzle(&mut Rng::new(seed));
+                self.replace_grid(grid);
+            }
+            GameInput::StartChallenge(challenge) => self.start_challenge(challenge),
+            // Clamped here since replays can hold any size
+            GameInput::NewGrid { width, height } => {
+                let (width, height) = Self::clamp_size(width, height);
+                self.replace_grid(Grid::new(width, height));
+            }
+            GameInput::ResizeGrid { width, height } => self.resize_grid(width, height),
+            GameInput::Copy => self.copy_region(),
+            GameInput::Paste => self.paste(),
+            GameInput::ToggleStepMode => self.set_step_mode(!self.step_mode),
+            GameInput::StepSignals => self.step_signals(),
+            GameInput::SpeedUp => self.change_sim_speed(true),
+            GameInput::SlowDown => self.change_sim_speed(false),
+            _ => {}
+        }
+    }
+
+    // Stops at the edges of the grid instead of wrapping around
+    fn move_selection(&mut self, dx: isize, dy: isize) {
+        let (x, y) = match self.selected_cell {
+            Some((x, y)) => (
+                x.saturating_add_signed(dx).min(self.grid.width() - 1),
+                y.saturating_add_signed(dy).min(self.grid.height() - 1),
+            ),
+            None => (0, 0),
+        };
+        self.selected_cell = self.grid.get(x, y).map(|_| (x, y));
+    }
+
+    fn cycle_selected(&mut self) {
+        if let Some((x, y)) = self.selected_cell {
+            let cell = self.grid.get(x, y).unwrap_or_default();
+            self.set_cell(x, y, cell.next());
+        }
+    }
+
+    fn rotate(&mut self) {
+        let Some((x, y)) = self.hovered_cell.or(self.selected_cell) else {
+            return;
+        };
+        let Some(cell) = self.grid.get(x, y).filter(|cell| cell.is_gate()) else {
+            return;
+        };
+
+        if let Some(edit) = self.apply(x, y, cell, self.grid.rotation(x, y) + 1) {
+            self.push_history(vec![edit]);
+            self.evaluate();
+        }
+    }
+
+    // Selects the cell under the press. Anything left over from a stroke
+    // whose release was missed is finished first.
+    fn start_stroke(&mut self, x: f32, y: f32) {
+        self.end_stroke();
+        self.region = None;
+        self.selected_cell = self.grid.cell_at_world(x, y);
+        self.stroke = self.selected_cell.map(|last| Stroke {
+            last,
+            edits: Vec::new(),
+            moved: false,
+        });
+    }
+
+    // Paints wire over every cell between the last one and the one under the
+    // cursor, walking through edge-adjacent cells so diagonal moves leave a
+    // connected wire. Moves off the grid are ignored until the cursor returns.
+    fn continue_stroke(&mut self, x: f32, y: f32) {
+        let Some(cell) = self.grid.cell_at_world(x, y) else {
+            return;
+        };
+        let Some(stroke) = self.stroke.as_mut() else {
+            return;
+        };
+        if cell == stroke.last {
+            return;
+        }
+
+        let mut cells = Vec::new();
+        if !stroke.moved {
+            cells.push(stroke.last);
+        }
+        cells.extend(line_walk(stroke.last, cell));
+        stroke.moved = true;
+        stroke.last = cell;
+
+        for (x, y) in cells {
+            if let Some(edit) = self.apply(x, y, Cell::Wire, 0) {
+                if let Some(stroke) = self.stroke.as_mut() {
+                    stroke.edits.push(edit);
+                }
+            }
+        }
+        self.selected_cell = Some(cell);
+        self.evaluate();
+    }
+
+    fn end_stroke(&mut self) {
+        let Some(stroke) = self.stroke.take() else {
+            return;
+        };
+
+        if stroke.moved {
+            self.push_history(stroke.edits);
+        } else {
+            self.cycle_selected();
+        }
+    }
+
+    fn start_region(&mut self, x: f32, y: f32) {
+        self.end_stroke();
+        self.region = self
+            .grid
+            .cell_at_world(x, y)
+            .map(|start| Region { start, end: start });
+        self.selecting_region = self.region.is_some();
+    }
+
+    // Off the grid, the region stays as it was until the cursor returns
+    fn continue_region(&mut self, x: f32, y: f32) {
+        if let (Some(region), Some(end)) = (self.region.as_mut(), self.grid.cell_at_world(x, y)) {
+            region.end = end;
+        }
+    }
+
+    fn copy_region(&mut self) {
+        let Some(region) = self.region else {
+            return;
+        };
+
+        let ((x0, y0), (x1, y1)) = region.bounds();
+        let clipboard = Clipboard {
+            width: x1 - x0 + 1,
+            height: y1 - y0 + 1,
+            cells: region
+                .cells()
+                .map(|(x, y)| {
+                    (
+                        self.grid.get(x, y).unwrap_or_default(),
+                        self.grid.rotation(x, y),
+                    )
+                })
+                .collect(),
+        };
+        log::info!("Copied {}x{} cells", clipboard.width, clipboard.height);
+        self.clipboard = Some(clipboard);
+    }
+
+    // Pastes as a single undoable edit. Cells that would land past the edges
+    // of the grid are dropped.
+    fn paste(&mut self) {
+        let (Some(clipboard), Some((x0, y0))) = (
+            self.clipboard.take(),
+            self.hovered_cell.or(self.selected_cell),
+        ) else {
+            return;
+        };
+
+        let mut edits = Vec::new();
+        for (i, &(cell, rotation)) in clipboard.cells.iter().enumerate() {
+            let (x, y) = (x0 + i % clipboard.width, y0 + i / clipboard.width);
+            if let Some(edit) = self.apply(x, y, cell, rotation) {
+                edits.push(edit);
+            }
+        }
+        self.clipboard = Some(clipboard);
+
+        if !edits.is_empty() {
+            self.push_history(edits);
+            self.evaluate();
+        }
+    }
+
+    // Changes a cell as an undoable edit. Any undone edits can no longer be
+    // redone afterwards. A gate replacing another keeps its rotation.
+    pub fn set_cell(&mut self, x: usize, y: usize, cell: Cell) {
+        if let Some(edit) = self.apply(x, y, cell, self.grid.rotation(x, y)) {
+            self.push_history(vec![edit]);
+            self.evaluate();
+        }
+    }
+
+    // Changes a cell without evaluating or recording history, returning the
+    // edit if it changed anything. The puzzle's cells are left alone.
+    fn apply(&mut self, x: usize, y: usize, cell: Cell, rotation: u8) -> Option<Edit> {
+        if self
+            .puzzle
+            .as_ref()
+            .is_some_and(|puzzle| puzzle.is_fixed((x, y)))
+        {
+            return None;
+        }
+        let old = (self.grid.get(x, y)?, self.grid.rotation(x, y));
+        self.grid.set_rotated(x, y, cell, rotation);
+        let new = (cell, self.grid.rotation(x, y));
+        (new != old).then_some(Edit {
+            position: (x, y),
+            old,
+            new,
+        })
+    }
+
+    fn push_history(&mut self, edits: Vec<Edit>) {
+        if edits.is_empty() {
+            return;
+        }
+
+        if self.undo_stack.len() == Self::MAX_HISTORY {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack.push_back(edits);
+        self.redo_stack.clear();
+    }
+
+    // A challenge's puzzle is saved in front of the circuit
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut bytes = self
+            .puzzle
+            .as_ref()
+            .map(Puzzle::to_bytes)
+            .unwrap_or_default();
+        bytes.extend(self.grid.to_bytes());
+        fs::write(path, bytes)
+    }
+
+    // Replaces the grid on success and leaves the game untouched otherwise.
+    // The loaded circuit starts with an empty history.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), LoadError> {
+        let bytes = fs::read(path).map_err(LoadError::Io)?;
+        let (puzzle, circuit) = Puzzle::split_bytes(&bytes).map_err(LoadError::Puzzle)?;
+        let mut grid = Grid::from_bytes(circuit).map_err(LoadError::Decode)?;
+        if let Some(puzzle) = &puzzle {
+            puzzle.check_bounds(&grid).map_err(LoadError::Puzzle)?;
+            puzzle.place(&mut grid);
+        }
+
+        self.replace_grid(grid);
+        self.puzzle = puzzle;
+        self.evaluate();
+        Ok(())
+    }
+
+    // Keeps the circuit in the corner at (0, 0), cutting off whatever no
+    // longer fits. Sizes are clamped to 1..=`Grid::MAX_SIZE`.
+    // Grids from 1x1 up to `Grid::MAX_SIZE` on each side
+    fn clamp_size(cols: usize, rows: usize) -> (usize, usize) {
+        let size = |length: usize| length.clamp(1, Grid::MAX_SIZE);
+        let (width, height) = (size(cols), size(rows));
+        if (width, height) != (cols, rows) {
+            log::warn!(
+                "Can't make the grid {}x{}, using {}x{}",
+                cols,
+                rows,
+                width,
+                height
+            );
+        }
+        (width, height)
+    }
+
+    pub fn resize_grid(&mut self, cols: usize, rows: usize) {
+        let (width, height) = Self::clamp_size(cols, rows);
+
+        // The puzzle is dropped if the grid no longer has room for it
+        let puzzle = self.puzzle.take();
+        self.replace_grid(self.grid.resized(width, height));
+        self.puzzle = puzzle.filter(|puzzle| puzzle.check_bounds(&self.grid).is_ok());
+        self.evaluate();
+    }
+
+    // Edits to the old grid can't be undone on the new one, and its puzzle
+    // doesn't apply to it
+    fn replace_grid(&mut self, grid: Grid) {
+        self.puzzle = None;
+        self.solved = false;
+        self.stroke = None;
+        self.region = None;
+        self.selecting_region = false;
+        self.grid = grid;
+        self.changed_cells.clear();
+        if self.step_mode {
+            self.grid.clear_signals();
+        }
+        self.selected_cell = None;
+        self.hovered_cell = None;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(edits) = self.undo_stack.pop_back() {
+            for edit in edits.iter().rev() {
+                let (x, y) = edit.position;
+                self.grid.set_rotated(x, y, edit.old.0, edit.old.1);
+            }
+            self.evaluate();
+            self.redo_stack.push(edits);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(edits) = self.redo_stack.pop() {
+            for edit in &edits {
+                let (x, y) = edit.position;
+                self.grid.set_rotated(x, y, edit.new.0, edit.new.1);
+            }
+            self.evaluate();
+            self.undo_stack.push_back(edits);
+        }
+    }
+}
+
+// The cells after `from` up to and including `to`, each sharing an edge with
+// the one before and staying as close as possible to the straight line
+fn line_walk(from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+    let (x0, y0) = (from.0 as i64, from.1 as i64);
+    let (x1, y1) = (to.0 as i64, to.1 as i64);
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    // How far a cell is from the line, scaled by its length
+    let distance = |x: i64, y: i64| ((x - x0) * dy - (y - y0) * dx).abs();
+
+    let (mut x, mut y) = (x0, y0);
+    let mut cells = Vec::new();
+    while (x, y) != (x1, y1) {
+        let step_x =
+            x != x1 && (y == y1 || distance(x + dx.signum(), y) <= distance(x, y + dy.signum()));
+        if step_x {
+            x += dx.signum();
+        } else {
+            y += dy.signum();
+        }
+        cells.push((x as usize, y as usize));
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_reverts_edits_in_reverse_order() {
+        let mut game = Game::new(3, 1);
+        game.set_cell(0, 0, Cell::Source);
+        game.set_cell(1, 0, Cell::Wire);
+        game.set_cell(2, 0, Cell::Sink);
+        game.set_cell(1, 0, Cell::Not);
+        assert!(!game.grid.signal(2, 0));
+
+        game.undo();
+        assert_eq!(game.grid.get(1, 0), Some(Cell::Wire));
+        assert!(game.grid.signal(2, 0));
+
+        game.undo();
+        game.undo();
+        assert_eq!(game.grid.get(1, 0), Some(Cell::Empty));
+        assert_eq!(game.grid.get(2, 0), Some(Cell::Empty));
+        assert!(!game.grid.signal(1, 0));
+
+        game.undo();
+        game.undo();
+        assert_eq!(game.grid, Grid::new(3, 1));
+    }
+
+    #[test]
+    fn redo_reapplies_undone_edits_until_a_new_edit() {
+        let mut game = Game::new(2, 1);
+        game.set_cell(0, 0, Cell::Source);
+        game.set_cell(1, 0, Cell::Sink);
+        game.undo();
+        game.undo();
+
+        game.redo();
+        assert_eq!(game.grid.get(0, 0), Some(Cell::Source));
+        assert_eq!(game.grid.get(1, 0), Some(Cell::Empty));
+
+        game.set_cell(1, 0, Cell::Wire);
+        game.redo();
+        assert_eq!(game.grid.get(1, 0), Some(Cell::Wire));
+        assert!(game.grid.signal(1, 0));
+    }
+
+    #[test]
+    fn the_simulation_speed_steps_between_its_limits() {
+        let mut game = Game::new(1, 1);
+        game.handle_input(GameInput::SlowDown);
+        assert_eq!(game.sim_speed, 0.5);
+        for _ in 0..3 {
+            game.handle_input(GameInput::SlowDown);
+        }
+        assert_eq!(game.sim_speed, 0.0);
+
+        for _ in 0..6 {
+            game.handle_input(GameInput::SpeedUp);
+        }
+        assert_eq!(game.sim_speed, Game::MAX_SIM_SPEED);
+    }
+
+    #[test]
+    fn signals_propagate_with_the_simulation_off_1x() {
+        let mut game = Game::new(3, 1);
+        game.handle_input(GameInput::SlowDown);
+        game.set_cell(0, 0, Cell::Source);
+        game.set_cell(1, 0, Cell::Not);
+        game.set_cell(2, 0, Cell::Sink);
+        assert!(!game.grid.signal(0, 0));
+
+        let propagate = |game: &mut Game| {
+            for _ in 0..Game::PROPAGATION_TICKS {
+                game.step();
+            }
+        };
+        propagate(&mut game);
+        assert!(game.grid.signal(0, 0));
+        assert!(!game.grid.signal(2, 0));
+        propagate(&mut game);
+        assert!(!game.grid.signal(1, 0));
+
+        // Back at 1x everything settles at once
+        game.set_cell(0, 0, Cell::Empty);
+        game.handle_input(GameInput::SpeedUp);
+        assert!(!game.grid.signal(0, 0));
+        assert!(game.grid.signal(1, 0));
+        assert!(game.grid.signal(2, 0));
+    }
+
+    #[test]
+    fn rotating_turns_the_hovered_gate_as_an_undoable_edit() {
+        let mut game = Game::new(2, 1);
+        game.set_cell(0, 0, Cell::Wire);
+        game.set_cell(1, 0, Cell::And);
+        game.hovered_cell = Some((1, 0));
+        for _ in 0..5 {
+            game.handle_input(GameInput::Rotate);
+        }
+        assert_eq!(game.grid.rotation(1, 0), 1);
+
+        // Cycling to the next gate keeps its rotation, while wires don't turn
+        game.set_cell(1, 0, Cell::Or);
+        assert_eq!(game.grid.rotation(1, 0), 1);
+        game.hovered_cell = Some((0, 0));
+        game.handle_input(GameInput::Rotate);
+        assert_eq!(game.grid.rotation(0, 0), 0);
+
+        game.undo();
+        game.undo();
+        assert_eq!(game.grid.get(1, 0), Some(Cell::And));
+        assert_eq!(game.grid.rotation(1, 0), 0);
+    }
+
+    #[test]
+    fn a_stroke_paints_a_connected_wire_as_one_undo_step() {
+        let mut game = Game::new(4, 4);
+        game.handle_input(GameInput::Click {
+            button: MouseButton::Left,
+            position: [0.5, 0.5],
+        });
+        // Diagonally to (2, 2), then off the grid and back to (3, 2)
+        for position in [[1.5, 1.5], [2.5, 2.5], [9.0, 9.0], [3.5, 2.5]] {
+            game.handle_input(GameInput::Drag { position });
+        }
+        game.handle_input(GameInput::Release {
+            button: MouseButton::Left,
+        });
+
+        let wires: Vec<_> = (0..4)
+            .flat_map(|y| (0..4).map(move |x| (x, y)))
+            .filter(|&(x, y)| game.grid.get(x, y) == Some(Cell::Wire))
+            .collect();
+        assert_eq!(wires, [(0, 0), (1, 0), (1, 1), (2, 1), (2, 2), (3, 2)]);
+        assert_eq!(game.selected_cell, Some((3, 2)));
+
+        game.undo();
+        assert_eq!(game.grid, Grid::new(4, 4));
+        game.redo();
+        assert_eq!(game.grid.get(3, 2), Some(Cell::Wire));
+    }
+
+    #[test]
+    fn cells_can_be_edited_from_the_keyboard() {
+        let mut game = Game::new(3, 2);
+        let mut press = |input| game.handle_input(input);
+        press(GameInput::MoveSelection { dx: 0, dy: 1 });
+        // The first move selects the bottom-left cell, and later ones stop at
+        // the edges
+        for _ in 0..4 {
+            press(GameInput::MoveSelection { dx: 1, dy: 1 });
+        }
+        press(GameInput::CycleSelected);
+        press(GameInput::CycleSelected);
+        assert_eq!(game.selected_cell, Some((2, 1)));
+        assert_eq!(game.grid.get(2, 1), Some(Cell::Empty.next().next()));
+
+        game.handle_input(GameInput::ClearSelected);
+        assert_eq!(game.grid.get(2, 1), Some(Cell::Empty));
+        game.undo();
+        assert_eq!(game.grid.get(2, 1), Some(Cell::Empty.next().next()));
+    }
+
+    #[test]
+    fn a_click_without_dragging_cycles_the_cell() {
+        let mut game = Game::new(2, 2);
+        game.handle_input(GameInput::Click {
+            button: MouseButton::Left,
+            position: [1.5, 0.5],
+        });
+        game.handle_input(GameInput::Drag {
+            position: [1.2, 0.8],
+        });
+        game.handle_input(GameInput::Release {
+            button: MouseButton::Left,
+        });
+
+        assert_eq!(game.grid.get(1, 0), Some(Cell::Empty.next()));
+        assert_eq!(game.undo_stack.len(), 1);
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut game = Game::new(1, 1);
+        for i in 0..Game::MAX_HISTORY + 10 {
+            let cell = if i % 2 == 0 { Cell::Wire } else { Cell::Empty };
+            game.set_cell(0, 0, cell);
+        }
+
+        for _ in 0..Game::MAX_HISTORY + 10 {
+            game.undo();
+        }
+        assert_eq!(game.undo_stack.len(), 0);
+        assert_eq!(game.redo_stack.len(), Game::MAX_HISTORY);
+    }
+
+    #[test]
+    fn edits_in_step_mode_wait_for_a_step() {
+        let mut game = Game::new(3, 1);
+        game.handle_input(GameInput::StepSignals);
+        assert!(game.step_mode());
+
+        game.set_cell(0, 0, Cell::Source);
+        game.set_cell(1, 0, Cell::Wire);
+        game.set_cell(2, 0, Cell::Sink);
+        assert!(!game.grid.signal(0, 0));
+
+        // Sources drive their wires straight away
+        game.step_signals();
+        assert_eq!(game.changed_cells, [(0, 0), (1, 0)]);
+        game.step_signals();
+        assert_eq!(game.changed_cells, [(2, 0)]);
+        game.step_signals();
+        assert!(game.changed_cells.is_empty());
+    }
+
+    #[test]
+    fn resizing_keeps_the_cells_that_fit() {
+        let mut game = Game::new(2, 1);
+        game.grid.set(0, 0, Cell::Source);
+        game.grid.set(1, 0, Cell::Wire);
+        game.grid.evaluate();
+
+        game.resize_grid(3, 2);
+        assert_eq!((game.grid.width(), game.grid.height()), (3, 2));
+        assert_eq!(game.grid.get(1, 0), Some(Cell::Wire));
+        assert_eq!(game.grid.get(2, 1), Some(Cell::Empty));
+        assert!(game.grid.signal(1, 0));
+
+        game.resize_grid(1, 0);
+        assert_eq!((game.grid.width(), game.grid.height()), (1, 1));
+        assert_eq!(game.grid.get(0, 0), Some(Cell::Source));
+        game.resize_grid(usize::MAX, 1);
+        assert_eq!(game.grid.width(), Grid::MAX_SIZE);
+    }
+
+    #[test]
+    fn pasting_a_copied_region_clips_at_the_edges_and_undoes_at_once() {
+        let mut game = Game::new(4, 4);
+        game.grid.set(0, 0, Cell::Source);
+        game.grid.set(1, 0, Cell::Wire);
+        game.grid.set(1, 1, Cell::Not);
+
+        game.handle_input(GameInput::SelectRegion {
+            position: [1.5, 1.5],
+        });
+        game.handle_input(GameInput::Drag {
+            position: [0.5, 0.5],
+        });
+        game.handle_input(GameInput::Release {
+            button: MouseButton::Left,
+        });
+        assert_eq!(game.region.unwrap().bounds(), ((0, 0), (1, 1)));
+        game.handle_input(GameInput::Copy);
+
+        // Only the bottom-left cell of the copy fits in the top-right corner
+        game.hovered_cell = Some((3, 3));
+        game.handle_input(GameInput::Paste);
+        assert_eq!(game.grid.get(3, 3), Some(Cell::Source));
+
+        game.hovered_cell = Some((2, 0));
+        game.handle_input(GameInput::Paste);
+        assert_eq!(game.grid.get(2, 0), Some(Cell::Source));
+        assert_eq!(game.grid.get(3, 0), Some(Cell::Wire));
+        assert_eq!(game.grid.get(3, 1), Some(Cell::Not));
+
+        game.undo();
+        assert_eq!(game.grid.get(2, 0), Some(Cell::Empty));
+        assert_eq!(game.grid.get(3, 1), Some(Cell::Empty));
+        assert_eq!(game.grid.get(3, 3), Some(Cell::Source));
+    }
+
+    #[test]
+    fn an_and_gate_solves_the_and_challenge() {
+        let mut game = Game::default();
+        game.start_challenge(Challenge::And);
+        assert!(!game.check_solution());
+
+        // The inputs at (0, 1) and (0, 3) feed the gate from below and above,
+        // and it drives the sink at (6, 2)
+        for (x, y) in [(1, 1), (2, 1), (1, 3), (2, 3), (3, 2), (4, 2), (5, 2)] {
+            game.set_cell(x, y, Cell::Wire);
+        }
+        game.set_cell(2, 2, Cell::Or);
+        assert!(!game.check_solution());
+        assert!(!game.is_solved());
+
+        game.set_cell(2, 2, Cell::And);
+        assert!(game.check_solution());
+        assert!(game.is_solved());
+
+        // The puzzle's cells can't be edited, and the save keeps the puzzle
+        game.set_cell(0, 1, Cell::Empty);
+        game.set_cell(6, 2, Cell::Wire);
+        assert!(game.is_solved());
+        let path =
+            std::env::temp_dir().join(format!("and-challenge-{}.circuit", std::process::id()));
+        game.save(&path).unwrap();
+        let mut loaded = Game::default();
+        loaded.load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.puzzle, game.puzzle);
+        assert_eq!(loaded.grid, game.grid);
+        assert!(loaded.is_solved());
+
+        game.undo();
+        assert!(!game.is_solved());
+        game.handle_input(GameInput::NewGrid {
+            width: 4,
+            height: 4,
+        });
+        assert!(game.puzzle.is_none());
+    }
+}
@@ -0,0 +1,62 @@
+use gilrs::{Button, EventType, Gilrs};
+
+use crate::game::GameInput;
+
+// Translates gamepad events into the same `GameInput`s the keyboard and mouse
+// produce: the D-pad moves the selection and the south face button (A on
+// Xbox layouts) cycles the selected cell
+pub struct Gamepads {
+    gilrs: Gilrs,
+}
+
+impl Gamepads {
+    // Gamepads are optional, so failing to initialize them only disables them
+    pub fn new() -> Option<Self> {
+        match Gilrs::new() {
+            Ok(gilrs) => {
+                for (_, gamepad) in gilrs.gamepads() {
+                    log::info!("Found gamepad {}", gamepad.name());
+                }
+                Some(Self { gilrs })
+            }
+            Err(e) => {
+                log::warn!("An error occured while initializing gamepads: {}", e);
+                None
+            }
+        }
+    }
+
+    // Drains every pending event, including connects and disconnects, so
+    // controllers can be plugged in and out at any time
+    pub fn poll(&mut self) -> Vec<GameInput> {
+        let mut inputs = Vec::new();
+
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::Connected => {
+                    log::info!("Gamepad {} connected", self.gilrs.gamepad(event.id).name());
+                }
+                EventType::Disconnected => {
+                    log::info!(
+                        "Gamepad {} disconnected",
+                        self.gilrs.gamepad(event.id).name()
+                    );
+                }
+                EventType::ButtonPressed(button, _) => {
+                    let input = match button {
+                        Button::DPadUp => GameInput::MoveSelection { dx: 0, dy: 1 },
+                        Button::DPadDown => GameInput::MoveSelection { dx: 0, dy: -1 },
+                        Button::DPadLeft => GameInput::MoveSelection { dx: -1, dy: 0 },
+                        Button::DPadRight => GameInput::MoveSelection { dx: 1, dy: 0 },
+                        Button::South => GameInput::CycleSelected,
+                        _ => continue,
+                    };
+                    inputs.push(input);
+                }
+                _ => {}
+            }
+        }
+
+        inputs
+    }
+}
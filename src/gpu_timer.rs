@@ -0,0 +1,133 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+// Measures how long the GPU spends on a render pass using timestamp queries.
+// Results are read back asynchronously, so only one measurement is in flight
+// at a time and frames in between aren't measured.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    // Nanoseconds per timestamp tick
+    period: f32,
+    // Set by the map callback once the readback buffer can be read
+    mapped: Arc<AtomicBool>,
+    // Cleared once the readback is read, or by the map callback if mapping
+    // failed, so the next frame can be measured
+    in_flight: Arc<AtomicBool>,
+}
+
+impl GpuTimer {
+    const QUERY_COUNT: u32 = 2;
+    const BUFFER_SIZE: wgpu::BufferAddress = Self::QUERY_COUNT as wgpu::BufferAddress
+        * std::mem::size_of::<u64>() as wgpu::BufferAddress;
+
+    // Returns `None` when the device wasn't created with timestamp queries
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("WGPU Timestamp Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: Self::QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("WGPU Timestamp Resolve Buffer"),
+            size: Self::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("WGPU Timestamp Readback Buffer"),
+            size: Self::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            mapped: Arc::new(AtomicBool::new(false)),
+            in_flight: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    // Writes a timestamp at the start and end of the pass these are used in
+    pub fn timestamp_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1),
+        }
+    }
+
+    // Copies this frame's timestamps towards the CPU, unless the previous ones
+    // are still being read
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        if self.in_flight.load(Ordering::Acquire) {
+            return;
+        }
+
+        encoder.resolve_query_set(
+            &self.query_set,
+            0..Self::QUERY_COUNT,
+            &self.resolve_buffer,
+            0,
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            Self::BUFFER_SIZE,
+        );
+    }
+
+    // Starts reading back the timestamps resolved by `resolve`, once the work
+    // that contains it has been submitted
+    pub fn map(&mut self) {
+        if self.in_flight.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let (mapped, in_flight) = (self.mapped.clone(), self.in_flight.clone());
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(e) => {
+                    log::warn!("An error occured while reading back GPU timestamps: {}", e);
+                    in_flight.store(false, Ordering::Release);
+                }
+            });
+    }
+
+    // Returns the measured pass duration once a readback has finished
+    pub fn read(&mut self, device: &wgpu::Device) -> Option<Duration> {
+        device.poll(wgpu::Maintain::Poll);
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+
+        let elapsed = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            timestamps[1].saturating_sub(timestamps[0])
+        };
+        self.readback_buffer.unmap();
+        self.in_flight.store(false, Ordering::Release);
+
+        Some(Duration::from_nanos(
+            (elapsed as f64 * self.period as f64) as u64,
+        ))
+    }
+}
@@ -0,0 +1,928 @@
+use std::{error::Error, fmt};
+
+use crate::rng::Rng;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Cell {
+    #[default]
+    Empty,
+    Wire,
+    And,
+    Or,
+    Not,
+    Source,
+    Sink,
+}
+
+impl Cell {
+    pub fn is_gate(self) -> bool {
+        matches!(self, Cell::And | Cell::Or | Cell::Not)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Cell::Empty => "Empty",
+            Cell::Wire => "Wire",
+            Cell::And => "AND",
+            Cell::Or => "OR",
+            Cell::Not => "NOT",
+            Cell::Source => "Source",
+            Cell::Sink => "Sink",
+        }
+    }
+
+    pub fn next(self) -> Cell {
+        match self {
+            Cell::Empty => Cell::Wire,
+            Cell::Wire => Cell::And,
+            Cell::And => Cell::Or,
+            Cell::Or => Cell::Not,
+            Cell::Not => Cell::Source,
+            Cell::Source => Cell::Sink,
+            Cell::Sink => Cell::Empty,
+        }
+    }
+
+    // The byte stored for each cell in a saved circuit. These must never
+    // change, or previously saved circuits would load differently.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn to_byte(self) -> u8 {
+        match self {
+            Cell::Empty => 0,
+            Cell::Wire => 1,
+            Cell::And => 2,
+            Cell::Or => 3,
+            Cell::Not => 4,
+            Cell::Source => 5,
+            Cell::Sink => 6,
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn from_byte(byte: u8) -> Option<Cell> {
+        Some(match byte {
+            0 => Cell::Empty,
+            1 => Cell::Wire,
+            2 => Cell::And,
+            3 => Cell::Or,
+            4 => Cell::Not,
+            5 => Cell::Source,
+            6 => Cell::Sink,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    NotACircuit,
+    InvalidSize { width: usize, height: usize },
+    WrongLength { expected: usize, actual: usize },
+    UnknownCell { index: usize, byte: u8 },
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::NotACircuit => write!(f, "The data is not a saved circuit"),
+            DecodeError::InvalidSize { width, height } => {
+                write!(f, "The circuit has an invalid size of {}x{}", width, height)
+            }
+            DecodeError::WrongLength { expected, actual } => write!(
+                f,
+                "The circuit should be {} bytes long but is {}",
+                expected, actual
+            ),
+            DecodeError::UnknownCell { index, byte } => {
+                write!(f, "Cell {} has an unknown type {}", index, byte)
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Error for DecodeError {}
+
+// One row per combination, each the inputs' values and the outputs' signals
+pub type TruthTable = Vec<(Vec<bool>, Vec<bool>)>;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TruthTableError {
+    OffGrid,
+    NoInputsOrOutputs,
+    TooManyInputs { inputs: usize },
+}
+
+impl fmt::Display for TruthTableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TruthTableError::OffGrid => write!(f, "The region is off the grid"),
+            TruthTableError::NoInputsOrOutputs => {
+                write!(f, "The region has no inputs or outputs")
+            }
+            TruthTableError::TooManyInputs { inputs } => write!(
+                f,
+                "The region has {} inputs, more than {}",
+                inputs,
+                Grid::MAX_TRUTH_TABLE_INPUTS
+            ),
+        }
+    }
+}
+
+impl Error for TruthTableError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+
+    fn offset(self) -> (isize, isize) {
+        match self {
+            Direction::North => (0, 1),
+            Direction::East => (1, 0),
+            Direction::South => (0, -1),
+            Direction::West => (-1, 0),
+        }
+    }
+
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+        }
+    }
+
+    // Turned clockwise by `rotation` quarter turns
+    fn rotated(self, rotation: u8) -> Direction {
+        let index = Direction::ALL.iter().position(|&d| d == self).unwrap_or(0);
+        Direction::ALL[(index + rotation as usize) % 4]
+    }
+}
+
+// Unrotated gates read from their west, north and south sides and drive the
+// cell to their east, and each quarter turn clockwise turns all of those
+// sides with it. Sources drive every neighbor, and connected wires form a
+// single network that is on when anything driving any of its cells is on.
+const GATE_OUTPUT: Direction = Direction::East;
+const GATE_INPUTS: [Direction; 3] = [Direction::West, Direction::North, Direction::South];
+
+// How `Grid::evaluate` finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Evaluation {
+    Stable,
+    // A feedback loop kept changing, e.g. a NOT gate wired into its own
+    // input. The cells involved are marked as unstable.
+    Oscillating,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    // Quarter turns clockwise, from 0 to 3. Only gates have a direction, so
+    // every other cell stays at 0.
+    rotations: Vec<u8>,
+    signals: Vec<bool>,
+    // Cells whose signal never settled during the last evaluation
+    unstable: Vec<bool>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![Cell::Empty; width * height],
+            rotations: vec![0; width * height],
+            signals: vec![false; width * height],
+            unstable: vec![false; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        (x < self.width && y < self.height).then_some(y * self.width + x)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Cell> {
+        self.index(x, y).map(|i| self.cells[i])
+    }
+
+    // Setting a cell leaves it unrotated
+    pub fn set(&mut self, x: usize, y: usize, cell: Cell) {
+        self.set_rotated(x, y, cell, 0);
+    }
+
+    // Rotations are taken modulo a full turn, and dropped for cells other
+    // than gates
+    pub fn set_rotated(&mut self, x: usize, y: usize, cell: Cell, rotation: u8) {
+        if let Some(i) = self.index(x, y) {
+            self.cells[i] = cell;
+            self.rotations[i] = if cell.is_gate() { rotation % 4 } else { 0 };
+        }
+    }
+
+    pub fn rotation(&self, x: usize, y: usize) -> u8 {
+        self.index(x, y).map_or(0, |i| self.rotations[i])
+    }
+
+    // A copy with the cells that still fit at the same positions and `Empty`
+    // everywhere else. Signals start off until it's evaluated.
+    pub fn resized(&self, width: usize, height: usize) -> Grid {
+        let mut grid = Grid::new(width, height);
+        for y in 0..height.min(self.height) {
+            for x in 0..width.min(self.width) {
+                let i = y * self.width + x;
+                grid.set_rotated(x, y, self.cells[i], self.rotations[i]);
+            }
+        }
+        grid
+    }
+
+    // Cell (x, y) covers the world-space square (x, y)..(x + 1, y + 1)
+    pub fn cell_at_world(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+
+        let (x, y) = (x as usize, y as usize);
+        self.index(x, y).map(|_| (x, y))
+    }
+
+    pub fn signal(&self, x: usize, y: usize) -> bool {
+        self.index(x, y).is_some_and(|i| self.signals[i])
+    }
+
+    pub fn is_unstable(&self, x: usize, y: usize) -> bool {
+        self.index(x, y).is_some_and(|i| self.unstable[i])
+    }
+
+    // The signals a gate reads from whichever of its input sides are
+    // connected, in `GATE_INPUTS` order as turned by the gate's rotation.
+    // `None` for cells that aren't gates.
+    pub fn gate_inputs(&self, x: usize, y: usize) -> Option<Vec<bool>> {
+        let i = self.index(x, y)?;
+        self.cells[i]
+            .is_gate()
+            .then(|| self.inputs(&self.signals, i))
+    }
+
+    fn inputs(&self, signals: &[bool], i: usize) -> Vec<bool> {
+        GATE_INPUTS
+            .into_iter()
+            .map(|direction| direction.rotated(self.rotations[i]))
+            .filter_map(|direction| {
+                let n = self.neighbor(i, direction)?;
+                self.connects(n, direction.opposite())
+                    .then(|| self.drives(signals, n, direction.opposite()))
+            })
+            .collect()
+    }
+
+    fn neighbor(&self, i: usize, direction: Direction) -> Option<usize> {
+        let (dx, dy) = direction.offset();
+        let x = (i % self.width).checked_add_signed(dx)?;
+        let y = (i / self.width).checked_add_signed(dy)?;
+        self.index(x, y)
+    }
+
+    // Whether the cell at `from` is currently pushing a signal into its
+    // neighbor in `direction`
+    fn drives(&self, signals: &[bool], from: usize, direction: Direction) -> bool {
+        match self.cells[from] {
+            Cell::Source => true,
+            Cell::Wire => signals[from],
+            cell if cell.is_gate() => direction == self.gate_output(from) && signals[from],
+            _ => false,
+        }
+    }
+
+    // Whether the cell at `from` is wired up to its neighbor in `direction`,
+    // regardless of its current signal
+    fn connects(&self, from: usize, direction: Direction) -> bool {
+        match self.cells[from] {
+            Cell::Source | Cell::Wire => true,
+            cell if cell.is_gate() => direction == self.gate_output(from),
+            _ => false,
+        }
+    }
+
+    fn gate_output(&self, i: usize) -> Direction {
+        GATE_OUTPUT.rotated(self.rotations[i])
+    }
+
+    // Groups connected wire cells into networks
+    fn wire_networks(&self) -> Vec<Vec<usize>> {
+        let mut visited = vec![false; self.cells.len()];
+        let mut networks = Vec::new();
+
+        for start in 0..self.cells.len() {
+            if self.cells[start] != Cell::Wire || visited[start] {
+                continue;
+            }
+
+            let mut network = Vec::new();
+            let mut stack = vec![start];
+            visited[start] = true;
+
+            while let Some(i) = stack.pop() {
+                network.push(i);
+                for direction in Direction::ALL {
+                    if let Some(n) = self.neighbor(i, direction) {
+                        if self.cells[n] == Cell::Wire && !visited[n] {
+                            visited[n] = true;
+                            stack.push(n);
+                        }
+                    }
+                }
+            }
+
+            networks.push(network);
+        }
+
+        networks
+    }
+
+    // Computes every cell's next signal from the current ones, returning
+    // whether anything changed
+    fn step(&mut self, networks: &[Vec<usize>]) -> bool {
+        let current = &self.signals;
+        let mut next = vec![false; self.cells.len()];
+
+        for network in networks {
+            let on = network.iter().any(|&i| {
+                Direction::ALL.into_iter().any(|direction| {
+                    self.neighbor(i, direction).is_some_and(|n| {
+                        self.cells[n] != Cell::Wire && self.drives(current, n, direction.opposite())
+                    })
+                })
+            });
+
+            for &i in network {
+                next[i] = on;
+            }
+        }
+
+        for (i, &cell) in self.cells.iter().enumerate() {
+            next[i] = match cell {
+                Cell::Empty => false,
+                Cell::Wire => next[i],
+                Cell::Source => true,
+                Cell::Sink => Direction::ALL.into_iter().any(|direction| {
+                    self.neighbor(i, direction)
+                        .is_some_and(|n| self.drives(current, n, direction.opposite()))
+                }),
+                Cell::And | Cell::Or | Cell::Not => {
+                    let inputs = self.inputs(current, i);
+
+                    match cell {
+                        Cell::And => !inputs.is_empty() && inputs.iter().all(|&on| on),
+                        Cell::Or => inputs.iter().any(|&on| on),
+                        _ => !inputs.iter().any(|&on| on),
+                    }
+                }
+            };
+        }
+
+        let changed = next != self.signals;
+        self.signals = next;
+        changed
+    }
+
+    // Saved circuits are the magic bytes, the width and height as
+    // little-endian u16s, then one byte per cell in row order. Each byte holds
+    // the cell's type in its low 4 bits and its rotation above them, so
+    // circuits saved before rotations load unrotated. Signals aren't stored
+    // since they're recomputed on load.
+    #[cfg(not(target_arch = "wasm32"))]
+    const MAGIC: &'static [u8; 4] = b"LGC1";
+    #[cfg(not(target_arch = "wasm32"))]
+    const HEADER_LEN: usize = 8;
+    // Keeps corrupt dimensions from allocating huge grids on load
+    pub const MAX_SIZE: usize = 1024;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::HEADER_LEN + self.cells.len());
+        bytes.extend_from_slice(Self::MAGIC);
+        bytes.extend_from_slice(&(self.width as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.height as u16).to_le_bytes());
+        bytes.extend(
+            self.cells
+                .iter()
+                .zip(&self.rotations)
+                .map(|(cell, rotation)| cell.to_byte() | rotation << 4),
+        );
+        bytes
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Grid, DecodeError> {
+        let header = bytes
+            .get(..Self::HEADER_LEN)
+            .ok_or(DecodeError::NotACircuit)?;
+        if &header[..4] != Self::MAGIC {
+            return Err(DecodeError::NotACircuit);
+        }
+
+        let width = u16::from_le_bytes([header[4], header[5]]) as usize;
+        let height = u16::from_le_bytes([header[6], header[7]]) as usize;
+        if width == 0 || height == 0 || width > Self::MAX_SIZE || height > Self::MAX_SIZE {
+            return Err(DecodeError::InvalidSize { width, height });
+        }
+
+        let data = &bytes[Self::HEADER_LEN..];
+        if data.len() != width * height {
+            return Err(DecodeError::WrongLength {
+                expected: Self::HEADER_LEN + width * height,
+                actual: bytes.len(),
+            });
+        }
+
+        let (cells, rotations) = data
+            .iter()
+            .enumerate()
+            .map(|(index, &byte)| {
+                let (cell, rotation) = (Cell::from_byte(byte & 0x0f), byte >> 4);
+                match cell {
+                    Some(cell) if rotation == 0 || (cell.is_gate() && rotation < 4) => {
+                        Ok((cell, rotation))
+                    }
+                    _ => Err(DecodeError::UnknownCell { index, byte }),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .unzip();
+
+        let mut grid = Grid {
+            width,
+            height,
+            cells,
+            rotations,
+            signals: vec![false; width * height],
+            unstable: vec![false; width * height],
+        };
+        grid.evaluate();
+        Ok(grid)
+    }
+
+    // Replaces the grid with a new puzzle: sources down the left edge, sinks
+    // down the right edge, and a few gates scattered in between for the
+    // player to wire up. Everything comes from `rng`, so the same seed always
+    // gives the same puzzle.
+    pub fn generate_puzzle(&mut self, rng: &mut Rng) {
+        const GATES: [Cell; 3] = [Cell::And, Cell::Or, Cell::Not];
+
+        self.cells.fill(Cell::Empty);
+        self.rotations.fill(0);
+        self.signals.fill(false);
+        self.unstable.fill(false);
+        if self.width < 3 || self.height == 0 {
+            self.evaluate();
+            return;
+        }
+
+        let (left, right) = (0, self.width - 1);
+        for y in 0..self.height {
+            if rng.one_in(3) {
+                self.set(left, y, Cell::Source);
+            }
+        }
+        // At least one source and one sink, so there's always something to solve
+        self.set(left, rng.below(self.height), Cell::Source);
+        for _ in 0..1 + rng.below(2) {
+            self.set(right, rng.below(self.height), Cell::Sink);
+        }
+
+        let gate_count = (self.width - 2) * self.height / 10;
+        for _ in 0..gate_count {
+            let x = 1 + rng.below(self.width - 2);
+            let y = rng.below(self.height);
+            self.set(x, y, GATES[rng.below(GATES.len())]);
+        }
+
+        self.evaluate();
+    }
+
+    // Recomputes all signals from scratch. Each step settles at least one more
+    // gate, sink or wire network along every path, so an acyclic circuit is
+    // stable after one step per one of those. A circuit still changing after
+    // that has a loop that never settles, which is then stepped for as long
+    // again to find every cell that keeps changing.
+    pub fn evaluate(&mut self) -> Evaluation {
+        let networks = self.wire_networks();
+        self.clear_signals();
+
+        let nodes = networks.len()
+            + self
+                .cells
+                .iter()
+                .filter(|&&cell| !matches!(cell, Cell::Empty | Cell::Wire))
+                .count();
+        for _ in 0..=nodes {
+            if !self.step(&networks) {
+                return Evaluation::Stable;
+            }
+        }
+
+        for _ in 0..=nodes {
+            let previous = self.signals.clone();
+            self.step(&networks);
+            for (i, unstable) in self.unstable.iter_mut().enumerate() {
+                *unstable |= previous[i] != self.signals[i];
+            }
+        }
+        Evaluation::Oscillating
+    }
+
+    // Advances signals by a single step from their current values instead of
+    // settling them, so a change can be watched as it propagates. Returns
+    // whether anything changed, which stops happening once the circuit is
+    // stable.
+    pub fn step_evaluate(&mut self) -> bool {
+        let networks = self.wire_networks();
+        self.step(&networks)
+    }
+
+    pub fn clear_signals(&mut self) {
+        self.signals = vec![false; self.cells.len()];
+        self.unstable = vec![false; self.cells.len()];
+    }
+
+    // Each input doubles the combinations a truth table evaluates
+    pub const MAX_TRUTH_TABLE_INPUTS: usize = 8;
+
+    // Evaluates a copy of the cells between two opposite corners, both
+    // inclusive, on their own for every combination of inputs. The sources
+    // and wires down the region's west edge are its inputs, from top to
+    // bottom, and are swapped for a source while on and emptied while off.
+    // Every other non-empty cell down its east edge is an output. Input i is
+    // on in the rows whose index has bit i set.
+    pub fn truth_table(
+        &self,
+        region: ((usize, usize), (usize, usize)),
+    ) -> Result<TruthTable, TruthTableError> {
+        let ((x0, y0), (x1, y1)) = region;
+        let x1 = x1.min(self.width.saturating_sub(1));
+        let y1 = y1.min(self.height.saturating_sub(1));
+        if x0 > x1 || y0 > y1 || x0 >= self.width || y0 >= self.height {
+            return Err(TruthTableError::OffGrid);
+        }
+
+        let mut subcircuit = Grid::new(x1 - x0 + 1, y1 - y0 + 1);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let i = y * self.width + x;
+                subcircuit.set_rotated(x - x0, y - y0, self.cells[i], self.rotations[i]);
+            }
+        }
+
+        let edge = |x: usize, is_end: fn(Cell) -> bool| -> Vec<(usize, usize)> {
+            (0..subcircuit.height)
+                .rev()
+                .map(|y| (x, y))
+                .filter(|&(x, y)| subcircuit.get(x, y).is_some_and(is_end))
+                .collect()
+        };
+        let inputs = edge(0, |cell| matches!(cell, Cell::Source | Cell::Wire));
+        let outputs = edge(subcircuit.width - 1, |cell| {
+            !matches!(cell, Cell::Empty | Cell::Source)
+        });
+        if inputs.is_empty() && outputs.is_empty() {
+            return Err(TruthTableError::NoInputsOrOutputs);
+        }
+        if inputs.len() > Self::MAX_TRUTH_TABLE_INPUTS {
+            return Err(TruthTableError::TooManyInputs {
+                inputs: inputs.len(),
+            });
+        }
+
+        let table = (0..1usize << inputs.len())
+            .map(|combination| {
+                let mut grid = subcircuit.clone();
+                let values: Vec<bool> = (0..inputs.len())
+                    .map(|i| combination >> i & 1 != 0)
+                    .collect();
+                for (&(x, y), &on) in inputs.iter().zip(&values) {
+                    grid.set(x, y, if on { Cell::Source } else { Cell::Empty });
+                }
+
+                grid.evaluate();
+                let signals = outputs.iter().map(|&(x, y)| grid.signal(x, y)).collect();
+                (values, signals)
+            })
+            .collect();
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // y=2: Source Wire Wire  .    .
+    // y=1:   .     .   And  Wire Sink
+    // y=0: Source Wire Wire  .    .
+    fn and_circuit() -> Grid {
+        let mut grid = Grid::new(5, 3);
+        for y in [0, 2] {
+            grid.set(0, y, Cell::Source);
+            grid.set(1, y, Cell::Wire);
+            grid.set(2, y, Cell::Wire);
+        }
+        grid.set(2, 1, Cell::And);
+        grid.set(3, 1, Cell::Wire);
+        grid.set(4, 1, Cell::Sink);
+        grid
+    }
+
+    #[test]
+    fn and_gate_with_both_inputs_on() {
+        let mut grid = and_circuit();
+        grid.evaluate();
+
+        assert!(grid.signal(2, 1));
+        assert!(grid.signal(3, 1));
+        assert!(grid.signal(4, 1));
+    }
+
+    #[test]
+    fn and_gate_with_one_input_off() {
+        let mut grid = and_circuit();
+        grid.set(0, 2, Cell::Empty);
+        grid.evaluate();
+
+        assert!(!grid.signal(2, 2));
+        assert!(grid.signal(2, 0));
+        assert!(!grid.signal(2, 1));
+        assert!(!grid.signal(4, 1));
+    }
+
+    #[test]
+    fn gate_inputs_follow_the_evaluated_signals() {
+        let mut grid = and_circuit();
+        grid.set(0, 2, Cell::Empty);
+        grid.evaluate();
+
+        // West is empty and not an input, north is off and south is on
+        assert_eq!(grid.gate_inputs(2, 1), Some(vec![false, true]));
+        assert_eq!(grid.gate_inputs(3, 1), None);
+    }
+
+    #[test]
+    fn stepping_settles_on_the_evaluated_signals() {
+        let mut grid = and_circuit();
+        grid.clear_signals();
+
+        let mut steps = 0;
+        while grid.step_evaluate() {
+            steps += 1;
+        }
+        // Sources with their wires, then the gate, the wire after it and the sink
+        assert_eq!(steps, 4);
+
+        let mut evaluated = grid.clone();
+        evaluated.evaluate();
+        assert_eq!(grid, evaluated);
+    }
+
+    #[test]
+    fn a_not_gate_feeding_itself_is_reported() {
+        // y=3:   .    Wire Wire
+        // y=2:   .    Not  Wire
+        // y=1:   .     .    .
+        // y=0: Source Wire Sink
+        let mut grid = Grid::new(3, 4);
+        grid.set(1, 2, Cell::Not);
+        for (x, y) in [(2, 2), (2, 3), (1, 3)] {
+            grid.set(x, y, Cell::Wire);
+        }
+        grid.set(0, 0, Cell::Source);
+        grid.set(1, 0, Cell::Wire);
+        grid.set(2, 0, Cell::Sink);
+
+        assert_eq!(grid.evaluate(), Evaluation::Oscillating);
+        for (x, y) in [(1, 2), (2, 2), (2, 3), (1, 3)] {
+            assert!(grid.is_unstable(x, y), "({}, {}) should be unstable", x, y);
+        }
+        assert!((0..3).all(|x| !grid.is_unstable(x, 0)));
+
+        grid.set(1, 3, Cell::Empty);
+        assert_eq!(grid.evaluate(), Evaluation::Stable);
+        assert!(!grid.is_unstable(1, 2));
+    }
+
+    #[test]
+    fn truth_tables_evaluate_the_region_on_its_own() {
+        // NOT gates with more than one input are NORs, so the first is
+        // NOR(A, B), the next two are A AND NOT B and B AND NOT A, and the OR
+        // of those is the XOR
+        //
+        // y=4: A  Wire Wire Wire Wire  .    .    .
+        // y=3: .   .   Wire  .   Not  Wire Wire  .
+        // y=2: .   .   Not  Wire Wire  .   Or   Sink
+        // y=1: .   .   Wire  .   Not  Wire Wire  .
+        // y=0: B  Wire Wire Wire Wire  .    .    .
+        let mut grid = Grid::new(10, 7);
+        let mut place = |x: usize, y: usize, cell| grid.set(x + 1, y + 1, cell);
+        for y in [0, 4] {
+            place(0, y, Cell::Source);
+            for x in 1..=4 {
+                place(x, y, Cell::Wire);
+            }
+        }
+        for (x, y) in [
+            (2, 3),
+            (2, 1),
+            (3, 2),
+            (4, 2),
+            (5, 3),
+            (6, 3),
+            (5, 1),
+            (6, 1),
+        ] {
+            place(x, y, Cell::Wire);
+        }
+        for (x, y) in [(2, 2), (4, 3), (4, 1)] {
+            place(x, y, Cell::Not);
+        }
+        place(6, 2, Cell::Or);
+        place(7, 2, Cell::Sink);
+        // Outside the region, and would otherwise keep the output on
+        grid.set(9, 3, Cell::Source);
+        grid.evaluate();
+
+        let before = grid.clone();
+        let table = grid.truth_table(((1, 1), (8, 5))).unwrap();
+        assert_eq!(grid, before);
+        assert_eq!(
+            table,
+            vec![
+                (vec![false, false], vec![false]),
+                (vec![true, false], vec![true]),
+                (vec![false, true], vec![true]),
+                (vec![true, true], vec![false]),
+            ]
+        );
+
+        assert_eq!(
+            grid.truth_table(((10, 0), (12, 2))),
+            Err(TruthTableError::OffGrid)
+        );
+        assert_eq!(
+            grid.truth_table(((0, 0), (0, 6))),
+            Err(TruthTableError::NoInputsOrOutputs)
+        );
+
+        let mut wide = Grid::new(2, 10);
+        for y in 0..10 {
+            wide.set(0, y, Cell::Wire);
+        }
+        assert_eq!(
+            wide.truth_table(((0, 0), (1, 9))),
+            Err(TruthTableError::TooManyInputs { inputs: 10 })
+        );
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_puzzle() {
+        let generate = |seed| {
+            let mut grid = Grid::new(8, 8);
+            grid.generate_puzzle(&mut Rng::new(seed));
+            grid
+        };
+
+        assert_eq!(generate(42), generate(42));
+        assert_ne!(generate(42), generate(43));
+    }
+
+    #[test]
+    fn a_puzzle_generated_over_rotated_gates_round_trips() {
+        let mut grid = Grid::new(8, 8);
+        for y in 0..8 {
+            for x in 0..8 {
+                grid.set_rotated(x, y, Cell::Not, 3);
+            }
+        }
+        grid.generate_puzzle(&mut Rng::new(42));
+
+        let loaded = Grid::from_bytes(&grid.to_bytes()).unwrap();
+        assert_eq!(loaded, grid);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut grid = and_circuit();
+        grid.evaluate();
+
+        let loaded = Grid::from_bytes(&grid.to_bytes()).unwrap();
+        assert_eq!(loaded, grid);
+    }
+
+    #[test]
+    fn corrupt_bytes_are_rejected() {
+        let bytes = and_circuit().to_bytes();
+
+        assert_eq!(Grid::from_bytes(&bytes[..6]), Err(DecodeError::NotACircuit));
+        assert_eq!(
+            Grid::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::WrongLength {
+                expected: bytes.len(),
+                actual: bytes.len() - 1
+            })
+        );
+
+        let mut bad_cell = bytes.clone();
+        bad_cell[8] = 7;
+        assert_eq!(
+            Grid::from_bytes(&bad_cell),
+            Err(DecodeError::UnknownCell { index: 0, byte: 7 })
+        );
+
+        let mut bad_size = bytes;
+        bad_size[4..6].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(
+            Grid::from_bytes(&bad_size),
+            Err(DecodeError::InvalidSize {
+                width: 0,
+                height: 3
+            })
+        );
+    }
+
+    #[test]
+    fn not_gate_inverts_its_input() {
+        let mut grid = Grid::new(4, 1);
+        grid.set(0, 0, Cell::Source);
+        grid.set(1, 0, Cell::Not);
+        grid.set(2, 0, Cell::Wire);
+        grid.set(3, 0, Cell::Sink);
+        grid.evaluate();
+        assert!(!grid.signal(3, 0));
+
+        grid.set(0, 0, Cell::Empty);
+        grid.evaluate();
+        assert!(grid.signal(3, 0));
+    }
+
+    #[test]
+    fn a_rotated_not_gate_reads_and_drives_its_turned_sides() {
+        // y=2:   .   Wire   .
+        // y=1: Source Not Source
+        // y=0:   .   Sink   .
+        let mut grid = Grid::new(3, 3);
+        grid.set(1, 2, Cell::Wire);
+        grid.set(0, 1, Cell::Source);
+        grid.set(2, 1, Cell::Source);
+        grid.set(1, 0, Cell::Sink);
+
+        // A quarter turn clockwise reads from north, east and west, and
+        // drives the sink to its south
+        grid.set_rotated(1, 1, Cell::Not, 1);
+        grid.evaluate();
+        assert_eq!(grid.gate_inputs(1, 1), Some(vec![false, true, true]));
+        assert!(!grid.signal(1, 0));
+
+        grid.set(0, 1, Cell::Empty);
+        grid.set(2, 1, Cell::Empty);
+        grid.evaluate();
+        assert!(grid.signal(1, 0));
+        // Three quarter turns have it drive north instead, into the wire
+        grid.set_rotated(1, 1, Cell::Not, 3);
+        grid.evaluate();
+        assert!(!grid.signal(1, 0));
+        assert!(grid.signal(1, 2));
+
+        // Only gates turn, and rotations survive a save
+        grid.set_rotated(1, 2, Cell::Wire, 2);
+        assert_eq!(grid.rotation(1, 2), 0);
+        grid.evaluate();
+        assert_eq!(Grid::from_bytes(&grid.to_bytes()).unwrap(), grid);
+        assert_eq!(grid.rotation(1, 1), 3);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::{camera::Camera2D, dynamic_buffer::DynamicBuffer, render_stats::RenderStats};
+
+// How lines meet at the corners of the grid's outline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+}
+
+// How the lines between cells are drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridStyle {
+    pub color: [f32; 4],
+    // Cells between lines. The outline of the grid is always drawn.
+    pub spacing: usize,
+    // In logical pixels, so lines look the same at any zoom or scale factor
+    pub line_width: f32,
+    pub outline_join: LineJoin,
+}
+
+impl Default for GridStyle {
+    fn default() -> Self {
+        GridStyle {
+            color: [0.0, 0.0, 0.0, 0.35],
+            spacing: 1,
+            line_width: 1.0,
+            outline_join: LineJoin::Miter,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineStyleUniform {
+    // In world units
+    half_width: f32,
+    // Room left around each line for its anti-aliased edge, one physical
+    // pixel in world units
+    padding: f32,
+    round: u32,
+    _padding: u32,
+}
+
+impl LineStyleUniform {
+    // `pixel_size` is the world size of one physical pixel
+    fn new(style: &GridStyle, pixel_size: f32, scale_factor: f32) -> Self {
+        let width = style.line_width * scale_factor * pixel_size;
+        LineStyleUniform {
+            half_width: width.max(0.0) / 2.0,
+            padding: pixel_size,
+            round: (style.outline_join == LineJoin::Round) as u32,
+            _padding: 0,
+        }
+    }
+}
+
+// One line, drawn as an instance of a quad the vertex shader stretches
+// between its ends
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineSegment {
+    start: [f32; 2],
+    end: [f32; 2],
+    color: [f32; 4],
+}
+
+impl LineSegment {
+    // Two triangles per quad
+    const VERTICES: u32 = 6;
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = [
+        wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 0,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+            shader_location: 1,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            shader_location: 2,
+            format: wgpu::VertexFormat::Float32x4,
+        },
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineSegment>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// What the current line vertices were generated for
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Region {
+    grid_size: (usize, usize),
+    style: GridStyle,
+    // World-space min and max corners the lines cover
+    min: [f32; 2],
+    max: [f32; 2],
+    zoom: f32,
+}
+
+// Draws lines along cell boundaries as anti-aliased quads. Only the part of
+// the grid around the view gets lines, so they're regenerated when the view
+// moves outside that part, zooms in a long way, or the grid or style changes.
+// Their width lives in a uniform instead, as it changes in world units with
+// every zoom.
+pub struct GridLines {
+    pipeline: wgpu::RenderPipeline,
+    segment_buffer: DynamicBuffer<LineSegment>,
+    region: Option<Region>,
+    style_buffer: wgpu::Buffer,
+    style_bind_group: wgpu::BindGroup,
+    line_style: Option<LineStyleUniform>,
+}
+
+impl GridLines {
+    const INITIAL_SEGMENT_CAPACITY: usize = 128;
+    // Zooming in by more than this much regenerates the lines for the
+    // smaller view
+    const MAX_ZOOM_IN: f32 = 4.0;
+
+    pub fn new(
+        device: &wgpu::Device,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        // Written on the first update
+        let style_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("WGPU Grid Lines Style Buffer"),
+            size: std::mem::size_of::<LineStyleUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let style_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("WGPU Grid Lines Style Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let style_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("WGPU Grid Lines Style Bind Group"),
+            layout: &style_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: style_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("grid_lines.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WGPU Grid Lines Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout, &style_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("WGPU Grid Lines Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[LineSegment::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            segment_buffer: DynamicBuffer::new(
+                device,
+                "WGPU Grid Lines Segment Buffer",
+                wgpu::BufferUsages::VERTEX,
+                Self::INITIAL_SEGMENT_CAPACITY,
+            ),
+            region: None,
+            style_buffer,
+            style_bind_group,
+            line_style: None,
+        }
+    }
+
+    // `viewport_height` is in the physical pixels the lines are drawn to
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        grid_size: (usize, usize),
+        style: GridStyle,
+        camera: &Camera2D,
+        aspect: f32,
+        viewport_height: f32,
+        scale_factor: f32,
+    ) {
+        let view_min = camera.ndc_to_world((-1.0, -1.0), aspect);
+        let view_max = camera.ndc_to_world((1.0, 1.0), aspect);
+
+        let pixel_size = (view_max[1] - view_min[1]) / viewport_height.max(1.0);
+        let line_style = LineStyleUniform::new(&style, pixel_size, scale_factor);
+        if self.line_style != Some(line_style) {
+            queue.write_buffer(&self.style_buffer, 0, bytemuck::cast_slice(&[line_style]));
+            self.line_style = Some(line_style);
+        }
+
+        let zoom = camera.zoom;
+        let still_covered = self.region.is_some_and(|region| {
+            region.grid_size == grid_size
+                && region.style == style
+                && zoom <= region.zoom * Self::MAX_ZOOM_IN
+                && (0..2).all(|i| region.min[i] <= view_min[i] && view_max[i] <= region.max[i])
+        });
+        if still_covered {
+            return;
+        }
+
+        // Lines are generated for a view's width and height of margin around
+        // the view, so small pans don't regenerate them
+        let margin = [view_max[0] - view_min[0], view_max[1] - view_min[1]];
+        let region = Region {
+            grid_size,
+            style,
+            min: [view_min[0] - margin[0], view_min[1] - margin[1]],
+            max: [view_max[0] + margin[0], view_max[1] + margin[1]],
+            zoom,
+        };
+        let segments = Self::segments(&region);
+
+        self.segment_buffer.write(device, queue, &segments);
+        self.region = Some(region);
+    }
+
+    pub fn allocations(&self) -> u64 {
+        self.segment_buffer.allocations()
+    }
+
+    // Cell (x, y) spans x..x + 1 and y..y + 1 in world space, so the lines sit
+    // on whole numbers and are clipped to both the grid and the region
+    fn segments(region: &Region) -> Vec<LineSegment> {
+        let (width, height) = region.grid_size;
+        let spacing = region.style.spacing.max(1);
+        let color = region.style.color;
+        let size = [width as f32, height as f32];
+        let min = [region.min[0].max(0.0), region.min[1].max(0.0)];
+        let max = [region.max[0].min(size[0]), region.max[1].min(size[1])];
+        if min[0] > max[0] || min[1] > max[1] {
+            return Vec::new();
+        }
+
+        let mut segments = Vec::new();
+        let mut line = |start: [f32; 2], end: [f32; 2]| {
+            segments.push(LineSegment { start, end, color });
+        };
+
+        // Every `spacing`th boundary on each axis, plus the far edge
+        let lines = |count: usize, min: f32, max: f32| {
+            let first = (min.ceil() as usize).div_ceil(spacing) * spacing;
+            (first..=count)
+                .step_by(spacing)
+                .chain((!count.is_multiple_of(spacing)).then_some(count))
+                .map(|i| i as f32)
+                .filter(move |&i| min <= i && i <= max)
+        };
+
+        for x in lines(width, min[0], max[0]) {
+            line([x, min[1]], [x, max[1]]);
+        }
+        for y in lines(height, min[1], max[1]) {
+            line([min[0], y], [max[0], y]);
+        }
+        segments
+    }
+
+    pub fn render<'p>(
+        &'p self,
+        render_pass: &mut wgpu::RenderPass<'p>,
+        camera_bind_group: &'p wgpu::BindGroup,
+        stats: &mut RenderStats,
+    ) {
+        if self.segment_buffer.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.style_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.segment_buffer.slice());
+        render_pass.draw(0..LineSegment::VERTICES, 0..self.segment_buffer.len());
+        stats.record_draw(LineSegment::VERTICES, self.segment_buffer.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_sit_on_cell_boundaries() {
+        let region = Region {
+            grid_size: (5, 3),
+            style: GridStyle {
+                spacing: 2,
+                ..GridStyle::default()
+            },
+            min: [0.5, -10.0],
+            max: [100.0, 100.0],
+            zoom: 1.0,
+        };
+        let segments = GridLines::segments(&region);
+
+        // Vertical lines at x = 2, 4 and the far edge at 5, clipped to the
+        // region on the left, then horizontal lines at y = 0, 2 and 3
+        let lines: Vec<_> = segments
+            .iter()
+            .map(|segment| (segment.start, segment.end))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ([2.0, 0.0], [2.0, 3.0]),
+                ([4.0, 0.0], [4.0, 3.0]),
+                ([5.0, 0.0], [5.0, 3.0]),
+                ([0.5, 0.0], [5.0, 0.0]),
+                ([0.5, 2.0], [5.0, 2.0]),
+                ([0.5, 3.0], [5.0, 3.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn line_widths_follow_the_zoom_and_scale_factor() {
+        let mut style = GridStyle {
+            line_width: 2.0,
+            ..GridStyle::default()
+        };
+        let uniform = LineStyleUniform::new(&style, 0.25, 1.5);
+        assert_eq!(uniform.half_width, 0.375);
+        assert_eq!(uniform.padding, 0.25);
+        assert_eq!(uniform.round, 0);
+
+        style.outline_join = LineJoin::Round;
+        let uniform = LineStyleUniform::new(&style, 0.5, 1.0);
+        assert_eq!(uniform.half_width, 0.5);
+        assert_eq!(uniform.round, 1);
+    }
+}
@@ -0,0 +1,78 @@
+@group(0) @binding(0)
+var<uniform> view_proj: mat4x4<f32>;
+
+struct LineStyle {
+    half_width: f32,
+    padding: f32,
+    // Round line ends when non-zero, otherwise square ones that meet in a
+    // miter at right-angled corners
+    round: u32,
+};
+@group(1) @binding(0)
+var<uniform> style: LineStyle;
+
+struct SegmentInput {
+    @location(0) start: vec2<f32>,
+    @location(1) end: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // Relative to the middle of the line, along then across it
+    @location(1) local_position: vec2<f32>,
+    @location(2) half_length: f32,
+};
+
+// Each line is a quad covering it, its ends and its padding, with corners
+// from the vertex index like the overlay's rects
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, segment: SegmentInput) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+    );
+    let delta = segment.end - segment.start;
+    let half_length = length(delta) / 2.0;
+    var direction = vec2<f32>(1.0, 0.0);
+    if half_length > 0.0 {
+        direction = delta / (half_length * 2.0);
+    }
+    let normal = vec2<f32>(-direction.y, direction.x);
+
+    let extent = style.half_width + style.padding;
+    let local_position = corners[index] * vec2<f32>(half_length + extent, extent);
+    let position = (segment.start + segment.end) / 2.0
+        + direction * local_position.x
+        + normal * local_position.y;
+
+    var out: VertexOutput;
+    out.clip_position = view_proj * vec4<f32>(position, 0.0, 1.0);
+    out.color = segment.color;
+    out.local_position = local_position;
+    out.half_length = half_length;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let p = abs(in.local_position);
+    var distance: f32;
+    if style.round != 0u {
+        distance = length(vec2<f32>(max(p.x - in.half_length, 0.0), p.y)) - style.half_width;
+    } else {
+        let q = p - vec2<f32>(in.half_length + style.half_width, style.half_width);
+        distance = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0);
+    }
+
+    // About a pixel wide edge, so lines thinner than a pixel fade out instead
+    // of breaking up
+    let edge = max(fwidth(distance), 1e-6);
+    let coverage = clamp(0.5 - distance / edge, 0.0, 1.0);
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
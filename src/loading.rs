@@ -0,0 +1,76 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{render_stats::RenderStats, text::TextRenderer};
+
+const LOADING_TEXT: &str = "Loading...";
+
+// Clears `view` to `clear_color` with the loading text in the middle, drawn
+// while `State::new` builds everything but the text renderer
+pub fn draw_frame(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    view: &wgpu::TextureView,
+    (width, height): (u32, u32),
+    text: &mut TextRenderer,
+    clear_color: wgpu::Color,
+) {
+    let [text_width, text_height] = text.measure(LOADING_TEXT);
+    text.queue(
+        LOADING_TEXT,
+        ((width as f32 - text_width) / 2.0).round(),
+        ((height as f32 - text_height) / 2.0).round(),
+        [1.0, 1.0, 1.0, 1.0],
+    );
+    text.prepare(device, queue);
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("WGPU Loading Command Encoder"),
+    });
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("WGPU Loading Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear_color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        text.render(
+            &mut render_pass,
+            (width, height),
+            &mut RenderStats::default(),
+        );
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+}
+
+// Pending on the first poll, so whatever is polling the future it's awaited
+// in gets control back, e.g. the event loop to show the loading frame
+#[derive(Default)]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
@@ -1,69 +1,514 @@
-use std::sync::Arc;
+mod animation;
+mod args;
+mod assets;
+mod bench;
+mod bind_group_cache;
+mod bindings;
+mod camera;
+mod checkerboard;
+mod console;
+mod dynamic_buffer;
+mod frame_trace;
+mod game;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod gpu_timer;
+mod grid;
+mod grid_lines;
+mod instance;
+mod loading;
+mod math;
+mod menu;
+mod minimap;
+mod overlay;
+mod palette;
+mod puzzle;
+mod render_stats;
+mod replay;
+mod rng;
+mod scissor;
+mod settings;
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+mod shader_watcher;
+mod sprite;
+mod state;
+mod text;
+mod texture;
+mod thumbnail;
+mod uniform;
+mod upscale;
+mod vertex;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
-use pollster::FutureExt;
-use wgpu::{Features, RenderPassDescriptor};
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use args::Args;
+use bench::{Bench, BenchResult};
+use bindings::Debounce;
+use frame_trace::FrameTrace;
+use game::Game;
+use menu::AppState;
+use replay::{Recorder, Replay};
+use settings::{Settings, SETTINGS_PATH};
+use state::{RenderError, State, StateError};
+use web_time::Instant;
 use winit::{
     application::ApplicationHandler,
-    dpi::{LogicalSize, PhysicalSize, Size},
+    dpi::{LogicalSize, Size},
+    error::OsError,
     event::{ElementState, KeyEvent, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     keyboard::{KeyCode, PhysicalKey},
-    window::{self, Window, WindowButtons},
+    window::{Fullscreen, Icon, Window, WindowAttributes, WindowButtons, WindowId},
 };
 
+const WINDOW_TITLE: &str = "[WGPU] Logic Game";
+const INSPECTOR_TITLE: &str = "[WGPU] Logic Game - Inspector";
+const WINDOW_ICON: &[u8] = include_bytes!("../assets/icon.png");
+// Picks an adapter like `--gpu`, which takes precedence
+const GPU_ENV_VAR: &str = "LOGIC_GAME_GPU";
+
+// `State::new` while it's running, polled from `about_to_wait`. It presents a
+// loading frame and then yields, so the window shows that frame while the
+// rest of the state is built on the next poll.
+type PendingState = Pin<Box<dyn Future<Output = Result<State<'static>, StateError>>>>;
+
+// A window whose state is being created again after losing its device
+struct Recovery {
+    // The screen it was showing
+    app_state: AppState,
+    // Each one doubles the wait before the next attempt
+    failures: u32,
+}
+
+fn load_window_icon() -> Option<Icon> {
+    let image = match image::load_from_memory(WINDOW_ICON) {
+        Ok(image) => image.to_rgba8(),
+        Err(e) => {
+            log::warn!("An error occured while decoding the window icon: {}", e);
+            return None;
+        }
+    };
+
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height)
+        .map_err(|e| log::warn!("An error occured while creating the window icon: {}", e))
+        .ok()
+}
+
+// Every window has its own surface and state, all showing the same game. The
+// main window's state is the one that updates it, while the rest are
+// inspectors.
 struct App {
-    window: Option<Arc<Window>>,
-    state: Option<State<'static>>,
+    windows: HashMap<WindowId, (Arc<Window>, State<'static>)>,
+    // Windows whose state is still being created
+    pending_windows: HashMap<WindowId, (Arc<Window>, PendingState)>,
+    // Windows whose state is being created again after losing its device
+    recovering_windows: HashMap<WindowId, Recovery>,
+    // Recovering windows whose last attempt failed, waiting to try again at
+    // the given time
+    retrying_windows: HashMap<WindowId, (Arc<Window>, Instant)>,
+    main_window: Option<WindowId>,
+    game: Game,
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<gamepad::Gamepads>,
+    settings: Settings,
+    // The requested window size in logical pixels, so it looks the same on any
+    // display. `State` only sees the physical size winit derives from it.
     size: Size,
+    fullscreen: bool,
+    // F11 isn't an action, so it's debounced here rather than by `State`
+    fullscreen_debounce: Debounce<KeyCode>,
+    max_fps: Option<u32>,
+    gpu: Option<usize>,
+    // For smoke tests: exit once this many frames have been rendered, unless
+    // it's 0
+    frame_limit: u64,
+    frames_rendered: u64,
+    // Replaces the game with ever larger grids and prints how long their
+    // frames take
+    bench: Option<Bench>,
+    // Handed to the state once it's created
+    recorder: Option<Recorder>,
+    replay: Option<Replay>,
+    // Traces the main window's frames for `--trace`
+    trace: Option<FrameTrace>,
+    // Handed to the main window's state for `--thumbnails`
+    thumbnails: bool,
+    // Set when startup or a frame failed for good, to exit with an error code
+    failed: bool,
 }
 
 impl Default for App {
     fn default() -> Self {
+        let settings = Settings::default();
         App {
-            window: None,
-            state: None,
-            size: LogicalSize::new(1280, 720).into(),
+            windows: HashMap::new(),
+            pending_windows: HashMap::new(),
+            recovering_windows: HashMap::new(),
+            retrying_windows: HashMap::new(),
+            main_window: None,
+            game: Game::default(),
+            #[cfg(feature = "gamepad")]
+            gamepads: gamepad::Gamepads::new(),
+            size: LogicalSize::new(settings.width, settings.height).into(),
+            fullscreen: false,
+            fullscreen_debounce: Debounce::new(settings.toggle_debounce()),
+            settings,
+            max_fps: None,
+            gpu: None,
+            frame_limit: 0,
+            frames_rendered: 0,
+            bench: None,
+            recorder: None,
+            replay: None,
+            trace: None,
+            thumbnails: false,
+            failed: false,
+        }
+    }
+}
+
+impl App {
+    // Logical size of a new inspector window
+    const INSPECTOR_SIZE: u32 = 480;
+    // How long to wait before recreating a lost device's state again after
+    // the first failed attempt, doubling up to the max with each failure
+    const RECOVERY_DELAY: Duration = Duration::from_millis(250);
+    const MAX_RECOVERY_DELAY: Duration = Duration::from_secs(8);
+
+    // Opens a window and starts creating its state, which `about_to_wait`
+    // picks up once it's ready
+    fn open_window(
+        &mut self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+        attributes: WindowAttributes,
+    ) -> Result<WindowId, OsError> {
+        let attributes = attributes
+            .with_window_icon(load_window_icon())
+            .with_resizable(true);
+        // Windows are canvases there, which only show up once they're on the
+        // page
+        #[cfg(target_arch = "wasm32")]
+        let attributes = {
+            use winit::platform::web::WindowAttributesExtWebSys;
+            attributes.with_append(true)
+        };
+        let window = Arc::new(event_loop.create_window(attributes)?);
+
+        let id = window.id();
+        self.create_state(window);
+        Ok(id)
+    }
+
+    fn create_state(&mut self, window: Arc<Window>) {
+        let (settings, gpu) = (self.settings.clone(), self.gpu);
+        let pending_state: PendingState = Box::pin({
+            let window = window.clone();
+            async move { State::new(window, &settings, gpu).await }
+        });
+        self.pending_windows
+            .insert(window.id(), (window, pending_state));
+    }
+
+    // Everything made from a lost device goes with it, so the window's whole
+    // state is created again, the way it was when the window opened. The
+    // game is the app's and carries on as it was, and so do the settings
+    // changed while playing and any recording or replay.
+    fn recreate_state(&mut self, id: WindowId) {
+        let Some((window, mut state)) = self.windows.remove(&id) else {
+            return;
+        };
+
+        log::warn!("Recreating the state of a window whose GPU device was lost");
+        if self.main_window == Some(id) {
+            Self::keep_settings(&mut self.settings, self.fullscreen, &window, &state);
+            self.recorder = state.take_recorder();
+            self.replay = state.take_replay();
+        }
+        let recovery = Recovery {
+            app_state: state.app_state(),
+            failures: 0,
+        };
+        self.recovering_windows.insert(id, recovery);
+        drop(state);
+        self.create_state(window);
+    }
+
+    // The settings that can change while playing
+    fn keep_settings(settings: &mut Settings, fullscreen: bool, window: &Window, state: &State) {
+        let color = state.clear_color();
+        settings.clear_color = [color.r, color.g, color.b, color.a];
+        settings.vsync = state.vsync();
+        settings.frame_latency = state.frame_latency();
+        settings.palette = state.palette;
+        if !fullscreen {
+            let size: LogicalSize<u32> = window.inner_size().to_logical(window.scale_factor());
+            settings.width = size.width;
+            settings.height = size.height;
+        }
+    }
+
+    fn open_inspector(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let attributes = Window::default_attributes()
+            .with_title(format!("{} - Loading…", INSPECTOR_TITLE))
+            .with_inner_size(LogicalSize::new(Self::INSPECTOR_SIZE, Self::INSPECTOR_SIZE));
+        match self.open_window(event_loop, attributes) {
+            Ok(_) => log::info!("Opening an inspector window"),
+            Err(e) => log::error!(
+                "An error occured while creating the inspector window: {}",
+                e
+            ),
+        }
+    }
+
+    fn state_created(
+        &mut self,
+        window: Arc<Window>,
+        result: Result<State<'static>, StateError>,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+    ) {
+        let is_main = self.main_window == Some(window.id());
+        let recovery = self.recovering_windows.remove(&window.id());
+        let mut state = match result {
+            Ok(state) => state,
+            Err(e) => match recovery {
+                // The GPU can take a while to come back, e.g. while its
+                // driver resets
+                Some(recovery) => {
+                    self.retry_recovery(window, recovery, e);
+                    return;
+                }
+                None if is_main => {
+                    log::error!("{}", e);
+                    self.failed = true;
+                    event_loop.exit();
+                    return;
+                }
+                // The game carries on without it
+                None => {
+                    log::error!("An error occured while creating the inspector: {}", e);
+                    return;
+                }
+            },
+        };
+
+        if recovery.is_some() {
+            log::info!("Recovered from the lost GPU device");
+        }
+
+        if !is_main {
+            state.make_inspector(&self.game);
+            window.set_title(INSPECTOR_TITLE);
+            self.windows.insert(window.id(), (window, state));
+            return;
+        }
+
+        state.fit_grid(&self.game);
+        state.set_app_state(recovery.map_or(AppState::Menu, |recovery| recovery.app_state));
+        state.set_max_fps(self.max_fps);
+        state.save_thumbnails = self.thumbnails;
+        // Frames are timed as fast as they can be drawn
+        if self.bench.is_some() {
+            state.set_app_state(AppState::Playing);
+            state.set_vsync(false);
+            state.set_max_fps(None);
+        }
+        if let Some(recorder) = self.recorder.take() {
+            state.record_to(recorder);
+        }
+        // A replay starts playing right away, as its recording did once the
+        // menu was left
+        if let Some(replay) = self.replay.take() {
+            state.set_app_state(AppState::Playing);
+            state.start_replay(replay);
+        }
+        window.set_title(WINDOW_TITLE);
+        self.windows.insert(window.id(), (window, state));
+    }
+
+    fn retry_recovery(&mut self, window: Arc<Window>, mut recovery: Recovery, error: StateError) {
+        let delay = (Self::RECOVERY_DELAY * 2u32.pow(recovery.failures.min(16)))
+            .min(Self::MAX_RECOVERY_DELAY);
+        log::error!(
+            "An error occured while recovering from the lost GPU device, retrying in {:?}: {}",
+            delay,
+            error
+        );
+        recovery.failures += 1;
+        self.recovering_windows.insert(window.id(), recovery);
+        self.retrying_windows
+            .insert(window.id(), (window, Instant::now() + delay));
+    }
+
+    // Drops the state, and with it the surface, before the window it renders
+    // to
+    fn close_window(&mut self, id: WindowId) {
+        self.pending_windows.remove(&id);
+        self.recovering_windows.remove(&id);
+        self.retrying_windows.remove(&id);
+        if let Some((window, state)) = self.windows.remove(&id) {
+            drop(state);
+            drop(window);
         }
     }
 }
 
 impl ApplicationHandler for App {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
-        let window = Arc::new(
-            event_loop
-                .create_window(
-                    Window::default_attributes()
-                        .with_title("[WGPU] Logic Game")
-                        .with_resizable(false)
-                        .with_enabled_buttons(WindowButtons::CLOSE)
-                        .with_inner_size(self.size),
-                )
-                .expect("An error occured while creating the window"),
-        );
+        let attributes = Window::default_attributes()
+            .with_title(format!("{} - Loading…", WINDOW_TITLE))
+            .with_enabled_buttons(
+                WindowButtons::CLOSE | WindowButtons::MINIMIZE | WindowButtons::MAXIMIZE,
+            )
+            .with_inner_size(self.size)
+            .with_fullscreen(self.fullscreen.then_some(Fullscreen::Borderless(None)));
+        let id = self
+            .open_window(event_loop, attributes)
+            .expect("An error occured while creating the window");
+        self.main_window = Some(id);
+    }
+
+    // Saves the settings that can change while playing, then closes every
+    // window
+    fn exiting(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        // Benchmarks turn vsync off and change the grid, neither of which
+        // should stick
+        if self.bench.is_none() {
+            let main = self.main_window.and_then(|id| self.windows.get(&id));
+            if let Some((window, state)) = main {
+                Self::keep_settings(&mut self.settings, self.fullscreen, window, state);
+            }
+            self.settings.save(SETTINGS_PATH);
+        }
 
-        self.window = Some(window.clone());
-        self.state = Some(State::new(window).block_on());
+        if let Some(trace) = self.trace.as_mut() {
+            if let Err(e) = trace.flush() {
+                log::error!("An error occured while writing the frame trace: {}", e);
+            }
+        }
+
+        self.pending_windows.clear();
+        self.retrying_windows.clear();
+        let ids: Vec<_> = self.windows.keys().copied().collect();
+        for id in ids {
+            self.close_window(id);
+        }
     }
 
-    fn about_to_wait(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
-        self.window.as_ref().unwrap().request_redraw();
+    fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let now = Instant::now();
+        let due: Vec<_> = self
+            .retrying_windows
+            .iter()
+            .filter(|(_, (_, retry_at))| *retry_at <= now)
+            .map(|(&id, _)| id)
+            .collect();
+        for id in due {
+            if let Some((window, _)) = self.retrying_windows.remove(&id) {
+                self.create_state(window);
+            }
+        }
+
+        // Nothing is woken when a state is ready, but the event loop polls
+        // continuously so they're checked again straight away
+        let mut context = Context::from_waker(Waker::noop());
+        let ready: Vec<_> = self
+            .pending_windows
+            .iter_mut()
+            .filter_map(|(&id, (_, pending_state))| {
+                match pending_state.as_mut().poll(&mut context) {
+                    Poll::Ready(result) => Some((id, result)),
+                    Poll::Pending => None,
+                }
+            })
+            .collect();
+        for (id, result) in ready {
+            if let Some((window, _)) = self.pending_windows.remove(&id) {
+                self.state_created(window, result, event_loop);
+            }
+        }
+
+        let lost: Vec<_> = self
+            .windows
+            .iter()
+            .filter(|(_, (_, state))| state.is_device_lost())
+            .map(|(&id, _)| id)
+            .collect();
+        for id in lost {
+            self.recreate_state(id);
+        }
+        if self.main_window.is_some_and(|id| {
+            self.pending_windows.contains_key(&id) || self.retrying_windows.contains_key(&id)
+        }) {
+            return;
+        }
+
+        if self.frame_limit > 0 && self.frames_rendered >= self.frame_limit {
+            log::info!("Rendered {} frames, exiting", self.frames_rendered);
+            event_loop.exit();
+            return;
+        }
+
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepads) = self.gamepads.as_mut() {
+            let mut main = self.main_window.and_then(|id| self.windows.get_mut(&id));
+            for input in gamepads.poll() {
+                match main.as_mut() {
+                    Some((_, state)) => state.apply_game_input(&mut self.game, input),
+                    None => self.game.handle_input(input),
+                }
+            }
+        }
+
+        // The frame that was just presented counts towards the cap
+        if let Some((_, state)) = self.main_window.and_then(|id| self.windows.get(&id)) {
+            state.wait_for_frame_cap();
+        }
+
+        let inspector_requested = self
+            .windows
+            .values_mut()
+            .fold(false, |requested, (_, state)| {
+                state.take_inspector_request() || requested
+            });
+        if inspector_requested {
+            self.open_inspector(event_loop);
+        }
+
+        for (window, _) in self.windows.values() {
+            window.request_redraw();
+        }
     }
 
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
-        window_id: window::WindowId,
+        window_id: WindowId,
         event: winit::event::WindowEvent,
     ) {
-        if window_id != self.window.as_ref().unwrap().id() {
+        let is_main = self.main_window == Some(window_id);
+        let Some((window, state)) = self.windows.get_mut(&window_id) else {
             return;
-        }
+        };
 
-        if self.state.as_mut().unwrap().input(event.clone()) {
+        if state.input(event.clone()) {
+            if state.quit_requested() {
+                event_loop.exit();
+            }
             return;
         }
 
         match event {
+            // Closing an inspector leaves the game running
             WindowEvent::CloseRequested
             | WindowEvent::KeyboardInput {
                 event:
@@ -73,17 +518,96 @@ impl ApplicationHandler for App {
                         ..
                     },
                 ..
-            } => event_loop.exit(),
-            WindowEvent::Resized(inner_size) => self.state.as_mut().unwrap().resize(inner_size),
+            } => {
+                if is_main {
+                    event_loop.exit();
+                } else {
+                    self.close_window(window_id);
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(KeyCode::F11),
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if is_main => {
+                if !self.fullscreen_debounce.fire(KeyCode::F11, Instant::now()) {
+                    return;
+                }
+                self.fullscreen = !self.fullscreen;
+
+                // The surface picks up the new size through the `Resized` event
+                if self.fullscreen {
+                    window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+                } else {
+                    window.set_fullscreen(None);
+                    let _ = window.request_inner_size(self.size);
+                }
+            }
+            WindowEvent::Destroyed => self.close_window(window_id),
+            WindowEvent::Resized(inner_size) => state.resize(inner_size),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                state.set_scale_factor(scale_factor);
+                state.resize(window.inner_size());
+            }
             WindowEvent::RedrawRequested => {
-                let state = self.state.as_mut().unwrap();
-
-                state.update();
-                match state.render() {
-                    Ok(_) => println!("RENDER"),
-                    Err(wgpu::SurfaceError::Lost) => state.resize(state.size.to_physical(1.0)),
-                    Err(wgpu::SurfaceError::OutOfMemory) => event_loop.exit(),
-                    Err(e) => eprintln!("{:?}", e),
+                state.update(&mut self.game);
+                let result = state.render(&self.game);
+                if is_main {
+                    self.frames_rendered += 1;
+                    if let Some(bench) = self.bench.as_mut() {
+                        let buffer_allocations = state.buffer_allocations();
+                        let stats = result.as_ref().copied().unwrap_or_default();
+                        if let Some(result) =
+                            bench.frame_rendered(Instant::now(), buffer_allocations, stats)
+                        {
+                            println!("{}", result.csv_row());
+                            match bench.game() {
+                                Some(game) => self.game = game,
+                                None => event_loop.exit(),
+                            }
+                        }
+                    }
+                }
+                // Only the errors a frame can't recover from count as a failure
+                match result {
+                    Ok(_) => {}
+                    Err(RenderError::Surface(
+                        wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated,
+                    )) => state.reconfigure(),
+                    Err(e @ RenderError::Surface(wgpu::SurfaceError::OutOfMemory)) => {
+                        log::error!("{}", e);
+                        self.failed = true;
+                        event_loop.exit();
+                    }
+                    Err(RenderError::Surface(wgpu::SurfaceError::Timeout)) => {
+                        log::debug!("Skipping a timed out frame")
+                    }
+                    Err(e @ RenderError::ScreenshotReadback(_)) => {
+                        log::error!("{}", e);
+                        self.failed = true;
+                    }
+                }
+
+                // Includes any reconfiguring the result above led to
+                if let Some(trace) = self.trace.as_mut().filter(|_| is_main) {
+                    let traced = trace.record(
+                        state.frame_time(),
+                        state.take_reconfigured(),
+                        state.present_mode(),
+                        state.frame_latency(),
+                    );
+                    if let Err(e) = traced {
+                        log::error!(
+                            "An error occured while writing the frame trace, stopping: {}",
+                            e
+                        );
+                        self.trace = None;
+                    }
                 }
             }
             _ => {}
@@ -91,159 +615,118 @@ impl ApplicationHandler for App {
     }
 }
 
-struct State<'a> {
-    surface: wgpu::Surface<'a>,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
-    size: Size,
+#[cfg(not(target_arch = "wasm32"))]
+#[pollster::main]
+async fn main() {
+    env_logger::init();
+    run().await;
 }
 
-impl<'a> State<'a> {
-    async fn new(window: Arc<Window>) -> State<'a> {
-        let size = window.inner_size();
-
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            ..Default::default()
-        });
+// The browser's main thread can't be blocked on, so the app is started as a
+// task on the page's event loop instead
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    web::init_logger();
+    wasm_bindgen_futures::spawn_local(run());
+}
 
-        let surface = instance.create_surface(window.clone()).expect(
-            format!(
-                "An error occured while creating a surface with the instance {:?}",
-                instance
-            )
-            .as_str(),
-        );
+async fn run() {
+    let event_loop = EventLoop::new().expect("An error occured while creating the event loop");
+    event_loop.set_control_flow(ControlFlow::Poll);
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptionsBase {
-                power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect(
-                format!(
-                    "An error occured while requesting an adapter with the instance {:?}",
-                    instance
-                )
-                .as_str(),
-            );
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features: Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                    ..Default::default()
-                },
-                None,
-            )
-            .await
-            .expect(
-                format!(
-                    "An error occured while requesting a device from the adapter {:?}",
-                    adapter
-                )
-                .as_str(),
-            );
-
-        let surface_capabilities = surface.get_capabilities(&adapter);
-        let surface_format = surface_capabilities
-            .formats
-            .iter()
-            .copied()
-            .filter(|s| s.is_srgb())
-            .next()
-            .unwrap_or(surface_capabilities.formats[0]);
-
-        let config = wgpu::SurfaceConfiguration {
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
-            alpha_mode: surface_capabilities.alpha_modes[0],
-            format: surface_format,
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    // Command-line arguments take precedence over the settings file
+    let args = Args::parse(std::env::args().skip(1));
+    let settings = Settings::load(SETTINGS_PATH);
+    let game = match args.seed.or(settings.seed) {
+        Some(seed) => {
+            log::info!("Generating a puzzle from seed {}", seed);
+            Game::from_seed(seed)
+        }
+        None => Game::default(),
+    };
+    let mut app = App {
+        game,
+        max_fps: args.fps.or(settings.max_fps),
+        gpu: args.gpu.or_else(|| {
+            let value = std::env::var(GPU_ENV_VAR).ok()?;
+            args::parse_unsigned(GPU_ENV_VAR, Some(value))
+        }),
+        size: LogicalSize::new(
+            args.width.unwrap_or(settings.width),
+            args.height.unwrap_or(settings.height),
+        )
+        .into(),
+        fullscreen: args.fullscreen,
+        fullscreen_debounce: Debounce::new(settings.toggle_debounce()),
+        settings,
+        frame_limit: args.frames,
+        thumbnails: args.thumbnails,
+        ..Default::default()
+    };
+    if args.bench {
+        let frames = match args.frames {
+            0 => Bench::DEFAULT_FRAMES_PER_STAGE,
+            frames => frames,
         };
-
-        Self {
-            size: Size::Physical(size.clone()),
-            config,
-            device,
-            queue,
-            surface,
+        let bench = Bench::new(frames);
+        app.game = bench
+            .game()
+            .expect("The first benchmark stage always has a grid");
+        app.bench = Some(bench);
+        app.frame_limit = 0;
+        println!("{}", BenchResult::CSV_HEADER);
+    }
+    if let Some(path) = args.record.as_ref() {
+        match Recorder::create(path) {
+            Ok(recorder) => {
+                log::info!("Recording input to {}", path.display());
+                app.recorder = Some(recorder);
+            }
+            Err(e) => log::error!(
+                "An error occured while creating {}, not recording: {}",
+                path.display(),
+                e
+            ),
         }
     }
-
-    fn resize(&mut self, size: PhysicalSize<u32>) {
-        if size.width <= 0 || size.height <= 0 {
-            return;
+    if let Some(path) = args.trace.as_ref() {
+        match FrameTrace::create(path) {
+            Ok(trace) => {
+                log::info!("Tracing frames to {}", path.display());
+                app.trace = Some(trace);
+            }
+            Err(e) => log::error!(
+                "An error occured while creating {}, not tracing: {}",
+                path.display(),
+                e
+            ),
         }
-
-        self.size = size.into();
-        self.config.width = size.width;
-        self.config.height = size.height;
-        self.surface.configure(&self.device, &self.config);
     }
-
-    fn input(&mut self, _event: WindowEvent) -> bool {
-        false
+    if let Some(path) = args.replay.as_ref() {
+        match Replay::load(path) {
+            Ok(replay) => app.replay = Some(replay),
+            Err(e) => {
+                log::error!("{}", e);
+                std::process::exit(1);
+            }
+        }
     }
 
-    fn update(&mut self) {}
-
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("WGPU Render Command Encoder"),
-            });
+    // Returns straight away, and the browser calls back into the app from
+    // then on
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::EventLoopExtWebSys;
+        event_loop.spawn_app(app);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        event_loop
+            .run_app(&mut app)
+            .expect("An error occured while running the app");
 
-        {
-            encoder.begin_render_pass(&RenderPassDescriptor {
-                label: Some("WGPU Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.2,
-                            g: 0.2,
-                            b: 0.2,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
+        if app.failed {
+            std::process::exit(1);
         }
-
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-        Ok(())
     }
 }
-
-#[pollster::main]
-async fn main() {
-    env_logger::init();
-    let event_loop = EventLoop::new().expect("An error occured while creating the event loop");
-    event_loop.set_control_flow(ControlFlow::Poll);
-
-    let mut app = App::default();
-
-    event_loop
-        .run_app(&mut app)
-        .expect("An error occured while running the app");
-}
@@ -0,0 +1,32 @@
+// Maps a position in physical pixels (origin top-left, y down) within a
+// `width` x `height` area to normalized device coordinates (origin center,
+// y up). Positions outside the area map past -1..1 rather than being clamped,
+// so callers can tell the cursor left it.
+pub fn pixel_to_ndc(x: f64, y: f64, width: u32, height: u32) -> (f32, f32) {
+    let (width, height) = (width.max(1) as f64, height.max(1) as f64);
+    let ndc_x = x / width * 2.0 - 1.0;
+    let ndc_y = 1.0 - y / height * 2.0;
+    (ndc_x as f32, ndc_y as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corners_and_center_map_to_ndc() {
+        assert_eq!(pixel_to_ndc(0.0, 0.0, 800, 600), (-1.0, 1.0));
+        assert_eq!(pixel_to_ndc(800.0, 0.0, 800, 600), (1.0, 1.0));
+        assert_eq!(pixel_to_ndc(0.0, 600.0, 800, 600), (-1.0, -1.0));
+        assert_eq!(pixel_to_ndc(800.0, 600.0, 800, 600), (1.0, -1.0));
+        assert_eq!(pixel_to_ndc(400.0, 300.0, 800, 600), (0.0, 0.0));
+    }
+
+    #[test]
+    fn positions_off_screen_map_past_the_edges() {
+        assert_eq!(pixel_to_ndc(-400.0, 900.0, 800, 600), (-2.0, -2.0));
+        assert_eq!(pixel_to_ndc(1200.0, -150.0, 800, 600), (2.0, 1.5));
+        // An empty area doesn't divide by zero
+        assert_eq!(pixel_to_ndc(0.0, 0.0, 0, 0), (-1.0, 1.0));
+    }
+}
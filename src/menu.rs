@@ -0,0 +1,44 @@
+// Which screen the app is showing. The game only runs while `Playing`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppState {
+    Menu,
+    Playing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    Play,
+    Quit,
+}
+
+impl MenuItem {
+    // In the order they're shown, top to bottom
+    pub const ALL: [MenuItem; 2] = [MenuItem::Play, MenuItem::Quit];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MenuItem::Play => "Play",
+            MenuItem::Quit => "Quit",
+        }
+    }
+}
+
+// A menu item laid out on screen, in pixels from the top-left of the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MenuEntry {
+    pub item: MenuItem,
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+}
+
+impl MenuEntry {
+    // Hit-tests against the text's bounding box grown by `padding` on every
+    // side, so short labels are easy to click
+    pub fn contains(&self, point: (f64, f64), padding: f32) -> bool {
+        let (x, y) = (point.0 as f32, point.1 as f32);
+        let [left, top] = self.position;
+        let [width, height] = self.size;
+        (left - padding..=left + width + padding).contains(&x)
+            && (top - padding..=top + height + padding).contains(&y)
+    }
+}
@@ -0,0 +1,372 @@
+use crate::{grid::Grid, palette::Palette, render_stats::RenderStats, scissor::ScissorRect};
+
+// A rectangle in physical pixels from the top-left of the surface, as
+// (x, y, width, height) like `State::viewport`
+pub type MinimapRect = (f32, f32, f32, f32);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MinimapUniform {
+    view_min: [f32; 2],
+    view_max: [f32; 2],
+    size: [f32; 2],
+    outline_width: f32,
+    _padding: f32,
+}
+
+struct CellTexture {
+    size: (u32, u32),
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+// The whole grid scaled down into a corner of the screen, with the part the
+// camera sees outlined. Cells are sampled into a texture of at most
+// `MAX_TEXELS` along either side, so a large grid costs no more to show than
+// a small one.
+pub struct Minimap {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    cells: Option<CellTexture>,
+    texels: Vec<u8>,
+}
+
+impl Minimap {
+    const MAX_TEXELS: usize = 128;
+
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("WGPU Minimap Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("minimap.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WGPU Minimap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("WGPU Minimap Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        // Each texel stands for a block of cells, so there's nothing to gain
+        // from blending them
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("WGPU Minimap Sampler"),
+            ..Default::default()
+        });
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("WGPU Minimap Uniform Buffer"),
+            size: std::mem::size_of::<MinimapUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            bind_group_layout,
+            pipeline,
+            sampler,
+            uniform_buffer,
+            cells: None,
+            texels: Vec::new(),
+        }
+    }
+
+    // Where the minimap of a grid goes on a surface of the given size: the
+    // bottom-right corner, `side` pixels along the grid's longer axis. `None`
+    // when the surface is too small to fit it.
+    pub fn layout(
+        grid_size: (usize, usize),
+        surface_size: (u32, u32),
+        side: f32,
+        margin: f32,
+    ) -> Option<MinimapRect> {
+        let (surface_width, surface_height) = (surface_size.0 as f32, surface_size.1 as f32);
+        let side = side
+            .min(surface_width - 2.0 * margin)
+            .min(surface_height - 2.0 * margin)
+            .floor();
+        if side < 1.0 {
+            return None;
+        }
+
+        let longer = grid_size.0.max(grid_size.1).max(1) as f32;
+        let width = (side * grid_size.0 as f32 / longer).max(1.0).round();
+        let height = (side * grid_size.1 as f32 / longer).max(1.0).round();
+        Some((
+            surface_width - margin - width,
+            surface_height - margin - height,
+            width,
+            height,
+        ))
+    }
+
+    pub fn contains(rect: MinimapRect, position: (f64, f64)) -> bool {
+        let (x, y, width, height) = rect;
+        let (px, py) = (position.0 as f32, position.1 as f32);
+        px >= x && px < x + width && py >= y && py < y + height
+    }
+
+    // The world position under a pixel of the minimap, clamped to the grid.
+    // The minimap's top row is the grid's, which has the highest y in world
+    // space.
+    pub fn to_world(
+        rect: MinimapRect,
+        grid_size: (usize, usize),
+        position: (f64, f64),
+    ) -> [f32; 2] {
+        let (x, y, width, height) = rect;
+        let u = ((position.0 as f32 - x) / width).clamp(0.0, 1.0);
+        let v = ((position.1 as f32 - y) / height).clamp(0.0, 1.0);
+        [u * grid_size.0 as f32, (1.0 - v) * grid_size.1 as f32]
+    }
+
+    fn texture_size(grid_size: (usize, usize)) -> (u32, u32) {
+        let longer = grid_size.0.max(grid_size.1).max(1);
+        let scale = (Self::MAX_TEXELS as f32 / longer as f32).min(1.0);
+        let texels = |cells: usize| ((cells as f32 * scale).ceil() as u32).max(1);
+        (texels(grid_size.0), texels(grid_size.1))
+    }
+
+    // Samples the grid's cells into the texture and outlines `view`, the
+    // world-space (min, max) corners the camera sees, on a minimap
+    // `rect_size` pixels large
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        grid: &Grid,
+        palette: Palette,
+        view: ([f32; 2], [f32; 2]),
+        rect_size: (f32, f32),
+        outline_width: f32,
+    ) {
+        let grid_size = (grid.width(), grid.height());
+        let size = Self::texture_size(grid_size);
+        if self.cells.as_ref().map(|cells| cells.size) != Some(size) {
+            self.cells = Some(self.create_cell_texture(device, size));
+        }
+
+        // Texels sample the cell under their center. Palette colors are used
+        // as they are, since the texture isn't sRGB.
+        self.texels.clear();
+        for row in 0..size.1 {
+            let y = grid_size.1.saturating_sub(
+                1 + ((row as f32 + 0.5) * grid_size.1 as f32 / size.1 as f32) as usize,
+            );
+            for column in 0..size.0 {
+                let x = ((column as f32 + 0.5) * grid_size.0 as f32 / size.0 as f32) as usize;
+                let cell = grid.get(x, y).unwrap_or_default();
+                let color = palette.cell_color(cell, grid.signal(x, y));
+                self.texels
+                    .extend(color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8));
+            }
+        }
+        if let Some(cells) = &self.cells {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &cells.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &self.texels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * size.0),
+                    rows_per_image: Some(size.1),
+                },
+                wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let (grid_width, grid_height) = (grid_size.0.max(1) as f32, grid_size.1.max(1) as f32);
+        let (min, max) = view;
+        let uniform = MinimapUniform {
+            view_min: [min[0] / grid_width, 1.0 - max[1] / grid_height],
+            view_max: [max[0] / grid_width, 1.0 - min[1] / grid_height],
+            size: [rect_size.0, rect_size.1],
+            outline_width,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    fn create_cell_texture(&self, device: &wgpu::Device, size: (u32, u32)) -> CellTexture {
+        log::debug!("Creating a {}x{} minimap texture", size.0, size.1);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("WGPU Minimap Texture"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("WGPU Minimap Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        CellTexture {
+            size,
+            texture,
+            bind_group,
+        }
+    }
+
+    // Draws into `rect` through the pass's viewport and scissor rect, and
+    // resets both to the whole `surface_size` after. The rect must lie within
+    // the surface, as `layout` makes sure of.
+    pub fn render<'p>(
+        &'p self,
+        render_pass: &mut wgpu::RenderPass<'p>,
+        rect: MinimapRect,
+        surface_size: (u32, u32),
+        stats: &mut RenderStats,
+    ) {
+        let Some(cells) = &self.cells else {
+            return;
+        };
+
+        let (x, y, width, height) = rect;
+        let scissor = ScissorRect::clamped([x, y], [width, height], surface_size);
+        if scissor.width == 0 || scissor.height == 0 {
+            return;
+        }
+
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        scissor.apply(render_pass);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &cells.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        stats.record_draw(3, 1);
+
+        let (surface_width, surface_height) = surface_size;
+        render_pass.set_viewport(
+            0.0,
+            0.0,
+            surface_width as f32,
+            surface_height as f32,
+            0.0,
+            1.0,
+        );
+        ScissorRect::full(surface_size).apply(render_pass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimap_pixels_map_back_onto_the_grid() {
+        let rect = Minimap::layout((40, 20), (800, 600), 160.0, 8.0).unwrap();
+        assert_eq!(rect, (632.0, 512.0, 160.0, 80.0));
+        assert!(Minimap::contains(rect, (700.0, 550.0)));
+        assert!(!Minimap::contains(rect, (700.0, 500.0)));
+
+        assert_eq!(
+            Minimap::to_world(rect, (40, 20), (632.0, 512.0)),
+            [0.0, 20.0]
+        );
+        assert_eq!(
+            Minimap::to_world(rect, (40, 20), (712.0, 552.0)),
+            [20.0, 10.0]
+        );
+        // Positions past the edges land on them
+        assert_eq!(
+            Minimap::to_world(rect, (40, 20), (900.0, 700.0)),
+            [40.0, 0.0]
+        );
+
+        // Too small a window has no room for it
+        assert!(Minimap::layout((40, 20), (16, 16), 160.0, 8.0).is_none());
+    }
+
+    #[test]
+    fn large_grids_are_sampled_down() {
+        assert_eq!(Minimap::texture_size((8, 3)), (8, 3));
+        assert_eq!(Minimap::texture_size((1024, 256)), (128, 32));
+        assert_eq!(Minimap::texture_size((1000, 1)), (128, 1));
+    }
+}
@@ -0,0 +1,49 @@
+@group(0) @binding(0)
+var t_cells: texture_2d<f32>;
+@group(0) @binding(1)
+var s_cells: sampler;
+
+struct Minimap {
+    // The part of the grid the camera sees, in texture coordinates
+    view_min: vec2<f32>,
+    view_max: vec2<f32>,
+    // In pixels
+    size: vec2<f32>,
+    outline_width: f32,
+};
+@group(0) @binding(2)
+var<uniform> minimap: Minimap;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+// A single triangle covering the whole viewport, like the upscaler's
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let ndc = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u)) * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.tex_coords = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_cells, s_cells, in.tex_coords);
+
+    // The view is outlined along the minimap's edges where it reaches past
+    // them, so it stays visible when zoomed out beyond the grid
+    let position = in.tex_coords * minimap.size;
+    let view_min = clamp(minimap.view_min * minimap.size, vec2<f32>(0.0), minimap.size);
+    let view_max = clamp(minimap.view_max * minimap.size, vec2<f32>(0.0), minimap.size);
+    let outside = any(position < view_min) || any(position > view_max);
+    let inner = any(position < view_min + minimap.outline_width)
+        || any(position > view_max - minimap.outline_width);
+    if !outside && inner {
+        return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+    }
+    return color;
+}
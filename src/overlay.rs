@@ -0,0 +1,195 @@
+use wgpu::util::DeviceExt;
+
+use crate::{
+    dynamic_buffer::DynamicBuffer,
+    render_stats::RenderStats,
+    scissor::{ClipRuns, ScissorRect},
+    uniform::ScreenUniform,
+};
+
+// A screen-space rectangle in pixels from the top-left of the target, drawn
+// as one instance of the overlay quad
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Rect {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl Rect {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = [
+        wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 0,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+            shader_location: 1,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            shader_location: 2,
+            format: wgpu::VertexFormat::Float32x4,
+        },
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Rect>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Draws alpha-blended rectangles over the finished frame. Like the text
+// renderer, rects are queued during the frame and uploaded in one go.
+pub struct OverlayRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    screen_buffer: wgpu::Buffer,
+    instance_buffer: DynamicBuffer<Rect>,
+    rects: Vec<Rect>,
+    clips: ClipRuns,
+}
+
+impl OverlayRenderer {
+    const INITIAL_INSTANCE_CAPACITY: usize = 256;
+
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let screen_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("WGPU Overlay Screen Buffer"),
+            contents: bytemuck::cast_slice(&[ScreenUniform::new(width, height)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("WGPU Overlay Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("WGPU Overlay Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("overlay.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WGPU Overlay Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("WGPU Overlay Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[Rect::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let instance_buffer = DynamicBuffer::new(
+            device,
+            "WGPU Overlay Instance Buffer",
+            wgpu::BufferUsages::VERTEX,
+            Self::INITIAL_INSTANCE_CAPACITY,
+        );
+
+        Self {
+            pipeline,
+            bind_group,
+            screen_buffer,
+            instance_buffer,
+            rects: Vec::new(),
+            clips: ClipRuns::default(),
+        }
+    }
+
+    pub fn resize(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&[ScreenUniform::new(width, height)]),
+        );
+    }
+
+    pub fn queue(&mut self, rect: Rect) {
+        self.rects.push(rect);
+    }
+
+    // Clips the rects queued from now on, or stops clipping them with `None`
+    pub fn set_clip(&mut self, rect: Option<ScissorRect>) {
+        self.clips.set(self.rects.len() as u32, rect);
+    }
+
+    // Uploads everything queued since the last call, growing the instance
+    // buffer if needed, and clears the queue for the next frame
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.instance_buffer.write(device, queue, &self.rects);
+        self.clips.prepare(self.instance_buffer.len());
+        self.rects.clear();
+    }
+
+    pub fn allocations(&self) -> u64 {
+        self.instance_buffer.allocations()
+    }
+
+    pub fn render<'p>(
+        &'p self,
+        render_pass: &mut wgpu::RenderPass<'p>,
+        surface_size: (u32, u32),
+        stats: &mut RenderStats,
+    ) {
+        if self.instance_buffer.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice());
+        self.clips
+            .render(render_pass, surface_size, |render_pass, rects| {
+                stats.record_draw(6, rects.len() as u32);
+                render_pass.draw(0..6, rects)
+            });
+    }
+}
@@ -0,0 +1,39 @@
+@group(0) @binding(0)
+var<uniform> screen_size: vec2<f32>;
+
+struct RectInput {
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+// Each rect is two triangles whose corners come from the vertex index, so no
+// vertex buffer is needed. Positions are in pixels from the top-left corner.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, rect: RectInput) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+    );
+    let position = rect.position + corners[index] * rect.size;
+    let ndc = position / screen_size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.color = rect.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use crate::grid::Cell;
+
+// The colors cells are tinted with. Gate types also differ by their atlas
+// tile, so the alternatives mainly keep on and off wires, sources and sinks
+// apart by brightness as well as hue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Palette {
+    #[default]
+    Default,
+    // Avoids telling red and green apart, using blues against oranges
+    Deuteranopia,
+    // Like `Deuteranopia`, but without reds, which look dark to protans
+    Protanopia,
+    // Saturated colors against near-black empty cells
+    HighContrast,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 4] = [
+        Palette::Default,
+        Palette::Deuteranopia,
+        Palette::Protanopia,
+        Palette::HighContrast,
+    ];
+
+    pub fn next(self) -> Palette {
+        let i = Self::ALL
+            .iter()
+            .position(|&palette| palette == self)
+            .unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    // Flashed over cells that never settle, so it only needs to stand out
+    // from whatever it's flashing over
+    pub fn unstable_color(self) -> [f32; 4] {
+        match self {
+            Palette::Default | Palette::HighContrast => [1.0, 0.1, 0.1, 1.0],
+            Palette::Deuteranopia | Palette::Protanopia => [1.0, 0.0, 1.0, 1.0],
+        }
+    }
+
+    pub fn cell_color(self, cell: Cell, on: bool) -> [f32; 4] {
+        match self {
+            Palette::Default => match (cell, on) {
+                (Cell::Empty, _) => [0.35, 0.35, 0.4, 1.0],
+                (Cell::Wire, false) => [0.45, 0.3, 0.2, 1.0],
+                (Cell::Wire, true) => [1.0, 0.6, 0.2, 1.0],
+                (Cell::And, _) => [0.3, 0.45, 0.9, 1.0],
+                (Cell::Or, _) => [0.3, 0.8, 0.4, 1.0],
+                (Cell::Not, _) => [0.7, 0.35, 0.85, 1.0],
+                (Cell::Source, _) => [1.0, 0.9, 0.3, 1.0],
+                (Cell::Sink, false) => [0.25, 0.25, 0.25, 1.0],
+                (Cell::Sink, true) => [0.4, 1.0, 0.5, 1.0],
+            },
+            // Based on the Okabe-Ito palette
+            Palette::Deuteranopia => match (cell, on) {
+                (Cell::Empty, _) => [0.35, 0.35, 0.38, 1.0],
+                (Cell::Wire, false) => [0.2, 0.25, 0.4, 1.0],
+                (Cell::Wire, true) => [0.9, 0.6, 0.0, 1.0],
+                (Cell::And, _) => [0.0, 0.45, 0.7, 1.0],
+                (Cell::Or, _) => [0.35, 0.7, 0.9, 1.0],
+                (Cell::Not, _) => [0.8, 0.6, 0.7, 1.0],
+                (Cell::Source, _) => [0.95, 0.9, 0.25, 1.0],
+                (Cell::Sink, false) => [0.2, 0.2, 0.2, 1.0],
+                (Cell::Sink, true) => [0.95, 0.95, 0.95, 1.0],
+            },
+            Palette::Protanopia => match (cell, on) {
+                (Cell::Empty, _) => [0.35, 0.35, 0.38, 1.0],
+                (Cell::Wire, false) => [0.2, 0.25, 0.4, 1.0],
+                (Cell::Wire, true) => [0.95, 0.9, 0.25, 1.0],
+                (Cell::And, _) => [0.0, 0.45, 0.7, 1.0],
+                (Cell::Or, _) => [0.0, 0.6, 0.5, 1.0],
+                (Cell::Not, _) => [0.8, 0.6, 0.7, 1.0],
+                (Cell::Source, _) => [1.0, 1.0, 1.0, 1.0],
+                (Cell::Sink, false) => [0.2, 0.2, 0.2, 1.0],
+                (Cell::Sink, true) => [0.35, 0.7, 0.9, 1.0],
+            },
+            Palette::HighContrast => match (cell, on) {
+                (Cell::Empty, _) => [0.08, 0.08, 0.08, 1.0],
+                (Cell::Wire, false) => [0.4, 0.4, 0.4, 1.0],
+                (Cell::Wire, true) => [1.0, 1.0, 1.0, 1.0],
+                (Cell::And, _) => [0.2, 0.6, 1.0, 1.0],
+                (Cell::Or, _) => [0.0, 1.0, 0.6, 1.0],
+                (Cell::Not, _) => [1.0, 0.3, 1.0, 1.0],
+                (Cell::Source, _) => [1.0, 0.85, 0.0, 1.0],
+                (Cell::Sink, false) => [0.2, 0.2, 0.2, 1.0],
+                (Cell::Sink, true) => [0.0, 1.0, 1.0, 1.0],
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_cell_state_has_its_own_color() {
+        let states = [
+            (Cell::Empty, false),
+            (Cell::Wire, false),
+            (Cell::Wire, true),
+            (Cell::And, false),
+            (Cell::Or, false),
+            (Cell::Not, false),
+            (Cell::Source, false),
+            (Cell::Sink, false),
+            (Cell::Sink, true),
+        ];
+
+        for palette in Palette::ALL {
+            let colors = states.map(|(cell, on)| palette.cell_color(cell, on));
+            for (i, a) in colors.iter().enumerate() {
+                for b in &colors[i + 1..] {
+                    assert_ne!(a, b, "{:?} reuses a color", palette);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::grid::{Cell, Evaluation, Grid};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PuzzleError {
+    #[cfg(not(target_arch = "wasm32"))]
+    Truncated,
+    TooLarge {
+        inputs: usize,
+        outputs: usize,
+    },
+    OutOfBounds {
+        x: usize,
+        y: usize,
+    },
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            PuzzleError::Truncated => write!(f, "The puzzle ends early"),
+            PuzzleError::TooLarge { inputs, outputs } => write!(
+                f,
+                "The puzzle has {} inputs and {} outputs, more than {} and {}",
+                inputs,
+                outputs,
+                Puzzle::MAX_INPUTS,
+                Puzzle::MAX_OUTPUTS
+            ),
+            PuzzleError::OutOfBounds { x, y } => {
+                write!(f, "The puzzle's cell ({}, {}) is off the grid", x, y)
+            }
+        }
+    }
+}
+
+impl Error for PuzzleError {}
+
+// An objective for the circuit: input cells that are switched on and off
+// through every combination, sinks to watch, and what each sink should read
+// for each combination. Inputs are sources while on and empty while off.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Puzzle {
+    pub inputs: Vec<(usize, usize)>,
+    pub outputs: Vec<(usize, usize)>,
+    // One row per combination, with input i on in the rows whose index has
+    // bit i set, and one value per output
+    expected: Vec<Vec<bool>>,
+}
+
+impl Puzzle {
+    // Every combination is evaluated after each edit, so this keeps that at
+    // a few dozen evaluations
+    pub const MAX_INPUTS: usize = 6;
+    // Each row's outputs are saved as the bits of a byte
+    pub const MAX_OUTPUTS: usize = 8;
+
+    // Saved puzzles are the magic bytes, the input and output counts as
+    // bytes, each input then output position as little-endian u16s, and a
+    // byte per truth table row. The circuit follows in its own format.
+    #[cfg(not(target_arch = "wasm32"))]
+    const MAGIC: &'static [u8; 4] = b"LGP1";
+
+    // The expected outputs come from `table`, called with every combination
+    // of inputs
+    pub fn new(
+        inputs: Vec<(usize, usize)>,
+        outputs: Vec<(usize, usize)>,
+        table: impl Fn(&[bool]) -> Vec<bool>,
+    ) -> Result<Self, PuzzleError> {
+        Self::check_counts(inputs.len(), outputs.len())?;
+        let expected = (0..1 << inputs.len())
+            .map(|combination| {
+                let row = table(&Self::input_values(inputs.len(), combination));
+                assert_eq!(row.len(), outputs.len(), "one value per output");
+                row
+            })
+            .collect();
+        Ok(Puzzle {
+            inputs,
+            outputs,
+            expected,
+        })
+    }
+
+    fn check_counts(inputs: usize, outputs: usize) -> Result<(), PuzzleError> {
+        if inputs > Self::MAX_INPUTS || outputs > Self::MAX_OUTPUTS {
+            return Err(PuzzleError::TooLarge { inputs, outputs });
+        }
+        Ok(())
+    }
+
+    fn input_values(inputs: usize, combination: usize) -> Vec<bool> {
+        (0..inputs).map(|i| combination >> i & 1 != 0).collect()
+    }
+
+    pub fn is_fixed(&self, cell: (usize, usize)) -> bool {
+        self.inputs.contains(&cell) || self.outputs.contains(&cell)
+    }
+
+    pub fn check_bounds(&self, grid: &Grid) -> Result<(), PuzzleError> {
+        match self
+            .inputs
+            .iter()
+            .chain(&self.outputs)
+            .find(|&&(x, y)| grid.get(x, y).is_none())
+        {
+            Some(&(x, y)) => Err(PuzzleError::OutOfBounds { x, y }),
+            None => Ok(()),
+        }
+    }
+
+    // Puts the inputs, all on, and the outputs into the grid for the player
+    // to wire up
+    pub fn place(&self, grid: &mut Grid) {
+        for &(x, y) in &self.inputs {
+            grid.set(x, y, Cell::Source);
+        }
+        for &(x, y) in &self.outputs {
+            grid.set(x, y, Cell::Sink);
+        }
+    }
+
+    // Whether every output reads as expected for every combination of
+    // inputs. Circuits that never settle for some combination fail it.
+    pub fn is_solved_by(&self, grid: &Grid) -> bool {
+        self.expected.iter().enumerate().all(|(combination, row)| {
+            let mut grid = grid.clone();
+            let values = Self::input_values(self.inputs.len(), combination);
+            for (&(x, y), on) in self.inputs.iter().zip(values) {
+                grid.set(x, y, if on { Cell::Source } else { Cell::Empty });
+            }
+
+            grid.evaluate() == Evaluation::Stable
+                && self.outputs.iter().zip(row).all(|(&(x, y), &expected)| {
+                    grid.get(x, y) == Some(Cell::Sink) && grid.signal(x, y) == expected
+                })
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(*Self::MAGIC);
+        bytes.extend_from_slice(&[self.inputs.len() as u8, self.outputs.len() as u8]);
+        for &(x, y) in self.inputs.iter().chain(&self.outputs) {
+            bytes.extend_from_slice(&(x as u16).to_le_bytes());
+            bytes.extend_from_slice(&(y as u16).to_le_bytes());
+        }
+        bytes.extend(self.expected.iter().map(|row| {
+            row.iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, &on)| byte | (on as u8) << i)
+        }));
+        bytes
+    }
+
+    // Splits a puzzle off the front of a save, returning it and the rest.
+    // Saves without one are returned whole.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn split_bytes(bytes: &[u8]) -> Result<(Option<Puzzle>, &[u8]), PuzzleError> {
+        let Some(mut rest) = bytes.strip_prefix(Self::MAGIC) else {
+            return Ok((None, bytes));
+        };
+        let mut take = |len: usize| {
+            let (taken, remaining) = rest.split_at_checked(len).ok_or(PuzzleError::Truncated)?;
+            rest = remaining;
+            Ok(taken)
+        };
+
+        let counts = take(2)?;
+        let (num_inputs, num_outputs) = (counts[0] as usize, counts[1] as usize);
+        Self::check_counts(num_inputs, num_outputs)?;
+        let mut positions = take(4 * (num_inputs + num_outputs))?
+            .chunks_exact(4)
+            .map(|position| {
+                (
+                    u16::from_le_bytes([position[0], position[1]]) as usize,
+                    u16::from_le_bytes([position[2], position[3]]) as usize,
+                )
+            })
+            .collect::<Vec<_>>();
+        let outputs = positions.split_off(num_inputs);
+        let expected = take(1 << num_inputs)?
+            .iter()
+            .map(|&byte| (0..num_outputs).map(|i| byte >> i & 1 != 0).collect())
+            .collect();
+
+        let puzzle = Puzzle {
+            inputs: positions,
+            outputs,
+            expected,
+        };
+        Ok((Some(puzzle), rest))
+    }
+}
+
+// The built-in puzzles, each one gate's truth table from two inputs down the
+// left edge, or one for NOT, to a sink on the right
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Challenge {
+    And,
+    Or,
+    Not,
+    Xor,
+}
+
+impl FromStr for Challenge {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        match name {
+            "and" => Ok(Challenge::And),
+            "or" => Ok(Challenge::Or),
+            "not" => Ok(Challenge::Not),
+            "xor" => Ok(Challenge::Xor),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Challenge {
+    const WIDTH: usize = 7;
+
+    // An empty grid with room to solve the puzzle in, and the puzzle
+    pub fn build(self) -> (Grid, Puzzle) {
+        let num_inputs = if self == Challenge::Not { 1 } else { 2 };
+        let height = 2 * num_inputs + 1;
+        let inputs = (0..num_inputs).map(|i| (0, 2 * i + 1)).collect();
+        let outputs = vec![(Self::WIDTH - 1, height / 2)];
+        let puzzle = Puzzle::new(inputs, outputs, |values| {
+            vec![match self {
+                Challenge::And => values[0] && values[1],
+                Challenge::Or => values[0] || values[1],
+                Challenge::Not => !values[0],
+                Challenge::Xor => values[0] != values[1],
+            }]
+        })
+        .expect("built-in puzzles are small enough");
+
+        let mut grid = Grid::new(Self::WIDTH, height);
+        puzzle.place(&mut grid);
+        grid.evaluate();
+        (grid, puzzle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn puzzles_round_trip_through_bytes() {
+        let (_, puzzle) = Challenge::Xor.build();
+        let mut bytes = puzzle.to_bytes();
+        bytes.extend_from_slice(b"rest");
+
+        let (loaded, rest) = Puzzle::split_bytes(&bytes).unwrap();
+        assert_eq!(loaded, Some(puzzle));
+        assert_eq!(rest, b"rest");
+
+        assert_eq!(Puzzle::split_bytes(b"LGC1"), Ok((None, &b"LGC1"[..])));
+        assert_eq!(
+            Puzzle::split_bytes(&bytes[..10]),
+            Err(PuzzleError::Truncated)
+        );
+        assert_eq!(
+            Puzzle::split_bytes(b"LGP1\x07\x01"),
+            Err(PuzzleError::TooLarge {
+                inputs: 7,
+                outputs: 1
+            })
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+// What a frame drew, counted by each renderer as it issues its draws and
+// returned from `State::render`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub instances: u32,
+    pub triangles: u32,
+    // From the start of `render` until the frame was submitted, apart from
+    // waiting for the surface texture, which is mostly waiting on vsync
+    pub encode_time: Duration,
+}
+
+impl RenderStats {
+    // Every draw is a triangle list of `vertices`, or indices, per instance
+    pub fn record_draw(&mut self, vertices: u32, instances: u32) {
+        self.draw_calls += 1;
+        self.instances += instances;
+        self.triangles += vertices / 3 * instances;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_add_up() {
+        let mut stats = RenderStats::default();
+        stats.record_draw(6, 100);
+        stats.record_draw(3, 1);
+        stats.record_draw(6, 0);
+        assert_eq!(stats.draw_calls, 3);
+        assert_eq!(stats.instances, 101);
+        assert_eq!(stats.triangles, 201);
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt, fs,
+    io::{self, LineWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Game, GameInput};
+
+// One input as the game received it. Inputs are recorded after `State` has
+// translated them into world space, since window positions mean nothing
+// without the camera and window size they were made with. The hovered cell
+// is kept too, as pasting depends on it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RecordedInput {
+    // The game's fixed step count when the input was applied
+    pub tick: u64,
+    pub hovered_cell: Option<(usize, usize)>,
+    pub input: GameInput,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Parse {
+        line: usize,
+        error: serde_json::Error,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "An error occured while reading the replay: {}", e),
+            ReplayError::Parse { line, error } => {
+                write!(f, "Invalid input on line {} of the replay: {}", line, error)
+            }
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::Io(e) => Some(e),
+            ReplayError::Parse { error, .. } => Some(error),
+        }
+    }
+}
+
+// Writes every input the game receives as one JSON line, so a session that
+// crashes still leaves everything up to the crash behind
+pub struct Recorder {
+    writer: Box<dyn Write>,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = fs::File::create(path)?;
+        Ok(Self::new(LineWriter::new(file)))
+    }
+
+    pub fn new(writer: impl Write + 'static) -> Self {
+        Recorder {
+            writer: Box::new(writer),
+        }
+    }
+
+    pub fn record(&mut self, game: &Game, input: GameInput) -> io::Result<()> {
+        let recorded = RecordedInput {
+            tick: game.ticks,
+            hovered_cell: game.hovered_cell,
+            input,
+        };
+        serde_json::to_writer(&mut self.writer, &recorded)?;
+        self.writer.write_all(b"\n")
+    }
+}
+
+// Inputs read back from a recording, handed out once the game reaches the
+// tick they were recorded at. Replaying a session only gives the same result
+// from the same starting game, so it needs the same `--seed` as the recording.
+#[derive(Debug, Default)]
+pub struct Replay {
+    inputs: VecDeque<RecordedInput>,
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        Self::parse(&fs::read_to_string(path).map_err(ReplayError::Io)?)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, ReplayError> {
+        let inputs = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .map_err(|error| ReplayError::Parse { line: i + 1, error })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Replay { inputs })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    // Applies every input recorded at or before the game's current tick
+    pub fn apply_due(&mut self, game: &mut Game) {
+        while let Some(recorded) = self.inputs.front().filter(|input| input.tick <= game.ticks) {
+            let RecordedInput {
+                hovered_cell,
+                input,
+                ..
+            } = *recorded;
+            self.inputs.pop_front();
+
+            game.hovered_cell = hovered_cell;
+            game.handle_input(input);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use winit::event::MouseButton;
+
+    use super::*;
+    use crate::grid::{Cell, Grid};
+
+    // Shares what the recorder writes with the test
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn replays_apply_inputs_at_their_recorded_ticks() {
+        let buffer = SharedBuffer::default();
+        let mut recorder = Recorder::new(buffer.clone());
+        let mut game = Game::new(3, 1);
+        let mut record = |game: &mut Game, input| {
+            recorder.record(game, input).unwrap();
+            game.handle_input(input);
+        };
+
+        record(
+            &mut game,
+            GameInput::Click {
+                button: MouseButton::Left,
+                position: [0.5, 0.5],
+            },
+        );
+        record(
+            &mut game,
+            GameInput::Release {
+                button: MouseButton::Left,
+            },
+        );
+        for _ in 0..3 {
+            game.step();
+        }
+        game.hovered_cell = Some((2, 0));
+        record(&mut game, GameInput::Copy);
+        record(&mut game, GameInput::Undo);
+
+        let contents = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        let mut replay = Replay::parse(&contents).unwrap();
+        let mut replayed = Game::new(3, 1);
+        replay.apply_due(&mut replayed);
+        assert_eq!(replayed.grid.get(0, 0), Some(Cell::Wire));
+        for _ in 0..2 {
+            replayed.step();
+            replay.apply_due(&mut replayed);
+        }
+        assert_eq!(replayed.grid.get(0, 0), Some(Cell::Wire));
+        assert!(!replay.is_finished());
+
+        replayed.step();
+        replay.apply_due(&mut replayed);
+        assert!(replay.is_finished());
+        assert_eq!(replayed.grid, game.grid);
+        assert_eq!(replayed.hovered_cell, Some((2, 0)));
+    }
+
+    #[test]
+    fn replayed_grid_sizes_are_clamped() {
+        let contents = concat!(
+            r#"{"tick":0,"hovered_cell":null,"#,
+            r#""input":{"new_grid":{"width":100000,"height":0}}}"#
+        );
+        let mut replay = Replay::parse(contents).unwrap();
+        let mut game = Game::new(3, 1);
+        replay.apply_due(&mut game);
+        assert_eq!((game.grid.width(), game.grid.height()), (Grid::MAX_SIZE, 1));
+    }
+
+    #[test]
+    fn malformed_lines_are_reported() {
+        let contents = "\n{\"tick\":0,\"hovered_cell\":null,\"input\":\"undo\"}\nnope\n";
+        match Replay::parse(contents) {
+            Err(ReplayError::Parse { line, .. }) => assert_eq!(line, 3),
+            result => panic!("{:?}", result),
+        }
+    }
+}
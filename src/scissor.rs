@@ -0,0 +1,199 @@
+use std::ops::Range;
+
+// A clip rectangle in physical pixels, always within the surface it was
+// clamped to, so setting it never fails validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScissorRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ScissorRect {
+    pub fn full(surface_size: (u32, u32)) -> Self {
+        ScissorRect {
+            x: 0,
+            y: 0,
+            width: surface_size.0,
+            height: surface_size.1,
+        }
+    }
+
+    // Covers every pixel the rect touches, cut off at the surface's edges.
+    // Rects entirely off the surface end up empty.
+    pub fn clamped(position: [f32; 2], size: [f32; 2], surface_size: (u32, u32)) -> Self {
+        let clamp = |value: f32, max: u32| (value.max(0.0) as u32).min(max);
+        let left = clamp(position[0].floor(), surface_size.0);
+        let top = clamp(position[1].floor(), surface_size.1);
+        let right = clamp((position[0] + size[0]).ceil(), surface_size.0).max(left);
+        let bottom = clamp((position[1] + size[1]).ceil(), surface_size.1).max(top);
+        ScissorRect {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+
+    fn intersect(self, other: ScissorRect) -> Self {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width).max(left);
+        let bottom = (self.y + self.height).min(other.y + other.height).max(top);
+        ScissorRect {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+
+    pub fn apply(self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_scissor_rect(self.x, self.y, self.width, self.height);
+    }
+}
+
+// Nested clip regions for UI panels. Each region is clipped to the ones it
+// was pushed inside of, and to the surface.
+#[derive(Debug, Default)]
+pub struct ScissorStack {
+    stack: Vec<ScissorRect>,
+}
+
+impl ScissorStack {
+    // Returns the region to clip to from now on
+    pub fn push(
+        &mut self,
+        position: [f32; 2],
+        size: [f32; 2],
+        surface_size: (u32, u32),
+    ) -> ScissorRect {
+        let rect = ScissorRect::clamped(position, size, surface_size);
+        let rect = match self.stack.last() {
+            Some(&outer) => rect.intersect(outer),
+            None => rect,
+        };
+        self.stack.push(rect);
+        rect
+    }
+
+    // Returns the region to go back to, or `None` once nothing is clipped
+    pub fn pop(&mut self) -> Option<ScissorRect> {
+        self.stack.pop();
+        self.stack.last().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    // Drops regions that were never popped
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+}
+
+// Where a renderer's queue changes clip region, so it can keep uploading
+// everything in one buffer and only split the draw. `None` is unclipped.
+#[derive(Debug, Default)]
+pub struct ClipRuns {
+    queued: Vec<(u32, Option<ScissorRect>)>,
+    prepared: Vec<(Range<u32>, Option<ScissorRect>)>,
+}
+
+impl ClipRuns {
+    // Clips everything queued from `start` on to `rect`
+    pub fn set(&mut self, start: u32, rect: Option<ScissorRect>) {
+        self.queued.push((start, rect));
+    }
+
+    // Splits `0..len` into the runs set since the last call, dropping empty
+    // ones, and clears them for the next frame
+    pub fn prepare(&mut self, len: u32) {
+        self.prepared.clear();
+        let mut current = (0, None);
+        for (start, rect) in self.queued.drain(..).chain([(len, None)]) {
+            if start > current.0 {
+                self.prepared.push((current.0..start, current.1));
+            }
+            current = (start, rect);
+        }
+    }
+
+    // Calls `draw` once per run with its scissor rect set, then leaves the
+    // whole surface unclipped again for whatever's drawn next
+    pub fn render<'p>(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'p>,
+        surface_size: (u32, u32),
+        mut draw: impl FnMut(&mut wgpu::RenderPass<'p>, Range<u32>),
+    ) {
+        let mut clipped = false;
+        for (range, rect) in &self.prepared {
+            if rect.is_some() || clipped {
+                rect.unwrap_or(ScissorRect::full(surface_size))
+                    .apply(render_pass);
+            }
+            clipped = rect.is_some();
+            draw(render_pass, range.clone());
+        }
+        if clipped {
+            ScissorRect::full(surface_size).apply(render_pass);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_are_clamped_to_the_surface_and_their_parents() {
+        let rect = |x, y, width, height| ScissorRect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let surface_size = (100, 50);
+        let mut stack = ScissorStack::default();
+        assert_eq!(
+            stack.push([-10.0, 10.5], [50.0, 100.0], surface_size),
+            rect(0, 10, 40, 40)
+        );
+        assert_eq!(
+            stack.push([30.0, 0.0], [20.0, 20.0], surface_size),
+            rect(30, 10, 10, 10)
+        );
+        // Disjoint from its parent, so nothing is drawn
+        assert_eq!(stack.push([60.0, 0.0], [10.0, 10.0], surface_size).width, 0);
+        assert_eq!(stack.pop(), Some(rect(30, 10, 10, 10)));
+        assert_eq!(stack.pop(), Some(rect(0, 10, 40, 40)));
+        assert_eq!(stack.pop(), None);
+        assert!(stack.is_empty());
+
+        assert_eq!(
+            ScissorRect::clamped([200.0, 200.0], [10.0, 10.0], (100, 50)),
+            rect(100, 50, 0, 0)
+        );
+    }
+
+    #[test]
+    fn runs_split_the_queue_where_the_clip_changes() {
+        let clip = Some(ScissorRect::full((8, 8)));
+        let mut runs = ClipRuns::default();
+        runs.set(2, clip);
+        runs.set(2, None);
+        runs.set(3, clip);
+        runs.set(5, None);
+        runs.prepare(5);
+        assert_eq!(runs.prepared, [(0..2, None), (2..3, None), (3..5, clip)]);
+
+        // Nothing set is a single unclipped run, and nothing queued none
+        runs.prepare(4);
+        assert_eq!(runs.prepared, [(0..4, None)]);
+        runs.prepare(0);
+        assert!(runs.prepared.is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs, io};
+use std::{path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{bindings::KeyBindings, palette::Palette};
+
+pub const SETTINGS_PATH: &str = "settings.json";
+
+// Player-tweakable settings, loaded from `SETTINGS_PATH` at startup and written
+// back on exit. Missing fields fall back to their defaults, so older or
+// hand-written files keep working.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Logical window size
+    pub width: u32,
+    pub height: u32,
+    pub vsync: bool,
+    // How many frames the CPU may queue up ahead of the display, at least 1.
+    // 1 gives the least input lag, but the GPU can sit idle waiting for the
+    // next frame and miss vsync on an uneven frame. 3 rides out uneven frames
+    // more smoothly at the cost of another frame of lag on top of 2.
+    pub frame_latency: u32,
+    // MSAA samples per pixel, or 1 to turn it off. Clamped to the nearest
+    // count the GPU supports.
+    pub sample_count: u32,
+    // Caps the frame rate when set, mostly useful without vsync
+    pub max_fps: Option<u32>,
+    // Linear RGBA
+    pub clear_color: [f64; 4],
+    pub palette: Palette,
+    // Renders the world at this size and scales it up to the window, e.g.
+    // `[320, 180]` for a pixelated look
+    pub internal_resolution: Option<(u32, u32)>,
+    // Cells of space left around the grid when the camera fits it to the
+    // window
+    pub fit_margin: f32,
+    // Generates a puzzle from this seed instead of loading the demo circuit
+    pub seed: Option<u64>,
+    pub key_bindings: KeyBindings,
+    // Presses of a toggle key, or F11, less than this many milliseconds apart
+    // count as one. 0 takes every press.
+    pub toggle_debounce_ms: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            width: 1280,
+            height: 720,
+            vsync: true,
+            frame_latency: 2,
+            sample_count: 4,
+            max_fps: None,
+            clear_color: [0.2, 0.2, 0.2, 1.0],
+            palette: Palette::default(),
+            internal_resolution: None,
+            fit_margin: 0.5,
+            seed: None,
+            key_bindings: KeyBindings::default(),
+            toggle_debounce_ms: 150,
+        }
+    }
+}
+
+impl Settings {
+    // Never fails: a missing file silently gives the defaults, and an
+    // unreadable or malformed one is reported and then ignored
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Settings::default(),
+            Err(e) => {
+                log::warn!(
+                    "An error occured while reading {}, using the defaults: {}",
+                    path.display(),
+                    e
+                );
+                return Settings::default();
+            }
+        };
+
+        let mut settings: Settings = serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log::warn!(
+                "An error occured while parsing {}, using the defaults: {}",
+                path.display(),
+                e
+            );
+            Settings::default()
+        });
+
+        if settings.width == 0 || settings.height == 0 {
+            log::warn!("Ignoring the empty window size in {}", path.display());
+            let defaults = Settings::default();
+            settings.width = defaults.width;
+            settings.height = defaults.height;
+        }
+        if settings.frame_latency == 0 {
+            log::warn!(
+                "The frame latency in {} must be at least 1, using 1",
+                path.display()
+            );
+            settings.frame_latency = 1;
+        }
+        for (key, actions) in settings.key_bindings.clashes() {
+            log::warn!(
+                "{:?} is bound to {:?} in {}, so it only triggers {:?}",
+                key,
+                actions,
+                path.display(),
+                actions[0]
+            );
+        }
+
+        settings
+    }
+
+    // Browsers have no settings file, so they always start from the defaults
+    // and nothing is kept between visits
+    #[cfg(target_arch = "wasm32")]
+    pub fn load(_path: impl AsRef<Path>) -> Self {
+        Settings::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let result = serde_json::to_string_pretty(self)
+            .map_err(io::Error::from)
+            .and_then(|json| fs::write(path, json));
+
+        if let Err(e) = result {
+            log::error!("An error occured while saving {}: {}", path.display(), e);
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self, _path: impl AsRef<Path>) {}
+
+    pub fn toggle_debounce(&self) -> Duration {
+        Duration::from_millis(self.toggle_debounce_ms)
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b, a] = self.clear_color;
+        wgpu::Color { r, g, b, a }
+    }
+}
@@ -0,0 +1,99 @@
+@group(0) @binding(0)
+var<uniform> clear_color: vec4<f32>;
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
+
+@group(2) @binding(0)
+var<uniform> view_proj: mat4x4<f32>;
+
+struct CellShape {
+    corner_radius: f32,
+    edge_softness: f32,
+};
+
+@group(3) @binding(0)
+var<uniform> cell_shape: CellShape;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) tex_coords: vec2<f32>,
+};
+
+struct SpriteInput {
+    @location(3) position: vec2<f32>,
+    @location(4) size: vec2<f32>,
+    @location(5) tex_min: vec2<f32>,
+    @location(6) tex_max: vec2<f32>,
+    @location(7) color: vec4<f32>,
+    @location(8) glow: vec2<f32>,
+    // Radians clockwise
+    @location(9) rotation: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) world_position: vec2<f32>,
+    @location(3) glow: vec2<f32>,
+    // Relative to the sprite's center, in world units
+    @location(4) local_position: vec2<f32>,
+    @location(5) half_size: vec2<f32>,
+};
+
+// Pulses per world unit along a wire, and how much they brighten it
+const PULSE_DENSITY: f32 = 0.25;
+const PULSE_BRIGHTNESS: f32 = 0.5;
+
+@vertex
+fn vs_main(model: VertexInput, sprite: SpriteInput) -> VertexOutput {
+    // Turned around the sprite's center, while the rounded edges below are
+    // measured before turning so they follow the quad
+    let local = model.position.xy * sprite.size;
+    let c = cos(sprite.rotation);
+    let s = sin(sprite.rotation);
+    let turned = vec2<f32>(local.x * c + local.y * s, local.y * c - local.x * s);
+    let position = vec3<f32>(turned + sprite.position, model.position.z);
+
+    var out: VertexOutput;
+    out.clip_position = view_proj * vec4<f32>(position, 1.0);
+    out.color = vec4<f32>(model.color, 1.0) * sprite.color;
+    out.tex_coords = mix(sprite.tex_min, sprite.tex_max, model.tex_coords);
+    out.world_position = position.xy;
+    out.glow = sprite.glow;
+    out.local_position = local;
+    out.half_size = sprite.size * 0.5;
+    return out;
+}
+
+// Signed distance to the edge of a box with rounded corners, negative inside
+fn rounded_box_distance(position: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let r = clamp(radius, 0.0, min(half_size.x, half_size.y));
+    let q = abs(position) - half_size + r;
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - r;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+    // A narrow bright band that moves right and up across lit wires as the
+    // phase advances
+    let along = in.world_position.x + in.world_position.y;
+    let wave = 0.5 + 0.5 * cos(6.2831855 * (along * PULSE_DENSITY - in.glow.y));
+    let pulse = pow(wave, 8.0) * in.glow.x * PULSE_BRIGHTNESS;
+
+    // Fades out just inside the edge, over at least a pixel so it stays
+    // smooth at any zoom
+    let distance = rounded_box_distance(in.local_position, in.half_size, cell_shape.corner_radius);
+    let edge = fwidth(distance) + max(cell_shape.edge_softness, 0.0);
+    let coverage = 1.0 - smoothstep(-edge, 0.0, distance);
+
+    // Premultiplied like the texture, to match the pipeline's blending
+    let alpha = in.color.a * coverage;
+    return vec4<f32>((in.color.rgb + pulse) * alpha, alpha) * texel;
+}
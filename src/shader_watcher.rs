@@ -0,0 +1,79 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+};
+
+use notify::{RecursiveMode, Watcher};
+
+// Watches a WGSL file on disk so its pipelines can be rebuilt when it's
+// saved. The whole directory is watched since many editors save by replacing
+// the file rather than writing to it.
+pub struct ShaderWatcher {
+    path: PathBuf,
+    changes: Receiver<()>,
+    // Stops watching when dropped
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl ShaderWatcher {
+    // Logs and returns `None` if the file can't be watched
+    pub fn new(path: impl AsRef<Path>) -> Option<Self> {
+        let path = path.as_ref().to_path_buf();
+        let (sender, changes) = mpsc::channel();
+
+        let file_name = path.file_name()?.to_owned();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            let modified = event.kind.is_modify() || event.kind.is_create();
+            if modified
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == Some(&file_name))
+            {
+                let _ = sender.send(());
+            }
+        });
+        let watcher = watcher.and_then(|mut watcher| {
+            let directory = path.parent().unwrap_or(Path::new("."));
+            watcher.watch(directory, RecursiveMode::NonRecursive)?;
+            Ok(watcher)
+        });
+
+        match watcher {
+            Ok(watcher) => {
+                log::info!("Watching {} for changes", path.display());
+                Some(ShaderWatcher {
+                    path,
+                    changes,
+                    _watcher: watcher,
+                })
+            }
+            Err(e) => {
+                log::warn!("An error occured while watching {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    // The file's new source if it changed since the last call. A save often
+    // shows up as several events, which are read back as one change.
+    pub fn poll(&self) -> Option<String> {
+        if self.changes.try_iter().count() == 0 {
+            return None;
+        }
+
+        fs::read_to_string(&self.path)
+            .map_err(|e| {
+                log::error!(
+                    "An error occured while reading {}: {}",
+                    self.path.display(),
+                    e
+                )
+            })
+            .ok()
+    }
+}
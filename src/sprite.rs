@@ -0,0 +1,154 @@
+use crate::{dynamic_buffer::DynamicBuffer, render_stats::RenderStats};
+
+// A region of the sprite atlas in texture coordinates, with (0, 0) at the
+// top-left of the atlas
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl AtlasRect {
+    // The tile at `column`, `row` of an atlas made of equally sized tiles
+    pub const fn tile(column: u32, row: u32, columns: u32, rows: u32) -> Self {
+        let (width, height) = (1.0 / columns as f32, 1.0 / rows as f32);
+        AtlasRect {
+            min: [column as f32 * width, row as f32 * height],
+            max: [(column + 1) as f32 * width, (row + 1) as f32 * height],
+        }
+    }
+}
+
+// One quad of the world mesh, scaled to `size`, centered on `position` and
+// textured with the `tex_min`..`tex_max` region of the atlas
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Sprite {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub tex_min: [f32; 2],
+    pub tex_max: [f32; 2],
+    pub color: [f32; 4],
+    // How lit the sprite is and the phase of the pulse travelling across it,
+    // for animating wires. Zero for everything else.
+    pub glow: [f32; 2],
+    // Radians clockwise around the center, turning the texture with it
+    pub rotation: f32,
+}
+
+impl Sprite {
+    // Like `Instance`, these continue the shader locations after `Vertex`'s
+    const ATTRIBUTES: [wgpu::VertexAttribute; 7] = [
+        wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 3,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+            shader_location: 4,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            shader_location: 5,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+            shader_location: 6,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+            shader_location: 7,
+            format: wgpu::VertexFormat::Float32x4,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 12]>() as wgpu::BufferAddress,
+            shader_location: 8,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 14]>() as wgpu::BufferAddress,
+            shader_location: 9,
+            format: wgpu::VertexFormat::Float32,
+        },
+    ];
+
+    pub fn new(rect: AtlasRect, position: [f32; 2], size: [f32; 2], color: [f32; 4]) -> Self {
+        Sprite {
+            position,
+            size,
+            tex_min: rect.min,
+            tex_max: rect.max,
+            color,
+            glow: [0.0, 0.0],
+            rotation: 0.0,
+        }
+    }
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Sprite>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Collects every sprite drawn during a frame so they can all be drawn with
+// one instanced draw call. All sprites share the atlas bound by `State`.
+pub struct SpriteBatch {
+    sprites: Vec<Sprite>,
+    instance_buffer: DynamicBuffer<Sprite>,
+}
+
+impl SpriteBatch {
+    const INITIAL_INSTANCE_CAPACITY: usize = 1024;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            sprites: Vec::new(),
+            instance_buffer: DynamicBuffer::new(
+                device,
+                "WGPU Sprite Instance Buffer",
+                wgpu::BufferUsages::VERTEX,
+                Self::INITIAL_INSTANCE_CAPACITY,
+            ),
+        }
+    }
+
+    pub fn push(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    // Uploads the sprites drawn since the last flush, growing the instance
+    // buffer if needed, and starts an empty batch for the next frame
+    pub fn flush(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.instance_buffer.write(device, queue, &self.sprites);
+        self.sprites.clear();
+    }
+
+    pub fn allocations(&self) -> u64 {
+        self.instance_buffer.allocations()
+    }
+
+    // Draws every sprite in the last flush as an instance of the mesh already
+    // bound to vertex buffer 0 and the index buffer
+    pub fn render<'p>(
+        &'p self,
+        render_pass: &mut wgpu::RenderPass<'p>,
+        num_indices: u32,
+        stats: &mut RenderStats,
+    ) {
+        // An empty instance buffer can't be bound, so there's nothing to draw
+        if self.instance_buffer.is_empty() {
+            return;
+        }
+
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice());
+        render_pass.draw_indexed(0..num_indices, 0, 0..self.instance_buffer.len());
+        stats.record_draw(num_indices, self.instance_buffer.len());
+    }
+}
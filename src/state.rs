@@ -0,0 +1,3924 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    error::Error,
+    fmt,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+use pollster::FutureExt;
+use web_time::Instant;
+use wgpu::{util::DeviceExt, Features, RenderPassDescriptor};
+use winit::{
+    dpi::PhysicalSize,
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
+    window::{CursorIcon, Window},
+};
+
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+use crate::shader_watcher::ShaderWatcher;
+use crate::{
+    animation::WireAnimations,
+    assets::{AssetHandle, LoadState},
+    bind_group_cache::BindGroupCache,
+    bindings::{Action, ActionKind, Debounce, KeyBindings},
+    camera::Camera2D,
+    checkerboard::Checkerboard,
+    console::{Command, CommandError, Console},
+    dynamic_buffer::DynamicBuffer,
+    game::{Game, GameInput, CIRCUIT_PATH},
+    gpu_timer::GpuTimer,
+    grid::Cell,
+    grid_lines::{GridLines, GridStyle},
+    instance::Instance,
+    loading, math,
+    menu::{AppState, MenuEntry, MenuItem},
+    minimap::{Minimap, MinimapRect},
+    overlay::{OverlayRenderer, Rect},
+    palette::Palette,
+    render_stats::RenderStats,
+    replay::{Recorder, Replay},
+    scissor::ScissorStack,
+    settings::Settings,
+    sprite::{AtlasRect, Sprite, SpriteBatch},
+    text::TextRenderer,
+    texture,
+    texture::{Texture, TextureLoadOptions},
+    thumbnail,
+    uniform::{CameraUniform, CellShapeUniform, ClearColorUniform},
+    upscale::Upscaler,
+    vertex::{Vertex, INDICES, VERTICES},
+};
+
+#[derive(Debug)]
+pub enum StateError {
+    SurfaceCreation(wgpu::CreateSurfaceError),
+    AdapterRequest,
+    DeviceRequest(wgpu::RequestDeviceError),
+    NoCompatibleFormat,
+    FontLoad(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::SurfaceCreation(e) => {
+                write!(f, "An error occured while creating a surface: {}", e)
+            }
+            StateError::AdapterRequest => {
+                write!(f, "No adapter compatible with the surface was found")
+            }
+            StateError::DeviceRequest(e) => {
+                write!(f, "An error occured while requesting a device: {}", e)
+            }
+            StateError::NoCompatibleFormat => {
+                write!(f, "The surface is not compatible with the adapter")
+            }
+            StateError::FontLoad(e) => {
+                write!(f, "An error occured while loading the font: {}", e)
+            }
+        }
+    }
+}
+
+impl Error for StateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StateError::SurfaceCreation(e) => Some(e),
+            StateError::DeviceRequest(e) => Some(e),
+            StateError::AdapterRequest
+            | StateError::NoCompatibleFormat
+            | StateError::FontLoad(_) => None,
+        }
+    }
+}
+
+// Why a frame couldn't be rendered, or only partly
+#[derive(Debug)]
+pub enum RenderError {
+    // Acquiring the frame failed, so nothing was drawn
+    Surface(wgpu::SurfaceError),
+    // The frame was presented, but the screenshot taken of it was lost
+    ScreenshotReadback(wgpu::BufferAsyncError),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Surface(e) => {
+                write!(f, "An error occured while acquiring a frame: {}", e)
+            }
+            RenderError::ScreenshotReadback(e) => {
+                write!(f, "An error occured while reading the screenshot: {}", e)
+            }
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RenderError::Surface(e) => Some(e),
+            RenderError::ScreenshotReadback(e) => Some(e),
+        }
+    }
+}
+
+impl From<wgpu::SurfaceError> for RenderError {
+    fn from(e: wgpu::SurfaceError) -> Self {
+        RenderError::Surface(e)
+    }
+}
+
+// How the world is fit to the window. `Stretch` uses the whole surface and
+// widens/narrows the visible area with the window, while `Letterbox` keeps a
+// fixed aspect ratio and leaves bars on the sides or top and bottom.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetAspect {
+    Stretch,
+    Letterbox(f32),
+}
+
+// A window event waiting in `State::input_events` for the next update
+#[derive(Debug)]
+struct QueuedEvent {
+    received: Instant,
+    event: WindowEvent,
+}
+
+// What a window event asks for, translated and applied by the next update in
+// the order the events arrived. Positions are in window pixels, and only
+// become world positions once they're applied, with the camera the player was
+// looking at. The cursor, modifiers and held keys are tracked while the
+// events are translated, so each one sees them as they were when it arrived.
+#[derive(Debug, Clone, Copy)]
+pub enum InputAction {
+    Click {
+        button: MouseButton,
+        position: (f64, f64),
+        shift: bool,
+    },
+    // The cursor moved while the left button was held
+    Drag {
+        position: (f64, f64),
+    },
+    Release {
+        button: MouseButton,
+    },
+    // The cursor moved while the middle button was held
+    DragView {
+        from: (f64, f64),
+        to: (f64, f64),
+    },
+    // In lines, zooming in towards the position when positive
+    Scroll {
+        lines: f32,
+        position: (f64, f64),
+    },
+    // The left button was pressed over the minimap, or the cursor moved while
+    // it's held from there
+    Minimap {
+        position: (f64, f64),
+    },
+    KeyPress(Action),
+    Shortcut(GameInput),
+}
+
+// Where debug builds load the world shader from, to reload it on changes
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+const WORLD_SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl");
+
+// One 16x16 tile per cell type, laid out by `State::cell_atlas_rect`
+const CELL_ATLAS: &[u8] = include_bytes!("../assets/cells.png");
+const CELL_ATLAS_COLUMNS: u32 = 4;
+const CELL_ATLAS_ROWS: u32 = 2;
+
+// Where frames end up: the window's surface, or an offscreen texture for
+// headless rendering
+enum RenderTarget<'a> {
+    Surface(wgpu::Surface<'a>),
+    Offscreen(Texture),
+}
+
+pub struct State<'a> {
+    window: Option<Arc<Window>>,
+    target: RenderTarget<'a>,
+    device: wgpu::Device,
+    // Set from the device's lost callback, for `App` to recreate the state
+    device_lost: Arc<AtomicBool>,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    present_modes: Vec<wgpu::PresentMode>,
+    // Everything in `State` works in physical pixels: the surface, cursor
+    // positions and overlay/text coordinates. `App` only deals in logical sizes
+    // when asking for a window, and winit reports the physical result through
+    // `Resized`. `scale_factor` is the ratio between the two, used to keep
+    // overlays and text the same size on screen across displays.
+    pub size: PhysicalSize<u32>,
+    scale_factor: f64,
+    shader: wgpu::ShaderModule,
+    // Rebuilds the world pipelines whenever the shader is saved
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    shader_watcher: Option<ShaderWatcher>,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    render_pipeline: wgpu::RenderPipeline,
+    // Only available with `Features::POLYGON_MODE_LINE`
+    wireframe_pipeline: Option<wgpu::RenderPipeline>,
+    wireframe: bool,
+    // Translucent world-space highlights, drawn over the world in their own pass
+    ui_pipeline: wgpu::RenderPipeline,
+    ui_instance_buffer: DynamicBuffer<Instance>,
+    grid_lines: GridLines,
+    pub grid_style: GridStyle,
+    // Drawn behind the world instead of the flat clear color while shown, in
+    // which case empty cells aren't drawn so it shows through
+    checkerboard: Checkerboard,
+    show_checkerboard: bool,
+    // The world's fixed size in pixels, if it's scaled up to the viewport
+    // rather than drawn at the window's resolution
+    internal_resolution: Option<(u32, u32)>,
+    upscaler: Upscaler,
+    pub checkerboard_colors: [[f32; 4]; 2],
+    // Cells of space around the grid when fitting it to the view
+    pub fit_margin: f32,
+    // The fit key eases the view over to the grid on the next update, once
+    // the game is at hand
+    fit_requested: bool,
+    // See `CellShapeUniform`
+    pub cell_corner_radius: f32,
+    pub cell_edge_softness: f32,
+    cell_shape_buffer: wgpu::Buffer,
+    cell_shape_bind_group: Rc<wgpu::BindGroup>,
+    sample_count: u32,
+    // What the MSAA key switches to
+    msaa_sample_count: u32,
+    // Counts both the surface and depth formats support, in ascending order
+    supported_sample_counts: Vec<u32>,
+    msaa_texture: Option<Texture>,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+    sprites: SpriteBatch,
+    wire_animations: WireAnimations,
+    last_frame: Instant,
+    dt: Duration,
+    accumulator: Duration,
+    clear_color: wgpu::Color,
+    clear_color_buffer: wgpu::Buffer,
+    // Shared by the bind groups below, see `BindGroupCache` for when entries
+    // need invalidating
+    bind_groups: BindGroupCache,
+    clear_color_bind_group: Rc<wgpu::BindGroup>,
+    depth_texture: Texture,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    diffuse_texture: Texture,
+    diffuse_bind_group: Rc<wgpu::BindGroup>,
+    atlas_texture: Option<AssetHandle<Result<image::RgbaImage, image::ImageError>>>,
+    camera: Camera2D,
+    target_aspect: TargetAspect,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: Rc<wgpu::BindGroup>,
+    // Uploads the per-frame uniforms as part of the frame's encoder, see
+    // `write_staged`
+    staging_belt: wgpu::util::StagingBelt,
+    text: TextRenderer,
+    overlay: OverlayRenderer,
+    minimap: Minimap,
+    pub show_minimap: bool,
+    // The UI panels being queued are clipped to, see `push_clip`
+    scissors: ScissorStack,
+    frame_times: VecDeque<Duration>,
+    // The last frame's, shown under the frame graph
+    render_stats: RenderStats,
+    show_frame_graph: bool,
+    cursor: (f64, f64),
+    cursor_in_window: bool,
+    // The icon last asked for, so the window is only told when it changes
+    cursor_icon: CursorIcon,
+    pub hover_color: [f32; 4],
+    pub palette: Palette,
+    // Shown next to the cursor while it's over a gate
+    tooltip: Option<String>,
+    // Where the cursor was when the view was last dragged, while the middle
+    // button is held
+    drag: Option<(f64, f64)>,
+    left_held: bool,
+    // Set while a left click that landed on the minimap is held, which drags
+    // the camera around instead of drawing
+    minimap_held: bool,
+    // Raw events since the last update, translated at its start so a click
+    // lands where the cursor was rather than where it is by the frame
+    input_events: VecDeque<QueuedEvent>,
+    input_actions: Vec<InputAction>,
+    keys_down: HashSet<KeyCode>,
+    key_bindings: KeyBindings,
+    toggle_debounce: Debounce<Action>,
+    modifiers: ModifiersState,
+    game_inputs: Vec<GameInput>,
+    console: Console,
+    // Issued from the console, and unlike `game_inputs` still applied while
+    // paused
+    console_inputs: Vec<GameInput>,
+    // Set by the `truth` command, which needs the game to print the table
+    truth_table_requested: bool,
+    // Logs every input the game receives, for `--record`
+    recorder: Option<Recorder>,
+    // Feeds the game a recording instead of live input until it runs out
+    replay: Option<Replay>,
+    // The camera is refitted whenever the grid changes size
+    grid_size: Option<(usize, usize)>,
+    fps: f64,
+    fps_frames: u32,
+    fps_elapsed: Duration,
+    // The shortest time between frames, if the frame rate is capped
+    frame_period: Option<Duration>,
+    // Only present when the device supports timestamp queries
+    gpu_timer: Option<GpuTimer>,
+    gpu_time: Option<Duration>,
+    screenshot_requested: bool,
+    // Set while the window reports a 0x0 size, during which nothing is rendered
+    minimized: bool,
+    // Set whenever the surface is configured, until `take_reconfigured`
+    reconfigured: bool,
+    paused: bool,
+    app_state: AppState,
+    quit_requested: bool,
+    // A second view of a game that another state updates, see
+    // `make_inspector`
+    inspector: bool,
+    // Only draws the grid, see `make_thumbnail`
+    thumbnail: bool,
+    // Renders a thumbnail next to the circuit each time it's saved, on a
+    // thread that's waited for on exit so the last one isn't cut off
+    pub save_thumbnails: bool,
+    thumbnail_thread: Option<JoinHandle<()>>,
+    // Set by the inspector key, for `App` to open a window
+    inspector_requested: bool,
+}
+
+impl<'a> State<'a> {
+    // Upper bound on a single frame's delta time, so the first frame after startup
+    // or a long stall (e.g. dragging the window) doesn't produce a huge jump.
+    const MAX_DT: Duration = Duration::from_millis(100);
+    // Upper bound on simulation steps per frame. If a frame still has time
+    // left over after this many, the backlog is dropped instead of growing.
+    const MAX_STEPS_PER_FRAME: u32 = 5;
+    const INITIAL_UI_INSTANCE_CAPACITY: usize = 64;
+    // In bytes. Every per-frame uniform fits in one chunk, so the belt
+    // settles on a chunk or two in flight.
+    const STAGING_BELT_CHUNK_SIZE: wgpu::BufferAddress = 1024;
+    // Sleeping can overshoot by about a scheduler tick, so this much of the
+    // wait for the frame cap is spun instead
+    const FRAME_CAP_SPIN: Duration = Duration::from_millis(2);
+    // Features used when available, e.g. for debug views, but not required
+    const OPTIONAL_FEATURES: Features =
+        Features::TIMESTAMP_QUERY.union(Features::POLYGON_MODE_LINE);
+    // MSAA sample count until the settings are applied
+    const MSAA_SAMPLE_COUNT: u32 = 4;
+    const LETTERBOX_ASPECT: f32 = 16.0 / 9.0;
+    // World units per second at a zoom of 1.0
+    const CAMERA_PAN_SPEED: f32 = 1.0;
+    const HOVER_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.15];
+    const MENU_ITEM_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+    const MENU_HOVER_COLOR: [f32; 4] = [1.0, 0.85, 0.3, 1.0];
+    // Warm so the keyboard's selection stands out from the mouse's hover
+    const SELECTED_COLOR: [f32; 4] = [1.0, 0.8, 0.3, 0.4];
+    const CHANGED_COLOR: [f32; 4] = [0.3, 0.9, 1.0, 0.35];
+    const REGION_COLOR: [f32; 4] = [0.4, 0.6, 1.0, 0.25];
+    const CHECKERBOARD_COLORS: [[f32; 4]; 2] = [[0.2, 0.2, 0.2, 1.0], [0.16, 0.16, 0.16, 1.0]];
+    // The world pipeline blends premultiplied colors, so the cell textures
+    // have to match
+    const CELL_TEXTURE_OPTIONS: TextureLoadOptions = TextureLoadOptions { premultiply: true };
+    const CELL_CORNER_RADIUS: f32 = 0.15;
+    const CELL_EDGE_SOFTNESS: f32 = 0.0;
+    // Flashes per second of cells stuck in a feedback loop
+    const UNSTABLE_FLASH_RATE: f32 = 2.0;
+    // Each line of scrolling zooms by this factor
+    const ZOOM_STEP: f32 = 1.1;
+    // Touchpads scroll in pixels, treated as this many per line
+    const PIXELS_PER_SCROLL_LINE: f32 = 40.0;
+    // Cells across an inspector's view when it opens
+    const INSPECTOR_CELLS: f32 = 6.0;
+    // The frame latency key cycles from 1 up to this
+    const MAX_CYCLED_FRAME_LATENCY: u32 = 3;
+    // Multiplied into the clear color while paused
+    const PAUSED_TINT: f64 = 0.4;
+    // The frame graph shows this many of the most recent frames, one bar each,
+    // scaled so a frame of `FRAME_GRAPH_REFERENCE` reaches the reference line
+    const FRAME_GRAPH_SAMPLES: usize = 120;
+    const FRAME_GRAPH_REFERENCE: Duration = Duration::from_micros(16_667);
+    const FRAME_GRAPH_REFERENCE_HEIGHT: f32 = 40.0;
+    const FRAME_GRAPH_BAR_WIDTH: f32 = 2.0;
+    // In logical pixels along the grid's longer side
+    const MINIMAP_SIZE: f32 = 160.0;
+
+    // `gpu` picks one of the adapters listed at startup by index, falling back
+    // to the usual selection when it's out of range or can't present
+    pub async fn new(
+        window: Arc<Window>,
+        settings: &Settings,
+        gpu: Option<usize>,
+    ) -> Result<State<'a>, StateError> {
+        let size = window.inner_size();
+
+        let (surface, adapter) = match Self::choose_adapter(window.clone(), gpu)? {
+            Some(chosen) => chosen,
+            None => Self::request_adapter(window.clone()).await?,
+        };
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        let surface_capabilities = surface.get_capabilities(&adapter);
+        let surface_format = surface_capabilities
+            .formats
+            .iter()
+            .copied()
+            .find(|s| s.is_srgb())
+            .or_else(|| surface_capabilities.formats.first().copied())
+            .ok_or(StateError::NoCompatibleFormat)?;
+
+        let config = wgpu::SurfaceConfiguration {
+            width: size.width,
+            height: size.height,
+            present_mode: Self::choose_present_mode(
+                settings.vsync,
+                &surface_capabilities.present_modes,
+            ),
+            alpha_mode: surface_capabilities.alpha_modes[0],
+            format: surface_format,
+            view_formats: texture::srgb_view_format(
+                surface_format,
+                adapter.get_downlevel_capabilities().flags,
+            )
+            .into_iter()
+            .collect(),
+            desired_maximum_frame_latency: settings.frame_latency.max(1),
+            // COPY_SRC lets screenshots copy the surface texture, where supported
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_capabilities.usages & wgpu::TextureUsages::COPY_SRC),
+        };
+
+        // Only the text is built before showing the loading frame, and the
+        // event loop gets to show it before everything else is
+        let mut text = Self::create_text(&device, &queue, &config, window.scale_factor())?;
+        Self::present_loading_frame(&surface, &device, &queue, &config, &mut text, settings);
+        loading::YieldNow::default().await;
+
+        let mut state = Self::from_parts(
+            Some(window),
+            RenderTarget::Surface(surface),
+            &adapter,
+            device,
+            queue,
+            config,
+            surface_capabilities.present_modes,
+            text,
+        )?;
+        state.set_clear_color(settings.clear_color());
+        state.key_bindings = settings.key_bindings.clone();
+        state.toggle_debounce.interval = settings.toggle_debounce();
+        state.palette = settings.palette;
+        state.set_internal_resolution(settings.internal_resolution);
+        state.set_sample_count(settings.sample_count);
+        state.fit_margin = settings.fit_margin.max(0.0);
+
+        Ok(state)
+    }
+
+    // Renders into an offscreen texture instead of a window, e.g. for tests
+    // that check the rendered pixels with `read_pixels`
+    #[allow(dead_code)]
+    pub async fn new_headless(width: u32, height: u32) -> Result<State<'a>, StateError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptionsBase {
+                power_preference: wgpu::PowerPreference::default(),
+                force_fallback_adapter: false,
+                compatible_surface: None,
+            })
+            .await
+            .ok_or(StateError::AdapterRequest)?;
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        let config = wgpu::SurfaceConfiguration {
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        };
+        let target = RenderTarget::Offscreen(Texture::create_render_target(
+            &device,
+            &config,
+            "WGPU Offscreen Texture",
+        ));
+        let text = Self::create_text(&device, &queue, &config, 1.0)?;
+
+        Self::from_parts(
+            None,
+            target,
+            &adapter,
+            device,
+            queue,
+            config,
+            vec![wgpu::PresentMode::Fifo],
+            text,
+        )
+    }
+
+    fn create_text(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        scale_factor: f64,
+    ) -> Result<TextRenderer, StateError> {
+        TextRenderer::new(
+            device,
+            queue,
+            texture::render_format(config),
+            config.width,
+            config.height,
+            scale_factor as f32,
+        )
+        .map_err(StateError::FontLoad)
+    }
+
+    // Skipped while the window is minimized, since there's nothing to show
+    fn present_loading_frame(
+        surface: &wgpu::Surface,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        text: &mut TextRenderer,
+        settings: &Settings,
+    ) {
+        if config.width == 0 || config.height == 0 {
+            return;
+        }
+
+        surface.configure(device, config);
+        let output = match surface.get_current_texture() {
+            Ok(output) => output,
+            Err(e) => {
+                log::warn!("An error occured while showing the loading frame: {}", e);
+                return;
+            }
+        };
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(texture::render_format(config)),
+            ..Default::default()
+        });
+        loading::draw_frame(
+            device,
+            queue,
+            &view,
+            (config.width, config.height),
+            text,
+            settings.clear_color(),
+        );
+        output.present();
+    }
+
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+    ) -> Result<(wgpu::Device, wgpu::Queue), StateError> {
+        let adapter_info = adapter.get_info();
+        log::info!(
+            "Using {:?} adapter {} on the {:?} backend (driver: {} {})",
+            adapter_info.device_type,
+            adapter_info.name,
+            adapter_info.backend,
+            adapter_info.driver,
+            adapter_info.driver_info
+        );
+
+        // GL drivers often can't satisfy the full WebGPU default limits
+        let required_limits = match adapter_info.backend {
+            wgpu::Backend::Gl => wgpu::Limits::downlevel_webgl2_defaults(),
+            _ => wgpu::Limits::default(),
+        }
+        .using_resolution(adapter.limits());
+
+        // Optional features are only requested when the adapter has them, and
+        // whatever uses them checks `device.features()` before doing so
+        let required_features = Self::OPTIONAL_FEATURES & adapter.features();
+        let dropped_features = Self::OPTIONAL_FEATURES - required_features;
+        if !dropped_features.is_empty() {
+            log::warn!(
+                "The adapter doesn't support optional features {:?}",
+                dropped_features
+            );
+        }
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("WGPU Device"),
+                    required_features,
+                    required_limits,
+                },
+                None,
+            )
+            .await
+            .map_err(StateError::DeviceRequest)?;
+
+        log::info!(
+            "Granted optional features {:?}",
+            device.features() & Self::OPTIONAL_FEATURES
+        );
+        let limits = device.limits();
+        log::info!(
+            "Granted limits: {} max 2D texture size, {} bind groups, {} byte buffers",
+            limits.max_texture_dimension_2d,
+            limits.max_bind_groups,
+            limits.max_buffer_size
+        );
+        log::debug!("All granted limits: {:?}", limits);
+
+        Ok((device, queue))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn from_parts(
+        window: Option<Arc<Window>>,
+        target: RenderTarget<'a>,
+        adapter: &wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        present_modes: Vec<wgpu::PresentMode>,
+        text: TextRenderer,
+    ) -> Result<State<'a>, StateError> {
+        let size = PhysicalSize::new(config.width, config.height);
+        let scale_factor = window.as_ref().map_or(1.0, |window| window.scale_factor());
+        let format = texture::render_format(&config);
+        log::info!(
+            "Rendering to {:?} with the {:?} present mode",
+            format,
+            config.present_mode
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(Self::world_shader_source().into()),
+        });
+
+        let clear_color = wgpu::Color {
+            r: 0.2,
+            g: 0.2,
+            b: 0.2,
+            a: 1.0,
+        };
+
+        let clear_color_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("WGPU Clear Color Buffer"),
+            contents: bytemuck::cast_slice(&[ClearColorUniform::from(clear_color)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let clear_color_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("WGPU Clear Color Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let mut bind_groups = BindGroupCache::default();
+        let clear_color_bind_group = bind_groups.get_or_create(
+            &device,
+            &wgpu::BindGroupDescriptor {
+                label: Some("WGPU Clear Color Bind Group"),
+                layout: &clear_color_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: clear_color_buffer.as_entire_binding(),
+                }],
+            },
+        );
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("WGPU Texture Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        // Cells are drawn untextured with a plain white placeholder until the
+        // atlas has been decoded in the background
+        let placeholder_texture = Texture::from_image(
+            &device,
+            &queue,
+            &image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])),
+            "WGPU Placeholder Texture",
+            Self::CELL_TEXTURE_OPTIONS,
+        );
+        let diffuse_bind_group = Self::texture_bind_group(
+            &mut bind_groups,
+            &device,
+            &texture_bind_group_layout,
+            &placeholder_texture,
+        );
+        let atlas_texture = AssetHandle::spawn("cell atlas", || {
+            image::load_from_memory(CELL_ATLAS).map(|image| image.to_rgba8())
+        });
+
+        let camera = Camera2D::default();
+        let camera_uniform = CameraUniform {
+            view_proj: camera.build_view_projection_matrix(
+                config.width.max(1) as f32 / config.height.max(1) as f32,
+            ),
+        };
+
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("WGPU Camera Buffer"),
+            contents: bytemuck::cast_slice(&[camera_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let camera_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("WGPU Camera Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let camera_bind_group = bind_groups.get_or_create(
+            &device,
+            &wgpu::BindGroupDescriptor {
+                label: Some("WGPU Camera Bind Group"),
+                layout: &camera_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                }],
+            },
+        );
+
+        let cell_shape_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("WGPU Cell Shape Buffer"),
+            contents: bytemuck::cast_slice(&[CellShapeUniform::new(
+                Self::CELL_CORNER_RADIUS,
+                Self::CELL_EDGE_SOFTNESS,
+            )]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let cell_shape_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("WGPU Cell Shape Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let cell_shape_bind_group = bind_groups.get_or_create(
+            &device,
+            &wgpu::BindGroupDescriptor {
+                label: Some("WGPU Cell Shape Bind Group"),
+                layout: &cell_shape_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: cell_shape_buffer.as_entire_binding(),
+                }],
+            },
+        );
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("WGPU World Pipeline Layout"),
+                bind_group_layouts: &[
+                    &clear_color_bind_group_layout,
+                    &texture_bind_group_layout,
+                    &camera_bind_group_layout,
+                    &cell_shape_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        let supported_sample_counts = Self::supported_sample_counts(adapter, format);
+        let msaa_sample_count =
+            Self::nearest_sample_count(&supported_sample_counts, Self::MSAA_SAMPLE_COUNT);
+        let sample_count = msaa_sample_count;
+        let msaa_texture = Texture::create_msaa_texture(&device, &config, sample_count);
+
+        let depth_texture =
+            Texture::create_depth_texture(&device, &config, sample_count, "WGPU Depth Texture");
+
+        let (render_pipeline, wireframe_pipeline) = Self::create_world_pipelines(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            format,
+            sample_count,
+        );
+
+        let ui_pipeline = Self::create_ui_pipeline(&device, &camera_bind_group_layout, format);
+        let grid_lines = GridLines::new(&device, &camera_bind_group_layout, format);
+        let checkerboard =
+            Checkerboard::new(&device, &camera_bind_group_layout, format, sample_count);
+
+        let upscaler = Upscaler::new(&device, format);
+
+        let (vertex_buffer, index_buffer) = Self::create_mesh_buffers(&device, VERTICES, INDICES);
+        let sprites = SpriteBatch::new(&device);
+        let ui_instance_buffer = DynamicBuffer::new(
+            &device,
+            "WGPU UI Instance Buffer",
+            wgpu::BufferUsages::VERTEX,
+            Self::INITIAL_UI_INSTANCE_CAPACITY,
+        );
+
+        let overlay = OverlayRenderer::new(&device, format, config.width, config.height);
+        let minimap = Minimap::new(&device, format);
+        let gpu_timer = GpuTimer::new(&device, &queue);
+
+        // Dropping the device, or replacing the callback, also calls it, but
+        // neither is a loss
+        let device_lost = Arc::new(AtomicBool::new(false));
+        device.set_device_lost_callback({
+            let device_lost = device_lost.clone();
+            move |reason, message| {
+                if !matches!(
+                    reason,
+                    wgpu::DeviceLostReason::Dropped | wgpu::DeviceLostReason::ReplacedCallback
+                ) {
+                    log::error!("The GPU device was lost ({:?}): {}", reason, message);
+                    device_lost.store(true, Ordering::Relaxed);
+                }
+            }
+        });
+        // The default handler panics, but the errors left uncaptured are
+        // worth seeing rather than worth exiting over
+        device.on_uncaptured_error(Box::new(|error| {
+            log::error!("An uncaptured GPU error occured: {}", error);
+        }));
+
+        let mut state = Self {
+            window,
+            present_modes,
+            size,
+            scale_factor,
+            config,
+            device,
+            device_lost,
+            queue,
+            target,
+            shader,
+            #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+            shader_watcher: ShaderWatcher::new(WORLD_SHADER_PATH),
+            render_pipeline_layout,
+            render_pipeline,
+            wireframe_pipeline,
+            wireframe: false,
+            ui_pipeline,
+            ui_instance_buffer,
+            grid_lines,
+            checkerboard,
+            show_checkerboard: false,
+            internal_resolution: None,
+            upscaler,
+            checkerboard_colors: Self::CHECKERBOARD_COLORS,
+            fit_margin: Settings::default().fit_margin,
+            fit_requested: false,
+            cell_corner_radius: Self::CELL_CORNER_RADIUS,
+            cell_edge_softness: Self::CELL_EDGE_SOFTNESS,
+            cell_shape_buffer,
+            cell_shape_bind_group,
+            grid_style: GridStyle::default(),
+            sample_count,
+            msaa_sample_count,
+            supported_sample_counts,
+            msaa_texture,
+            vertex_buffer,
+            index_buffer,
+            num_indices: INDICES.len() as u32,
+            sprites,
+            wire_animations: WireAnimations::default(),
+            last_frame: Instant::now(),
+            dt: Duration::ZERO,
+            accumulator: Duration::ZERO,
+            clear_color,
+            clear_color_buffer,
+            bind_groups,
+            clear_color_bind_group,
+            depth_texture,
+            texture_bind_group_layout,
+            diffuse_texture: placeholder_texture,
+            diffuse_bind_group,
+            atlas_texture: Some(atlas_texture),
+            camera,
+            target_aspect: TargetAspect::Stretch,
+            camera_buffer,
+            staging_belt: wgpu::util::StagingBelt::new(Self::STAGING_BELT_CHUNK_SIZE),
+            camera_bind_group,
+            text,
+            overlay,
+            minimap,
+            show_minimap: false,
+            scissors: ScissorStack::default(),
+            frame_times: VecDeque::with_capacity(Self::FRAME_GRAPH_SAMPLES),
+            render_stats: RenderStats::default(),
+            show_frame_graph: false,
+            cursor: (0.0, 0.0),
+            cursor_in_window: false,
+            cursor_icon: CursorIcon::Default,
+            hover_color: Self::HOVER_COLOR,
+            palette: Palette::default(),
+            drag: None,
+            left_held: false,
+            minimap_held: false,
+            input_events: VecDeque::new(),
+            input_actions: Vec::new(),
+            tooltip: None,
+            keys_down: HashSet::new(),
+            key_bindings: KeyBindings::default(),
+            toggle_debounce: Debounce::new(Settings::default().toggle_debounce()),
+            modifiers: ModifiersState::empty(),
+            game_inputs: Vec::new(),
+            console: Console::default(),
+            console_inputs: Vec::new(),
+            truth_table_requested: false,
+            recorder: None,
+            replay: None,
+            grid_size: None,
+            fps: 0.0,
+            fps_frames: 0,
+            fps_elapsed: Duration::ZERO,
+            frame_period: None,
+            gpu_timer,
+            gpu_time: None,
+            screenshot_requested: false,
+            minimized: false,
+            reconfigured: false,
+            paused: false,
+            app_state: AppState::Playing,
+            quit_requested: false,
+            inspector: false,
+            thumbnail: false,
+            save_thumbnails: false,
+            thumbnail_thread: None,
+            inspector_requested: false,
+        };
+
+        // Configure the surface up front instead of relying on the platform to
+        // send an initial `Resized` event
+        state.resize(size);
+
+        Ok(state)
+    }
+
+    // Lists every adapter on every backend, and returns the one at `gpu` along
+    // with a surface for it if there is one that can present to the window
+    #[cfg(not(target_arch = "wasm32"))]
+    fn choose_adapter(
+        window: Arc<Window>,
+        gpu: Option<usize>,
+    ) -> Result<Option<(wgpu::Surface<'a>, wgpu::Adapter)>, StateError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let mut adapters = instance.enumerate_adapters(wgpu::Backends::all());
+        for (i, adapter) in adapters.iter().enumerate() {
+            let info = adapter.get_info();
+            log::info!(
+                "Adapter {}: {} ({:?}, {:?} backend)",
+                i,
+                info.name,
+                info.device_type,
+                info.backend
+            );
+        }
+
+        let Some(gpu) = gpu else {
+            return Ok(None);
+        };
+        if gpu >= adapters.len() {
+            log::warn!(
+                "There is no adapter {} (found {}), using the default selection",
+                gpu,
+                adapters.len()
+            );
+            return Ok(None);
+        }
+
+        let adapter = adapters.swap_remove(gpu);
+        let surface = instance
+            .create_surface(window)
+            .map_err(StateError::SurfaceCreation)?;
+        if !adapter.is_surface_supported(&surface) {
+            log::warn!(
+                "Adapter {} can't present to the window, using the default selection",
+                gpu
+            );
+            return Ok(None);
+        }
+        Ok(Some((surface, adapter)))
+    }
+
+    // Browsers don't list their adapters, so there's nothing to pick from
+    #[cfg(target_arch = "wasm32")]
+    fn choose_adapter(
+        _window: Arc<Window>,
+        _gpu: Option<usize>,
+    ) -> Result<Option<(wgpu::Surface<'a>, wgpu::Adapter)>, StateError> {
+        Ok(None)
+    }
+
+    // Tries the PRIMARY backends first, then every backend (including GL), and
+    // finally a software fallback adapter. A surface is bound to the instance
+    // that created it, so each attempt needs its own instance and surface.
+    // Browsers only have WebGPU, which either gives an adapter or doesn't.
+    async fn request_adapter(
+        window: Arc<Window>,
+    ) -> Result<(wgpu::Surface<'a>, wgpu::Adapter), StateError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let attempts = [
+            (wgpu::Backends::PRIMARY, false),
+            (wgpu::Backends::all(), false),
+            (wgpu::Backends::all(), true),
+        ];
+        #[cfg(target_arch = "wasm32")]
+        let attempts = [(wgpu::Backends::BROWSER_WEBGPU, false)];
+
+        for (backends, force_fallback_adapter) in attempts {
+            let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends,
+                ..Default::default()
+            });
+
+            let surface = instance
+                .create_surface(window.clone())
+                .map_err(StateError::SurfaceCreation)?;
+
+            let adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptionsBase {
+                    power_preference: wgpu::PowerPreference::default(),
+                    force_fallback_adapter,
+                    compatible_surface: Some(&surface),
+                })
+                .await;
+
+            match adapter {
+                Some(adapter) => return Ok((surface, adapter)),
+                None => log::warn!(
+                    "No adapter found for backends {:?} (fallback adapter: {})",
+                    backends,
+                    force_fallback_adapter
+                ),
+            }
+        }
+
+        Err(StateError::AdapterRequest)
+    }
+
+    // The filled world pipeline, plus a wireframe variant of it when the device
+    // supports line polygon mode
+    fn create_world_pipelines(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> (wgpu::RenderPipeline, Option<wgpu::RenderPipeline>) {
+        let render_pipeline = Self::create_render_pipeline(
+            device,
+            layout,
+            shader,
+            format,
+            sample_count,
+            wgpu::PolygonMode::Fill,
+        );
+        let wireframe_pipeline = device
+            .features()
+            .contains(Features::POLYGON_MODE_LINE)
+            .then(|| {
+                Self::create_render_pipeline(
+                    device,
+                    layout,
+                    shader,
+                    format,
+                    sample_count,
+                    wgpu::PolygonMode::Line,
+                )
+            });
+
+        (render_pipeline, wireframe_pipeline)
+    }
+
+    fn create_render_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        polygon_mode: wgpu::PolygonMode,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(match polygon_mode {
+                wgpu::PolygonMode::Fill => "WGPU World Pipeline",
+                _ => "WGPU World Wireframe Pipeline",
+            }),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[Vertex::desc(), Sprite::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    // Cells fade out at their rounded edges. Their textures
+                    // are premultiplied, see `CELL_TEXTURE_OPTIONS`.
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+
+    // Like the world pipeline, but alpha blended and without depth testing or
+    // multisampling, since it draws over the already resolved frame
+    fn create_ui_pipeline(
+        device: &wgpu::Device,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("ui.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WGPU UI Pipeline Layout"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("WGPU UI Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[Vertex::desc(), Instance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    // The sample counts that both the surface format and the depth format
+    // support, always including 1
+    fn supported_sample_counts(adapter: &wgpu::Adapter, format: wgpu::TextureFormat) -> Vec<u32> {
+        let color_flags = adapter.get_texture_format_features(format).flags;
+        let depth_flags = adapter
+            .get_texture_format_features(Texture::DEPTH_FORMAT)
+            .flags;
+
+        [1, 2, 4, 8, 16]
+            .into_iter()
+            .filter(|&count| {
+                count == 1
+                    || (color_flags.sample_count_supported(count)
+                        && depth_flags.sample_count_supported(count))
+            })
+            .collect()
+    }
+
+    // The supported count closest to `requested`, preferring the lower of two
+    // equally close ones
+    fn nearest_sample_count(supported: &[u32], requested: u32) -> u32 {
+        supported
+            .iter()
+            .copied()
+            .min_by_key(|&count| (count.abs_diff(requested), count))
+            .unwrap_or(1)
+    }
+
+    // Turns MSAA on at the supported count nearest to `requested`, or off for
+    // a count of 1
+    pub fn set_sample_count(&mut self, requested: u32) {
+        let count = Self::nearest_sample_count(&self.supported_sample_counts, requested);
+        if count != requested {
+            log::warn!(
+                "A sample count of {} is not supported, using {} instead. Supported counts are {:?}",
+                requested,
+                count,
+                self.supported_sample_counts
+            );
+        }
+
+        self.msaa_sample_count = count;
+        if count != self.sample_count {
+            self.sample_count = count;
+            log::info!("Switching MSAA sample count to {}", self.sample_count);
+            self.rebuild_multisampled();
+        }
+    }
+
+    fn toggle_wireframe(&mut self) {
+        if self.wireframe_pipeline.is_none() {
+            log::warn!("Wireframe rendering needs the POLYGON_MODE_LINE feature");
+            return;
+        }
+
+        self.wireframe = !self.wireframe;
+        log::info!(
+            "Switching to {} rendering",
+            if self.wireframe {
+                "wireframe"
+            } else {
+                "filled"
+            }
+        );
+    }
+
+    fn toggle_msaa(&mut self) {
+        if self.supported_sample_counts.len() == 1 {
+            log::warn!(
+                "MSAA is not supported for the surface format {:?}",
+                texture::render_format(&self.config)
+            );
+            return;
+        }
+        if self.msaa_sample_count == 1 {
+            log::warn!("MSAA is turned off by a sample count of 1 in the settings");
+            return;
+        }
+
+        self.sample_count = if self.sample_count == 1 {
+            self.msaa_sample_count
+        } else {
+            1
+        };
+        log::info!("Switching MSAA sample count to {}", self.sample_count);
+        self.rebuild_multisampled();
+    }
+
+    // Everything drawn into the multisampled targets has to match their
+    // sample count
+    fn rebuild_multisampled(&mut self) {
+        (self.render_pipeline, self.wireframe_pipeline) = Self::create_world_pipelines(
+            &self.device,
+            &self.render_pipeline_layout,
+            &self.shader,
+            texture::render_format(&self.config),
+            self.sample_count,
+        );
+        self.checkerboard
+            .set_sample_count(&self.device, self.sample_count);
+        self.recreate_render_targets();
+    }
+
+    // Native debug builds read the world shader from the source tree so it can
+    // be reloaded while running, but still start with the embedded copy if the
+    // file can't be read
+    fn world_shader_source() -> String {
+        #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+        match std::fs::read_to_string(WORLD_SHADER_PATH) {
+            Ok(source) => return source,
+            Err(e) => log::warn!(
+                "An error occured while reading {}, using the embedded shader: {}",
+                WORLD_SHADER_PATH,
+                e
+            ),
+        }
+        include_str!("shader.wgsl").to_string()
+    }
+
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    fn reload_shaders(&mut self) {
+        let Some(source) = self.shader_watcher.as_ref().and_then(ShaderWatcher::poll) else {
+            return;
+        };
+        if self.rebuild_world_pipelines(&source) {
+            log::info!("Reloaded {}", WORLD_SHADER_PATH);
+        }
+    }
+
+    // Swaps in pipelines built from `source`, or keeps the current ones and
+    // logs the error if it doesn't compile. Returns whether it compiled.
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    fn rebuild_world_pipelines(&mut self, source: &str) -> bool {
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("shader.wgsl"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let pipelines = Self::create_world_pipelines(
+            &self.device,
+            &self.render_pipeline_layout,
+            &shader,
+            texture::render_format(&self.config),
+            self.sample_count,
+        );
+        if let Some(e) = self.device.pop_error_scope().block_on() {
+            log::error!(
+                "An error occured while compiling the world shader, keeping the previous one: {}",
+                e
+            );
+            return false;
+        }
+
+        self.shader = shader;
+        (self.render_pipeline, self.wireframe_pipeline) = pipelines;
+        true
+    }
+
+    fn recreate_render_targets(&mut self) {
+        self.depth_texture = Texture::create_depth_texture(
+            &self.device,
+            &self.config,
+            self.sample_count,
+            "WGPU Depth Texture",
+        );
+        self.msaa_texture =
+            Texture::create_msaa_texture(&self.device, &self.config, self.sample_count);
+    }
+
+    fn texture_bind_group(
+        bind_groups: &mut BindGroupCache,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        texture: &Texture,
+    ) -> Rc<wgpu::BindGroup> {
+        bind_groups.get_or_create(
+            device,
+            &wgpu::BindGroupDescriptor {
+                label: Some("WGPU Texture Bind Group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    },
+                ],
+            },
+        )
+    }
+
+    // Loaded like the cell atlas, so it can be swapped in with `set_texture`
+    #[allow(dead_code)]
+    pub fn load_texture(&self, bytes: &[u8]) -> Result<Texture, image::ImageError> {
+        Texture::from_bytes(
+            &self.device,
+            &self.queue,
+            bytes,
+            "WGPU Loaded Texture",
+            Self::CELL_TEXTURE_OPTIONS,
+        )
+    }
+
+    // Swaps in the cell atlas once its background decode has finished
+    fn poll_assets(&mut self) {
+        let Some(handle) = self.atlas_texture.as_mut() else {
+            return;
+        };
+
+        match handle.state() {
+            LoadState::Loading => {}
+            LoadState::Ready => {
+                match handle.take() {
+                    Some(Ok(image)) => {
+                        let texture = Texture::from_image(
+                            &self.device,
+                            &self.queue,
+                            &image,
+                            "WGPU Cell Atlas Texture",
+                            Self::CELL_TEXTURE_OPTIONS,
+                        );
+                        self.set_texture(texture);
+                        log::debug!("Loaded the cell atlas");
+                    }
+                    Some(Err(e)) => {
+                        log::error!("An error occured while loading the cell atlas: {}", e)
+                    }
+                    None => {}
+                }
+                self.atlas_texture = None;
+            }
+            LoadState::Failed => {
+                log::error!("The cell atlas failed to load");
+                self.atlas_texture = None;
+            }
+        }
+    }
+
+    // The old texture's bind group is dropped from the cache so the texture
+    // can be freed
+    pub fn set_texture(&mut self, texture: Texture) {
+        let old = std::mem::replace(&mut self.diffuse_texture, texture);
+        self.bind_groups.invalidate(&old.view);
+        self.bind_groups.invalidate(&old.sampler);
+        self.diffuse_bind_group = Self::texture_bind_group(
+            &mut self.bind_groups,
+            &self.device,
+            &self.texture_bind_group_layout,
+            &self.diffuse_texture,
+        );
+    }
+
+    // Every cell is a sprite from the cell atlas, tinted by its type and signal
+    fn queue_cells(&mut self, game: &Game) {
+        let flash_phase = game.elapsed.as_secs_f32() * Self::UNSTABLE_FLASH_RATE;
+        for y in 0..game.grid.height() {
+            for x in 0..game.grid.width() {
+                let cell = game.grid.get(x, y).unwrap_or_default();
+                if cell == Cell::Empty && self.show_checkerboard {
+                    continue;
+                }
+                let mut sprite = Sprite::new(
+                    Self::cell_atlas_rect(cell),
+                    [x as f32 + 0.5, y as f32 + 0.5],
+                    [1.0, 1.0],
+                    self.palette.cell_color(cell, game.grid.signal(x, y)),
+                );
+                sprite.rotation = game.grid.rotation(x, y) as f32 * std::f32::consts::FRAC_PI_2;
+
+                // Wires fade between their off and on colors, and pulse while
+                // lit
+                if cell == Cell::Wire {
+                    let animation = self.wire_animations.get(x, y);
+                    let (off, on) = (
+                        self.palette.cell_color(cell, false),
+                        self.palette.cell_color(cell, true),
+                    );
+                    sprite.color =
+                        std::array::from_fn(|i| off[i] + (on[i] - off[i]) * animation.level);
+                    sprite.glow = [animation.level, animation.phase];
+                }
+                if game.grid.is_unstable(x, y) {
+                    let unstable = self.palette.unstable_color();
+                    let flash = 0.5 + 0.5 * (flash_phase * std::f32::consts::TAU).sin();
+                    sprite.color = std::array::from_fn(|i| {
+                        sprite.color[i] + (unstable[i] - sprite.color[i]) * flash
+                    });
+                }
+                self.sprites.push(sprite);
+            }
+        }
+    }
+
+    // Queues an untinted sprite for this frame, drawn in world space with the
+    // cells
+    #[allow(dead_code)]
+    pub fn draw_sprite(&mut self, rect: AtlasRect, pos: [f32; 2], size: [f32; 2]) {
+        self.sprites
+            .push(Sprite::new(rect, pos, size, [1.0, 1.0, 1.0, 1.0]));
+    }
+
+    fn cell_atlas_rect(cell: Cell) -> AtlasRect {
+        let index = match cell {
+            Cell::Empty => 0,
+            Cell::Wire => 1,
+            Cell::And => 2,
+            Cell::Or => 3,
+            Cell::Not => 4,
+            Cell::Source => 5,
+            Cell::Sink => 6,
+        };
+        AtlasRect::tile(
+            index % CELL_ATLAS_COLUMNS,
+            index / CELL_ATLAS_COLUMNS,
+            CELL_ATLAS_COLUMNS,
+            CELL_ATLAS_ROWS,
+        )
+    }
+
+    // Highlights the selected cell, and more faintly the one under the cursor,
+    // the selected region and any cells whose signal changed in the last step
+    fn ui_instances(&self, game: &Game) -> Vec<Instance> {
+        let highlight = |(x, y): (usize, usize), color| Instance {
+            offset: [x as f32 + 0.5, y as f32 + 0.5],
+            color,
+        };
+
+        let mut instances: Vec<_> = game
+            .changed_cells
+            .iter()
+            .map(|&cell| highlight(cell, Self::CHANGED_COLOR))
+            .collect();
+        if let Some(region) = game.region {
+            instances.extend(
+                region
+                    .cells()
+                    .map(|cell| highlight(cell, Self::REGION_COLOR)),
+            );
+        }
+        if let Some(cell) = game
+            .hovered_cell
+            .filter(|&cell| Some(cell) != game.selected_cell)
+        {
+            instances.push(highlight(cell, self.hover_color));
+        }
+        if let Some(cell) = game.selected_cell {
+            instances.push(highlight(cell, Self::SELECTED_COLOR));
+        }
+        instances
+    }
+
+    // The cell under the cursor, if the cursor is inside the window and over
+    // the part of the grid that's in the viewport
+    fn hovered_cell(&self, game: &Game) -> Option<(usize, usize)> {
+        let ndc = self.cursor_ndc();
+        if !self.cursor_in_window || ndc.0.abs() > 1.0 || ndc.1.abs() > 1.0 {
+            return None;
+        }
+
+        let [x, y] = self.camera.ndc_to_world(ndc, self.aspect());
+        game.grid.cell_at_world(x, y)
+    }
+
+    fn create_mesh_buffers(
+        device: &wgpu::Device,
+        vertices: &[Vertex],
+        indices: &[u16],
+    ) -> (wgpu::Buffer, wgpu::Buffer) {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("WGPU Vertex Buffer"),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("WGPU Index Buffer"),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        (vertex_buffer, index_buffer)
+    }
+
+    #[allow(dead_code)]
+    fn set_mesh(&mut self, vertices: &[Vertex], indices: &[u16]) {
+        let (vertex_buffer, index_buffer) =
+            Self::create_mesh_buffers(&self.device, vertices, indices);
+
+        self.vertex_buffer = vertex_buffer;
+        self.index_buffer = index_buffer;
+        self.num_indices = indices.len() as u32;
+    }
+
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        // A minimized window reports a 0x0 size, which is not a valid surface size
+        let minimized = size.width == 0 || size.height == 0;
+        if minimized != self.minimized {
+            log::debug!(
+                "{}",
+                if minimized {
+                    "Minimized, pausing rendering"
+                } else {
+                    "Restored, resuming rendering"
+                }
+            );
+            self.minimized = minimized;
+        }
+        if minimized {
+            return;
+        }
+
+        self.size = size;
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.reconfigure();
+        self.recreate_render_targets();
+        self.text
+            .resize(&self.queue, self.config.width, self.config.height);
+        self.overlay
+            .resize(&self.queue, self.config.width, self.config.height);
+    }
+
+    // Called when the window moves to a display with a different scale factor.
+    // The new physical size arrives separately through `resize`.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        log::info!("Scale factor changed to {}", scale_factor);
+        self.scale_factor = scale_factor;
+        if let Err(e) = self
+            .text
+            .set_scale_factor(&self.device, &self.queue, scale_factor as f32)
+        {
+            log::error!("An error occured while rebuilding the glyph atlas: {}", e);
+        }
+    }
+
+    // The hovered gate's inputs and output, e.g. "AND: 1,0 -> 0"
+    fn tooltip_text(game: &Game) -> Option<String> {
+        let (x, y) = game.hovered_cell?;
+        let inputs = game.grid.gate_inputs(x, y)?;
+        let bit = |on: bool| if on { "1" } else { "0" };
+
+        let inputs = if inputs.is_empty() {
+            "-".to_string()
+        } else {
+            inputs.into_iter().map(bit).collect::<Vec<_>>().join(",")
+        };
+        let name = game.grid.get(x, y)?.name();
+        Some(format!(
+            "{}: {} -> {}",
+            name,
+            inputs,
+            bit(game.grid.signal(x, y))
+        ))
+    }
+
+    // Queues the tooltip below and to the right of the cursor, pushed back
+    // inside the window when it would overflow an edge
+    fn queue_tooltip(&mut self) {
+        let Some(text) = self.tooltip.clone() else {
+            return;
+        };
+
+        let padding = self.ui_scale(4.0);
+        let offset = self.ui_scale(16.0);
+        let [width, height] = self.text.measure(&text);
+        let size = [width + 2.0 * padding, height + 2.0 * padding];
+        let window = [self.config.width as f32, self.config.height as f32];
+        let position = [
+            (self.cursor.0 as f32 + offset)
+                .min(window[0] - size[0])
+                .max(0.0),
+            (self.cursor.1 as f32 + offset)
+                .min(window[1] - size[1])
+                .max(0.0),
+        ];
+
+        self.overlay.queue(Rect {
+            position,
+            size,
+            color: [0.0, 0.0, 0.0, 0.75],
+        });
+        // Tooltips too large for the window would otherwise spill over its
+        // edges' padding
+        self.push_clip(position, size);
+        self.draw_text(&text, position[0] + padding, position[1] + padding);
+        self.pop_clip();
+    }
+
+    // Centered across the top of the window while the circuit solves its
+    // puzzle
+    fn queue_solved_banner(&mut self, margin: f32) {
+        const TEXT: &str = "Solved!";
+        let padding = self.ui_scale(8.0);
+        let [width, height] = self.text.measure(TEXT);
+        let size = [width + 2.0 * padding, height + 2.0 * padding];
+        let position = [(self.config.width as f32 - size[0]) / 2.0, margin];
+
+        self.overlay.queue(Rect {
+            position,
+            size,
+            color: [0.1, 0.5, 0.2, 0.85],
+        });
+        self.draw_text(TEXT, position[0] + padding, position[1] + padding);
+    }
+
+    // Converts a length in logical pixels to physical pixels
+    fn ui_scale(&self, length: f32) -> f32 {
+        length * self.scale_factor as f32
+    }
+
+    // Reapplies the current configuration, e.g. after the surface was lost or
+    // became outdated
+    pub fn reconfigure(&mut self) {
+        self.reconfigured = true;
+        match &mut self.target {
+            RenderTarget::Surface(surface) => surface.configure(&self.device, &self.config),
+            RenderTarget::Offscreen(texture) => {
+                *texture = Texture::create_render_target(
+                    &self.device,
+                    &self.config,
+                    "WGPU Offscreen Texture",
+                )
+            }
+        }
+    }
+
+    pub fn take_reconfigured(&mut self) -> bool {
+        std::mem::take(&mut self.reconfigured)
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    pub fn frame_latency(&self) -> u32 {
+        self.config.desired_maximum_frame_latency
+    }
+
+    // See `Settings::frame_latency` for the tradeoffs. Latencies below 1 are
+    // raised to it.
+    pub fn set_frame_latency(&mut self, latency: u32) {
+        if latency == 0 {
+            log::warn!("The frame latency must be at least 1, using 1");
+        }
+        let latency = latency.max(1);
+        if latency == self.frame_latency() {
+            return;
+        }
+
+        log::info!("Switching to a frame latency of {}", latency);
+        self.config.desired_maximum_frame_latency = latency;
+        self.reconfigure();
+    }
+
+    // The time between the last two updates, unclamped unlike `dt`
+    pub fn frame_time(&self) -> Duration {
+        self.frame_times.back().copied().unwrap_or_default()
+    }
+
+    // How many times the buffers refilled every frame have been allocated
+    pub fn buffer_allocations(&self) -> u64 {
+        self.sprites.allocations()
+            + self.ui_instance_buffer.allocations()
+            + self.grid_lines.allocations()
+            + self.text.allocations()
+            + self.overlay.allocations()
+    }
+
+    fn aspect(&self) -> f32 {
+        let (_, _, width, height) = self.viewport();
+        width / height
+    }
+
+    // The region of the surface the world is drawn into, as (x, y, width, height)
+    // in physical pixels
+    fn viewport(&self) -> (f32, f32, f32, f32) {
+        let width = self.config.width.max(1) as f32;
+        let height = self.config.height.max(1) as f32;
+
+        match self.target_aspect {
+            TargetAspect::Stretch => (0.0, 0.0, width, height),
+            TargetAspect::Letterbox(aspect) if width / height > aspect => {
+                let viewport_width = height * aspect;
+                ((width - viewport_width) / 2.0, 0.0, viewport_width, height)
+            }
+            TargetAspect::Letterbox(aspect) => {
+                let viewport_height = width / aspect;
+                (
+                    0.0,
+                    (height - viewport_height) / 2.0,
+                    width,
+                    viewport_height,
+                )
+            }
+        }
+    }
+
+    // Sizes are clamped to what the device can create a texture for
+    pub fn set_internal_resolution(&mut self, resolution: Option<(u32, u32)>) {
+        let max = self.device.limits().max_texture_dimension_2d;
+        self.internal_resolution =
+            resolution.map(|(width, height)| (width.clamp(1, max), height.clamp(1, max)));
+        match self.internal_resolution {
+            Some((width, height)) => {
+                log::info!("Rendering the world at {}x{}", width, height)
+            }
+            None => log::info!("Rendering the world at the window's resolution"),
+        }
+    }
+
+    pub fn set_target_aspect(&mut self, target_aspect: TargetAspect) {
+        self.target_aspect = target_aspect;
+    }
+
+    // Copies `data` to the start of `buffer` through a chunk of the staging
+    // belt, as a copy recorded into `encoder` rather than a separate queue
+    // write. Takes the belt and device apart from the state so the buffer
+    // can be one of its fields.
+    pub fn write_staged<T: bytemuck::Pod>(
+        staging_belt: &mut wgpu::util::StagingBelt,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        buffer: &wgpu::Buffer,
+        data: &[T],
+    ) {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        if let Some(size) = wgpu::BufferSize::new(bytes.len() as wgpu::BufferAddress) {
+            staging_belt
+                .write_buffer(encoder, buffer, 0, size, device)
+                .copy_from_slice(bytes);
+        }
+    }
+
+    // Returns whether the event was used, otherwise `App` gets to handle it
+    pub fn input(&mut self, event: WindowEvent) -> bool {
+        if self.app_state == AppState::Menu {
+            return self.menu_input(event);
+        }
+
+        if let WindowEvent::KeyboardInput { event, .. } = &event {
+            if self.console_input(event) {
+                return true;
+            }
+        }
+
+        let consumed = self.consumes(&event);
+        self.input_events.push_back(QueuedEvent {
+            received: Instant::now(),
+            event,
+        });
+        consumed
+    }
+
+    // Whether `translate_input` makes something of the event, decided as it
+    // arrives since `App` can't wait for the update. Cursor and mouse button
+    // events are always used, even if they only move the cursor or are
+    // swallowed by the console.
+    fn consumes(&self, event: &WindowEvent) -> bool {
+        match *event {
+            WindowEvent::CursorMoved { .. }
+            | WindowEvent::CursorLeft { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::MouseWheel { .. } => true,
+            // Repeats of a bound key are used even when they're ignored, so
+            // they don't reach `App` either
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.shortcut(key).is_some() || self.key_bindings.action(key).is_some(),
+            _ => false,
+        }
+    }
+
+    // Translates and applies the queued events in the order they arrived.
+    // The game input from each one is applied before the next is translated,
+    // with the cell that was hovered at the time, so a key that acts on the
+    // hovered cell isn't moved by the cursor leaving it later in the frame.
+    fn apply_input_events(&mut self, game: &mut Game) {
+        while let Some(QueuedEvent { received, event }) = self.input_events.pop_front() {
+            log::trace!(
+                "Handling {:?}, {:?} after it arrived",
+                event,
+                received.elapsed()
+            );
+            let actions = self.translate_input(&event, received);
+            self.input_actions.extend(actions);
+            self.apply_input_actions(game);
+        }
+        self.apply_input_actions(game);
+    }
+
+    // Input received while paused or in the menu is left for `update` to
+    // drop rather than replay on resume, and an inspector's is always dropped
+    fn apply_input_actions(&mut self, game: &mut Game) {
+        for action in std::mem::take(&mut self.input_actions) {
+            self.apply_input_action(action);
+        }
+        if self.inspector || self.paused || self.app_state == AppState::Menu {
+            return;
+        }
+
+        game.hovered_cell = self.hovered_cell(game);
+        for input in std::mem::take(&mut self.game_inputs) {
+            self.apply_game_input(game, input);
+        }
+    }
+
+    fn translate_input(&mut self, event: &WindowEvent, received: Instant) -> Vec<InputAction> {
+        match *event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = (position.x, position.y);
+                self.cursor_in_window = true;
+
+                let mut actions = Vec::new();
+                if self.minimap_held {
+                    actions.push(InputAction::Minimap {
+                        position: self.cursor,
+                    });
+                }
+                // Only while the middle button is held, which is when `drag`
+                // is set
+                if let Some(from) = self.drag {
+                    self.drag = Some(self.cursor);
+                    actions.push(InputAction::DragView {
+                        from,
+                        to: self.cursor,
+                    });
+                }
+                if self.left_held {
+                    actions.push(InputAction::Drag {
+                        position: self.cursor,
+                    });
+                }
+                actions
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_in_window = false;
+                Vec::new()
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Middle,
+                ..
+            } => {
+                self.drag = (state == ElementState::Pressed).then_some(self.cursor);
+                Vec::new()
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => {
+                        position.y as f32 / Self::PIXELS_PER_SCROLL_LINE
+                    }
+                };
+                vec![InputAction::Scroll {
+                    lines,
+                    position: self.cursor,
+                }]
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button,
+                ..
+            } => {
+                if self.console.is_open() {
+                    return Vec::new();
+                }
+                let on_minimap = self
+                    .minimap_rect()
+                    .is_some_and(|rect| Minimap::contains(rect, self.cursor));
+                if button == MouseButton::Left && on_minimap {
+                    self.minimap_held = true;
+                    return vec![InputAction::Minimap {
+                        position: self.cursor,
+                    }];
+                }
+                self.left_held |= button == MouseButton::Left;
+                vec![InputAction::Click {
+                    button,
+                    position: self.cursor,
+                    shift: self.modifiers.shift_key(),
+                }]
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button,
+                ..
+            } => {
+                if button == MouseButton::Left {
+                    self.left_held = false;
+                    if std::mem::take(&mut self.minimap_held) {
+                        return Vec::new();
+                    }
+                }
+                vec![InputAction::Release { button }]
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                Vec::new()
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state,
+                        repeat,
+                        ..
+                    },
+                ..
+            } => self
+                .key_input(key, state, repeat, received)
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Held keys are tracked whether or not the key does anything, so `App`
+    // still sees keys like Escape. Only actions that repeat take the key's
+    // repeats, and toggles are debounced by when the press arrived. Split out
+    // of `translate_input` since `KeyEvent`s can't be made up outside of
+    // winit.
+    fn key_input(
+        &mut self,
+        key: KeyCode,
+        state: ElementState,
+        repeat: bool,
+        received: Instant,
+    ) -> Option<InputAction> {
+        match state {
+            ElementState::Pressed => self.keys_down.insert(key),
+            ElementState::Released => self.keys_down.remove(&key),
+        };
+        if state == ElementState::Released {
+            return None;
+        }
+
+        if let Some(input) = self.shortcut(key) {
+            return (!repeat).then_some(InputAction::Shortcut(input));
+        }
+        let action = self.key_bindings.action(key)?;
+        let runs = match action.kind() {
+            ActionKind::Repeat => true,
+            ActionKind::Press | ActionKind::Held => !repeat,
+            ActionKind::Toggle => !repeat && self.toggle_debounce.fire(action, received),
+        };
+        runs.then_some(InputAction::KeyPress(action))
+    }
+
+    // The menu only reacts to the cursor, left clicks and Enter to play.
+    // Everything else is left for `App`, so Escape still quits.
+    fn menu_input(&mut self, event: WindowEvent) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = (position.x, position.y);
+                self.cursor_in_window = true;
+                true
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_in_window = false;
+                true
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.select_hovered_menu_item();
+                true
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(KeyCode::Enter),
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                self.select_menu_item(MenuItem::Play);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn select_hovered_menu_item(&mut self) {
+        if let Some(item) = self.hovered_menu_item() {
+            self.select_menu_item(item);
+        }
+    }
+
+    fn select_menu_item(&mut self, item: MenuItem) {
+        match item {
+            MenuItem::Play => self.set_app_state(AppState::Playing),
+            MenuItem::Quit => self.quit_requested = true,
+        }
+    }
+
+    pub fn set_app_state(&mut self, app_state: AppState) {
+        log::debug!("Switching to {:?}", app_state);
+        self.app_state = app_state;
+        // The frame time would otherwise include however long the menu was up
+        self.last_frame = Instant::now();
+    }
+
+    pub fn app_state(&self) -> AppState {
+        self.app_state
+    }
+
+    // Nothing made from a lost device works again, so `App` replaces the
+    // whole state
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    // Set once Quit has been picked in the menu, for `App` to exit
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+
+    pub fn take_inspector_request(&mut self) -> bool {
+        std::mem::take(&mut self.inspector_requested)
+    }
+
+    // Turns this into an inspector: a view of a game the main window's state
+    // updates, opened zoomed in on the selected or hovered cell. Its input
+    // only moves its own camera.
+    pub fn make_inspector(&mut self, game: &Game) {
+        self.inspector = true;
+        self.set_app_state(AppState::Playing);
+
+        let position = game
+            .selected_cell
+            .or(game.hovered_cell)
+            .map_or(game.center(), |(x, y)| [x as f32 + 0.5, y as f32 + 0.5]);
+        let size = [Self::INSPECTOR_CELLS; 2];
+        self.look_at(position, Camera2D::zoom_to_fit(size, self.aspect()));
+        // Already where it should be, rather than fitting the whole grid
+        self.grid_size = Some((game.grid.width(), game.grid.height()));
+    }
+
+    // Turns this into a still of `game` for a saved circuit's thumbnail: the
+    // whole grid in view, its wires as lit as they'll get, and none of the UI.
+    // The one frame it's rendered for has to have the cell atlas already.
+    pub fn make_thumbnail(&mut self, game: &Game) {
+        if let Some(handle) = self.atlas_texture.as_mut() {
+            handle.wait();
+        }
+        self.poll_assets();
+        self.thumbnail = true;
+        self.set_app_state(AppState::Playing);
+        self.fit_grid(game);
+        self.grid_size = Some((game.grid.width(), game.grid.height()));
+        self.wire_animations.settle(&game.grid);
+    }
+
+    // The menu items stacked in the middle of the window
+    fn menu_layout(&self) -> Vec<MenuEntry> {
+        let spacing = self.ui_scale(16.0);
+        let sizes = MenuItem::ALL.map(|item| self.text.measure(item.label()));
+        let total_height =
+            sizes.iter().map(|size| size[1]).sum::<f32>() + spacing * (sizes.len() - 1) as f32;
+
+        let mut y = (self.size.height as f32 - total_height) / 2.0;
+        MenuItem::ALL
+            .into_iter()
+            .zip(sizes)
+            .map(|(item, size)| {
+                let entry = MenuEntry {
+                    item,
+                    position: [(self.size.width as f32 - size[0]) / 2.0, y],
+                    size,
+                };
+                y += size[1] + spacing;
+                entry
+            })
+            .collect()
+    }
+
+    fn hovered_menu_item(&self) -> Option<MenuItem> {
+        if !self.cursor_in_window {
+            return None;
+        }
+
+        let padding = self.ui_scale(6.0);
+        self.menu_layout()
+            .into_iter()
+            .find(|entry| entry.contains(self.cursor, padding))
+            .map(|entry| entry.item)
+    }
+
+    // Dims the world behind the menu and draws its items, highlighting the
+    // one under the cursor
+    fn queue_menu(&mut self) {
+        self.overlay.queue(Rect {
+            position: [0.0, 0.0],
+            size: [self.size.width as f32, self.size.height as f32],
+            color: [0.0, 0.0, 0.0, 0.6],
+        });
+
+        let hovered = self.hovered_menu_item();
+        for entry in self.menu_layout() {
+            let color = if Some(entry.item) == hovered {
+                Self::MENU_HOVER_COLOR
+            } else {
+                Self::MENU_ITEM_COLOR
+            };
+            let [x, y] = entry.position;
+            self.text.queue(entry.item.label(), x, y, color);
+        }
+    }
+
+    // Panning isn't handled here since it lasts as long as its keys are held,
+    // and the console key is handled by `console_input`
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::MoveUp => self
+                .game_inputs
+                .push(GameInput::MoveSelection { dx: 0, dy: 1 }),
+            Action::MoveDown => self
+                .game_inputs
+                .push(GameInput::MoveSelection { dx: 0, dy: -1 }),
+            Action::MoveLeft => self
+                .game_inputs
+                .push(GameInput::MoveSelection { dx: -1, dy: 0 }),
+            Action::MoveRight => self
+                .game_inputs
+                .push(GameInput::MoveSelection { dx: 1, dy: 0 }),
+            Action::Place => self.game_inputs.push(GameInput::CycleSelected),
+            Action::Rotate => self.game_inputs.push(GameInput::Rotate),
+            Action::Clear => self.game_inputs.push(GameInput::ClearSelected),
+            Action::Deselect => self.game_inputs.push(GameInput::Deselect),
+            Action::StepMode => self.game_inputs.push(GameInput::ToggleStepMode),
+            Action::StepSignals => self.game_inputs.push(GameInput::StepSignals),
+            Action::SpeedUp => self.game_inputs.push(GameInput::SpeedUp),
+            Action::SlowDown => self.game_inputs.push(GameInput::SlowDown),
+            Action::Pause => self.toggle_pause(),
+            Action::ToggleVsync => self.set_vsync(!self.vsync()),
+            Action::FrameLatency => {
+                self.set_frame_latency(self.frame_latency() % Self::MAX_CYCLED_FRAME_LATENCY + 1)
+            }
+            // Reading the frame back blocks until the GPU is done with it,
+            // which a browser's main thread can't
+            Action::Screenshot if cfg!(target_arch = "wasm32") => {
+                log::warn!("Screenshots aren't supported in the browser")
+            }
+            Action::Screenshot => self.screenshot_requested = true,
+            Action::FrameGraph => self.show_frame_graph = !self.show_frame_graph,
+            Action::Wireframe => self.toggle_wireframe(),
+            Action::Msaa => self.toggle_msaa(),
+            Action::Checkerboard => self.show_checkerboard = !self.show_checkerboard,
+            Action::FitGrid => self.fit_requested = true,
+            Action::Inspector => self.inspector_requested = true,
+            Action::Minimap => self.show_minimap = !self.show_minimap,
+            Action::Palette => {
+                self.palette = self.palette.next();
+                log::info!("Switching to the {:?} palette", self.palette);
+            }
+            Action::Letterbox => self.set_target_aspect(match self.target_aspect {
+                TargetAspect::Stretch => TargetAspect::Letterbox(Self::LETTERBOX_ASPECT),
+                TargetAspect::Letterbox(_) => TargetAspect::Stretch,
+            }),
+            Action::PanUp
+            | Action::PanDown
+            | Action::PanLeft
+            | Action::PanRight
+            | Action::Console => {}
+        }
+    }
+
+    // The console key toggles the console. While it's open it takes every
+    // key, apart from releases still being tracked so held keys don't get
+    // stuck.
+    fn console_input(&mut self, event: &KeyEvent) -> bool {
+        let pressed = event.state == ElementState::Pressed;
+        let is_console_key = match event.physical_key {
+            PhysicalKey::Code(key) => self.key_bindings.keys(Action::Console).contains(&key),
+            PhysicalKey::Unidentified(_) => false,
+        };
+        if pressed && !event.repeat && is_console_key {
+            if self.toggle_debounce.fire(Action::Console, Instant::now()) {
+                self.console.toggle();
+                self.keys_down.clear();
+            }
+            return true;
+        }
+        if !self.console.is_open() {
+            return false;
+        }
+
+        match event.physical_key {
+            PhysicalKey::Code(key) if !pressed => {
+                self.keys_down.remove(&key);
+            }
+            PhysicalKey::Code(KeyCode::Escape) => self.console.toggle(),
+            PhysicalKey::Code(KeyCode::Enter | KeyCode::NumpadEnter) => {
+                if let Some(result) = self.console.submit() {
+                    self.run_command(result);
+                }
+            }
+            PhysicalKey::Code(KeyCode::Backspace) => self.console.backspace(),
+            _ if pressed => {
+                if let Some(text) = &event.text {
+                    self.console.push_text(text);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn run_command(&mut self, result: Result<Command, CommandError>) {
+        let command = match result {
+            Ok(command) => command,
+            Err(e) => {
+                self.console.print(e.to_string());
+                return;
+            }
+        };
+
+        match command {
+            Command::Clear => self.console.clear(),
+            Command::Fps => {
+                let mut line = format!("{:.0} FPS", self.fps);
+                if let Some(gpu_time) = self.gpu_time {
+                    line += &format!(", {:.2} ms GPU", gpu_time.as_secs_f64() * 1000.0);
+                }
+                self.console.print(line);
+            }
+            Command::Seed(seed) => {
+                self.console_inputs.push(GameInput::NewPuzzle(seed));
+                self.console
+                    .print(format!("Generated a puzzle from seed {}", seed));
+            }
+            Command::Grid { width, height } => {
+                self.console_inputs
+                    .push(GameInput::NewGrid { width, height });
+                self.console
+                    .print(format!("Created an empty {}x{} grid", width, height));
+            }
+            Command::Resize { width, height } => {
+                self.console_inputs
+                    .push(GameInput::ResizeGrid { width, height });
+                self.console
+                    .print(format!("Resized the grid to {}x{}", width, height));
+            }
+            Command::Challenge(challenge) => {
+                self.console_inputs
+                    .push(GameInput::StartChallenge(challenge));
+                self.console
+                    .print(format!("Started the {:?} challenge", challenge));
+            }
+            Command::Truth => self.truth_table_requested = true,
+        }
+    }
+
+    // Rows are the inputs then the outputs as 1s and 0s, a few to a line so
+    // small tables fit in the scrollback
+    fn print_truth_table(&mut self, game: &Game) {
+        const ROWS_PER_LINE: usize = 4;
+
+        let Some(region) = game.region else {
+            self.console.print("Select a region first");
+            return;
+        };
+        let table = match game.grid.truth_table(region.bounds()) {
+            Ok(table) => table,
+            Err(e) => {
+                self.console.print(e.to_string());
+                return;
+            }
+        };
+
+        let bits = |values: &[bool]| -> String {
+            values
+                .iter()
+                .map(|&on| if on { '1' } else { '0' })
+                .collect()
+        };
+        let (inputs, outputs) = (&table[0].0, &table[0].1);
+        self.console.print(format!(
+            "{} inputs top to bottom, {} outputs",
+            inputs.len(),
+            outputs.len()
+        ));
+        for rows in table.chunks(ROWS_PER_LINE) {
+            let line = rows
+                .iter()
+                .map(|(inputs, outputs)| format!("{}:{}", bits(inputs), bits(outputs)))
+                .collect::<Vec<_>>()
+                .join("  ");
+            self.console.print(line);
+        }
+    }
+
+    // Vsync uses Fifo, otherwise the lowest latency mode the surface supports.
+    // Fifo is guaranteed to be supported, so it's always the fallback.
+    fn choose_present_mode(vsync: bool, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        if vsync {
+            return wgpu::PresentMode::Fifo;
+        }
+
+        [wgpu::PresentMode::Mailbox, wgpu::PresentMode::Immediate]
+            .into_iter()
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(wgpu::PresentMode::Fifo)
+    }
+
+    pub fn vsync(&self) -> bool {
+        self.config.present_mode == wgpu::PresentMode::Fifo
+    }
+
+    pub fn set_vsync(&mut self, vsync: bool) {
+        let present_mode = Self::choose_present_mode(vsync, &self.present_modes);
+        if present_mode == self.config.present_mode {
+            if vsync != self.vsync() {
+                log::warn!("The surface only supports {:?}", present_mode);
+            }
+            return;
+        }
+
+        log::info!("Switching present mode to {:?}", present_mode);
+        self.config.present_mode = present_mode;
+        self.reconfigure();
+    }
+
+    // `None` renders as fast as the present mode allows
+    pub fn set_max_fps(&mut self, max_fps: Option<u32>) {
+        self.frame_period = max_fps.map(|fps| Duration::from_secs(1) / fps.max(1));
+    }
+
+    pub fn record_to(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    pub fn start_replay(&mut self, replay: Replay) {
+        log::info!("Replaying the recorded input, live input is ignored until it ends");
+        self.replay = Some(replay);
+    }
+
+    // For handing a recording or replay in progress on to a new state
+    pub fn take_recorder(&mut self) -> Option<Recorder> {
+        self.recorder.take()
+    }
+
+    pub fn take_replay(&mut self) -> Option<Replay> {
+        self.replay.take()
+    }
+
+    // Applies live input, recording it if asked to. It's ignored during a
+    // replay, which would go out of sync otherwise, and in an inspector.
+    pub fn apply_game_input(&mut self, game: &mut Game, input: GameInput) {
+        if self.replay.is_some() || self.inspector {
+            return;
+        }
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(e) = recorder.record(game, input) {
+                log::error!("An error occured while recording input, stopping: {}", e);
+                self.recorder = None;
+            }
+        }
+        game.handle_input(input);
+
+        if input == GameInput::Save && self.save_thumbnails {
+            // Both would write the same file, and the old handle would be lost
+            self.join_thumbnail_thread();
+            self.thumbnail_thread = Some(thumbnail::spawn(
+                game.grid.clone(),
+                self.palette,
+                self.clear_color,
+                thumbnail::thumbnail_path(CIRCUIT_PATH),
+            ));
+        }
+    }
+
+    fn join_thumbnail_thread(&mut self) {
+        if let Some(thread) = self.thumbnail_thread.take() {
+            if thread.join().is_err() {
+                log::error!("The thumbnail thread panicked");
+            }
+        }
+    }
+
+    // Applies the replayed input due by the game's current tick
+    fn apply_replay(&mut self, game: &mut Game) {
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+
+        replay.apply_due(game);
+        if replay.is_finished() {
+            log::info!("The replay has ended, switching back to live input");
+            self.replay = None;
+        }
+    }
+
+    // Blocks until a whole frame period has passed since the last update
+    // started, sleeping for most of it and spinning for the rest so the cap
+    // stays accurate
+    pub fn wait_for_frame_cap(&self) {
+        let Some(period) = self.frame_period else {
+            return;
+        };
+        // The page would freeze, and browsers pace frames to the display anyway
+        if cfg!(target_arch = "wasm32") {
+            return;
+        }
+
+        let deadline = self.last_frame + period;
+        if let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            if remaining > Self::FRAME_CAP_SPIN {
+                std::thread::sleep(remaining - Self::FRAME_CAP_SPIN);
+            }
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        log::info!("{}", if self.paused { "Paused" } else { "Resumed" });
+
+        // Time spent paused shouldn't count towards the first frame after it
+        if !self.paused {
+            self.last_frame = Instant::now();
+        }
+    }
+
+    // Ctrl combinations that map to a game action rather than a plain key press
+    fn shortcut(&self, key: KeyCode) -> Option<GameInput> {
+        if !self.modifiers.control_key() {
+            return None;
+        }
+
+        match key {
+            KeyCode::KeyZ if self.modifiers.shift_key() => Some(GameInput::Redo),
+            KeyCode::KeyZ => Some(GameInput::Undo),
+            KeyCode::KeyY => Some(GameInput::Redo),
+            KeyCode::KeyS => Some(GameInput::Save),
+            KeyCode::KeyO => Some(GameInput::Load),
+            KeyCode::KeyC => Some(GameInput::Copy),
+            KeyCode::KeyV => Some(GameInput::Paste),
+            _ => None,
+        }
+    }
+
+    pub fn is_key_down(&self, key: KeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    // Whether any of the keys bound to `action` are held
+    fn is_action_down(&self, action: Action) -> bool {
+        self.key_bindings
+            .keys(action)
+            .iter()
+            .any(|&key| self.is_key_down(key))
+    }
+
+    pub fn cursor_ndc(&self) -> (f32, f32) {
+        self.pixel_to_ndc(self.cursor)
+    }
+
+    // Like `math::pixel_to_ndc`, within the letterbox viewport
+    fn pixel_to_ndc(&self, position: (f64, f64)) -> (f32, f32) {
+        let (x, y, width, height) = self.viewport();
+        math::pixel_to_ndc(
+            position.0 - x as f64,
+            position.1 - y as f64,
+            width.round() as u32,
+            height.round() as u32,
+        )
+    }
+
+    pub fn look_at(&mut self, position: [f32; 2], zoom: f32) {
+        self.camera.snap_to(position, zoom);
+    }
+
+    // Pans so the world point that was under `last` follows the cursor
+    fn drag_camera(&mut self, last: (f64, f64), position: (f64, f64)) {
+        let (from, to) = (self.pixel_to_ndc(last), self.pixel_to_ndc(position));
+        let scale = self.camera.target_zoom;
+        self.camera.pan([
+            (from.0 - to.0) * self.aspect() / scale,
+            (from.1 - to.1) / scale,
+        ]);
+    }
+
+    // Centers the grid with all of it and `fit_margin` cells around it in
+    // view. The aspect ratio comes from the current configuration, so this is
+    // right straight after a resize.
+    pub fn fit_grid(&mut self, game: &Game) {
+        let (position, zoom) = self.grid_fit(game);
+        self.look_at(position, zoom);
+    }
+
+    fn grid_fit(&self, game: &Game) -> ([f32; 2], f32) {
+        let margin = 2.0 * self.fit_margin;
+        let size = [
+            game.grid.width() as f32 + margin,
+            game.grid.height() as f32 + margin,
+        ];
+        (game.center(), Camera2D::zoom_to_fit(size, self.aspect()))
+    }
+
+    // Snaps to a grid that changed size, and eases over to it when the fit
+    // key was pressed
+    fn fit_grid_if_needed(&mut self, game: &Game) {
+        let grid_size = Some((game.grid.width(), game.grid.height()));
+        if grid_size != self.grid_size {
+            self.grid_size = grid_size;
+            self.fit_requested = false;
+            self.fit_grid(game);
+        } else if std::mem::take(&mut self.fit_requested) {
+            let (position, zoom) = self.grid_fit(game);
+            self.camera.move_to(position, zoom);
+        }
+    }
+
+    // A hand over cells that a click would edit. Platforms that can't change
+    // the cursor ignore the request, which winit doesn't report, so there's
+    // nothing to handle.
+    fn update_cursor_icon(&mut self, game: &Game) {
+        let clickable = game.hovered_cell.is_some()
+            && self.app_state == AppState::Playing
+            && !self.paused
+            && !self.console.is_open();
+        let icon = if clickable {
+            CursorIcon::Pointer
+        } else {
+            CursorIcon::Default
+        };
+        if icon == self.cursor_icon {
+            return;
+        }
+
+        self.cursor_icon = icon;
+        if let Some(window) = &self.window {
+            window.set_cursor(icon);
+        }
+    }
+
+    // Game inputs are only queued here, so that they're dropped while paused
+    fn apply_input_action(&mut self, action: InputAction) {
+        let to_world = |state: &Self, position| {
+            state
+                .camera
+                .ndc_to_world(state.pixel_to_ndc(position), state.aspect())
+        };
+
+        match action {
+            InputAction::Click {
+                button,
+                position,
+                shift,
+            } => {
+                let position = to_world(self, position);
+                log::debug!("{:?} click at {:?}", button, position);
+                self.game_inputs
+                    .push(if shift && button == MouseButton::Left {
+                        GameInput::SelectRegion { position }
+                    } else {
+                        GameInput::Click { button, position }
+                    });
+            }
+            InputAction::Drag { position } => self.game_inputs.push(GameInput::Drag {
+                position: to_world(self, position),
+            }),
+            InputAction::Release { button } => self.game_inputs.push(GameInput::Release { button }),
+            InputAction::DragView { from, to } => self.drag_camera(from, to),
+            InputAction::Scroll { lines, position } => self.camera.zoom_towards(
+                self.pixel_to_ndc(position),
+                self.aspect(),
+                Self::ZOOM_STEP.powf(lines),
+            ),
+            InputAction::Minimap { position } => {
+                if let (Some(rect), Some(grid_size)) = (self.minimap_rect(), self.grid_size) {
+                    let target = Minimap::to_world(rect, grid_size, position);
+                    self.camera.move_to(target, self.camera.target_zoom);
+                }
+            }
+            InputAction::KeyPress(action) => self.run_action(action),
+            InputAction::Shortcut(input) => self.game_inputs.push(input),
+        }
+    }
+
+    pub fn update(&mut self, game: &mut Game) {
+        // Any GPU work would fail until `App` replaces the state
+        if self.is_device_lost() {
+            return;
+        }
+
+        self.poll_assets();
+        #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+        self.reload_shaders();
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_frame);
+        self.dt = elapsed.min(Self::MAX_DT);
+        self.last_frame = now;
+
+        // The FPS counter uses the unclamped frame time so it reflects stalls
+        self.fps_frames += 1;
+        self.fps_elapsed += elapsed;
+        if let Some(gpu_time) = self
+            .gpu_timer
+            .as_mut()
+            .and_then(|gpu_timer| gpu_timer.read(&self.device))
+        {
+            self.gpu_time = Some(gpu_time);
+        }
+        if self.fps_elapsed >= Duration::from_secs(1) {
+            let fps = self.fps_frames as f64 / self.fps_elapsed.as_secs_f64();
+            self.fps = fps;
+            if let Some(window) = &self.window {
+                let mut title = format!(
+                    "{} - Score {} - {:.0} FPS",
+                    if self.inspector {
+                        crate::INSPECTOR_TITLE
+                    } else {
+                        crate::WINDOW_TITLE
+                    },
+                    game.score,
+                    fps
+                );
+                if let Some(gpu_time) = self.gpu_time {
+                    title += &format!(" - {:.2} ms GPU", gpu_time.as_secs_f64() * 1000.0);
+                }
+                window.set_title(&title);
+            }
+            self.fps_frames = 0;
+            self.fps_elapsed = Duration::ZERO;
+        }
+
+        if self.frame_times.len() == Self::FRAME_GRAPH_SAMPLES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(elapsed);
+
+        for input in std::mem::take(&mut self.console_inputs) {
+            self.apply_game_input(game, input);
+        }
+        if std::mem::take(&mut self.truth_table_requested) {
+            self.print_truth_table(game);
+        }
+
+        // Applied before checking for the pause so it can still be toggled,
+        // and the view still moves while paused
+        self.apply_input_events(game);
+
+        // The camera still moves while paused
+        let pan = Self::CAMERA_PAN_SPEED * self.dt.as_secs_f32() / self.camera.zoom;
+        if self.is_action_down(Action::PanLeft) {
+            self.camera.pan([-pan, 0.0]);
+        }
+        if self.is_action_down(Action::PanRight) {
+            self.camera.pan([pan, 0.0]);
+        }
+        if self.is_action_down(Action::PanDown) {
+            self.camera.pan([0.0, -pan]);
+        }
+        if self.is_action_down(Action::PanUp) {
+            self.camera.pan([0.0, pan]);
+        }
+        self.camera.update(self.dt.as_secs_f32());
+
+        // The main window's state steps the game and owns the hovered cell,
+        // while an inspector still animates its own copy of the wires
+        if self.inspector {
+            self.game_inputs.clear();
+            self.fit_grid_if_needed(game);
+            self.wire_animations.update(&game.grid, self.dt);
+            self.tooltip = Self::tooltip_text(game);
+            return;
+        }
+
+        game.hovered_cell = self.hovered_cell(game);
+        self.update_cursor_icon(game);
+
+        // Input received while paused or in the menu is dropped rather than
+        // replayed on resume
+        if self.paused || self.app_state == AppState::Menu {
+            self.game_inputs.clear();
+            self.fit_grid_if_needed(game);
+            self.tooltip = Self::tooltip_text(game);
+            return;
+        }
+
+        // Replayed input is tied to the fixed step it was recorded at, so
+        // that it lands on the same signals whatever the frame rate
+        self.apply_replay(game);
+        // Loading a circuit or a console command can change the grid size
+        self.fit_grid_if_needed(game);
+
+        // Faster speeds catch up on proportionally more steps before giving up
+        // on the backlog, which still keeps a slow frame from snowballing
+        let sim_speed = game.sim_speed.clamp(0.0, Game::MAX_SIM_SPEED);
+        let sim_dt = self.dt.mul_f32(sim_speed);
+        let max_steps = Self::MAX_STEPS_PER_FRAME * sim_speed.max(1.0).ceil() as u32;
+        self.accumulator += sim_dt;
+        let mut steps = 0;
+        while self.accumulator >= Game::FIXED_STEP {
+            if steps == max_steps {
+                log::debug!("Dropping {:?} of simulation backlog", self.accumulator);
+                self.accumulator = Duration::ZERO;
+                break;
+            }
+
+            game.step();
+            self.apply_replay(game);
+            self.accumulator -= Game::FIXED_STEP;
+            steps += 1;
+        }
+
+        self.wire_animations.update(&game.grid, sim_dt);
+        self.tooltip = Self::tooltip_text(game);
+    }
+
+    #[allow(dead_code)]
+    pub fn dt(&self) -> Duration {
+        self.dt
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        self.clear_color
+    }
+
+    pub fn set_clear_color(&mut self, color: wgpu::Color) {
+        self.clear_color = color;
+    }
+
+    // The clear color dims while paused as an indicator
+    fn frame_clear_color(&self) -> wgpu::Color {
+        if !self.paused {
+            return self.clear_color;
+        }
+
+        wgpu::Color {
+            r: self.clear_color.r * Self::PAUSED_TINT,
+            g: self.clear_color.g * Self::PAUSED_TINT,
+            b: self.clear_color.b * Self::PAUSED_TINT,
+            a: self.clear_color.a,
+        }
+    }
+
+    // Queues white text with its top-left corner at (x, y) in physical pixels
+    // from the top-left of the window. Queued text is drawn by the next
+    // `render` and then cleared.
+    pub fn draw_text(&mut self, text: &str, x: f32, y: f32) {
+        self.text.queue(text, x, y, [1.0, 1.0, 1.0, 1.0]);
+    }
+
+    // Queues the console across the top of the screen, output first and the
+    // input line at the bottom, replacing the HUD while it's open
+    fn queue_console(&mut self, margin: f32, line_height: f32) {
+        let num_lines = self.console.num_output_lines() + 1;
+        let size = [
+            self.size.width as f32,
+            num_lines as f32 * line_height + 2.0 * margin,
+        ];
+        self.overlay.queue(Rect {
+            position: [0.0, 0.0],
+            size,
+            color: [0.0, 0.0, 0.0, 0.75],
+        });
+
+        // Long lines are cut off at the panel's margin
+        self.push_clip([margin, 0.0], [size[0] - 2.0 * margin, size[1]]);
+        let mut y = margin;
+        for line in self.console.output() {
+            self.text.queue(line, margin, y, [0.8, 0.8, 0.8, 1.0]);
+            y += line_height;
+        }
+        let input = format!("{}{}_", Console::PROMPT, self.console.input());
+        self.draw_text(&input, margin, y);
+        self.pop_clip();
+    }
+
+    // Clips the overlay rects and text queued until the matching `pop_clip`
+    // to a panel, within any panel it's pushed inside of
+    fn push_clip(&mut self, position: [f32; 2], size: [f32; 2]) {
+        let rect = self
+            .scissors
+            .push(position, size, (self.config.width, self.config.height));
+        self.overlay.set_clip(Some(rect));
+        self.text.set_clip(Some(rect));
+    }
+
+    fn pop_clip(&mut self) {
+        let rect = self.scissors.pop();
+        self.overlay.set_clip(rect);
+        self.text.set_clip(rect);
+    }
+
+    // Queues one bar per recent frame in the bottom-left corner, green when on
+    // time, yellow when up to twice the reference and red beyond that
+    fn queue_frame_graph(&mut self) {
+        let margin = self.ui_scale(8.0);
+        let bar_width = self.ui_scale(Self::FRAME_GRAPH_BAR_WIDTH);
+        let reference_height = self.ui_scale(Self::FRAME_GRAPH_REFERENCE_HEIGHT);
+        let max_height = 3.0 * reference_height;
+        let width = Self::FRAME_GRAPH_SAMPLES as f32 * bar_width;
+        let bottom = self.config.height as f32 - margin;
+
+        self.overlay.queue(Rect {
+            position: [margin, bottom - max_height],
+            size: [width, max_height],
+            color: [0.0, 0.0, 0.0, 0.5],
+        });
+
+        for (i, frame_time) in self.frame_times.iter().enumerate() {
+            let ratio = frame_time.as_secs_f32() / Self::FRAME_GRAPH_REFERENCE.as_secs_f32();
+            let height = (ratio * reference_height).min(max_height);
+            let color = match ratio {
+                r if r <= 1.0 => [0.2, 0.8, 0.2, 0.9],
+                r if r <= 2.0 => [0.9, 0.8, 0.2, 0.9],
+                _ => [0.9, 0.2, 0.2, 0.9],
+            };
+
+            self.overlay.queue(Rect {
+                position: [margin + i as f32 * bar_width, bottom - height],
+                size: [bar_width, height],
+                color,
+            });
+        }
+
+        self.overlay.queue(Rect {
+            position: [margin, bottom - reference_height],
+            size: [width, self.ui_scale(1.0)],
+            color: [1.0, 1.0, 1.0, 0.8],
+        });
+
+        let stats = self.render_stats;
+        let line_height = self.ui_scale(24.0);
+        self.draw_text(
+            &format!(
+                "{} draws, {} instances, {} triangles",
+                stats.draw_calls, stats.instances, stats.triangles
+            ),
+            margin,
+            bottom - max_height - 2.0 * line_height,
+        );
+        self.draw_text(
+            &format!(
+                "{:.2} ms encoding",
+                stats.encode_time.as_secs_f64() * 1000.0
+            ),
+            margin,
+            bottom - max_height - line_height,
+        );
+    }
+
+    // Where the minimap is shown, if it is. Inspectors and the menu go
+    // without, and so does a window too small to fit it.
+    fn minimap_rect(&self) -> Option<MinimapRect> {
+        if !self.show_minimap || self.inspector || self.app_state == AppState::Menu {
+            return None;
+        }
+
+        Minimap::layout(
+            self.grid_size?,
+            (self.config.width, self.config.height),
+            self.ui_scale(Self::MINIMAP_SIZE),
+            self.ui_scale(8.0),
+        )
+    }
+
+    // Samples the grid into the minimap and queues a frame around it
+    fn prepare_minimap(&mut self, game: &Game, rect: MinimapRect) {
+        let (x, y, width, height) = rect;
+        let border = self.ui_scale(1.0);
+        self.overlay.queue(Rect {
+            position: [x - border, y - border],
+            size: [width + 2.0 * border, height + 2.0 * border],
+            color: [0.0, 0.0, 0.0, 0.75],
+        });
+
+        let aspect = self.aspect();
+        let [x, y] = self.camera.position;
+        let half_size = [aspect / self.camera.zoom, 1.0 / self.camera.zoom];
+        let view = (
+            [x - half_size[0], y - half_size[1]],
+            [x + half_size[0], y + half_size[1]],
+        );
+        self.minimap.update(
+            &self.device,
+            &self.queue,
+            &game.grid,
+            self.palette,
+            view,
+            (width, height),
+            border,
+        );
+    }
+
+    pub fn render(&mut self, game: &Game) -> Result<RenderStats, RenderError> {
+        // There's no surface to draw to until the window is restored, and
+        // nothing works on a lost device until `App` replaces the state
+        if self.minimized || self.is_device_lost() {
+            return Ok(RenderStats::default());
+        }
+
+        let started = Instant::now();
+        let mut stats = RenderStats::default();
+
+        self.queue_cells(game);
+        self.sprites.flush(&self.device, &self.queue);
+        let ui_instances = self.ui_instances(game);
+        self.ui_instance_buffer
+            .write(&self.device, &self.queue, &ui_instances);
+
+        let (margin, line_height) = (self.ui_scale(8.0), self.ui_scale(24.0));
+        if self.thumbnail {
+            // Nothing but the grid
+        } else if self.app_state == AppState::Menu {
+            self.queue_menu();
+        } else if self.console.is_open() {
+            self.queue_console(margin, line_height);
+        } else {
+            self.draw_text(&format!("Score {}", game.score), margin, margin);
+            let mut y = margin + line_height;
+            if self.paused {
+                self.draw_text("Paused", margin, y);
+                y += line_height;
+            }
+            if game.sim_speed != 1.0 {
+                self.draw_text(&format!("Speed {}x", game.sim_speed), margin, y);
+                y += line_height;
+            }
+            if game.step_mode() {
+                self.draw_text("Step mode", margin, y);
+            }
+            if game.is_solved() {
+                self.queue_solved_banner(margin);
+            }
+            self.queue_tooltip();
+        }
+        if self.show_frame_graph {
+            self.queue_frame_graph();
+        }
+        let minimap_rect = self.minimap_rect();
+        if let Some(rect) = minimap_rect {
+            self.prepare_minimap(game, rect);
+        }
+        if !self.scissors.is_empty() {
+            log::warn!("A UI clip region was never popped");
+            self.scissors.clear();
+        }
+        self.text.prepare(&self.device, &self.queue);
+        self.overlay.prepare(&self.device, &self.queue);
+        if self.show_checkerboard {
+            self.checkerboard
+                .set_colors(&self.queue, self.checkerboard_colors);
+        }
+
+        let encode_time = started.elapsed();
+        let (output, view) = match &self.target {
+            RenderTarget::Surface(surface) => {
+                let output = surface.get_current_texture()?;
+                let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
+                    format: Some(texture::render_format(&self.config)),
+                    ..Default::default()
+                });
+                (Some(output), view)
+            }
+            RenderTarget::Offscreen(texture) => (
+                None,
+                texture
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default()),
+            ),
+        };
+        let acquired = Instant::now();
+
+        // At an internal resolution the world and its UI are drawn to the
+        // whole low resolution texture, and only scaled into the viewport
+        self.upscaler.prepare(
+            &self.device,
+            &self.config,
+            self.sample_count,
+            self.internal_resolution,
+        );
+        let low_res = self.upscaler.target();
+        let (world_view, world_viewport, depth_view, msaa_texture) = match low_res {
+            Some(target) => (
+                &target.color.view,
+                (0.0, 0.0, target.size.0 as f32, target.size.1 as f32),
+                &target.depth.view,
+                target.msaa.as_ref(),
+            ),
+            None => (
+                &view,
+                self.viewport(),
+                &self.depth_texture.view,
+                self.msaa_texture.as_ref(),
+            ),
+        };
+        self.grid_lines.update(
+            &self.device,
+            &self.queue,
+            (game.grid.width(), game.grid.height()),
+            self.grid_style,
+            &self.camera,
+            self.aspect(),
+            world_viewport.3,
+            self.scale_factor as f32,
+        );
+
+        // With MSAA enabled the pass renders into the multisampled texture and
+        // resolves into the world's texture
+        let (color_view, resolve_target) = match msaa_texture {
+            Some(msaa_texture) => (&msaa_texture.view, Some(world_view)),
+            None => (world_view, None),
+        };
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("WGPU Frame Command Encoder"),
+            });
+
+        // The uniforms every frame sets go through the staging belt, copied
+        // ahead of the passes that read them
+        let camera_uniform = CameraUniform {
+            view_proj: self.camera.build_view_projection_matrix(self.aspect()),
+        };
+        let clear_color = ClearColorUniform::from(self.clear_color);
+        let cell_shape = CellShapeUniform::new(self.cell_corner_radius, self.cell_edge_softness);
+        let uniforms: [(&wgpu::Buffer, &[u8]); 3] = [
+            (&self.camera_buffer, bytemuck::bytes_of(&camera_uniform)),
+            (&self.clear_color_buffer, bytemuck::bytes_of(&clear_color)),
+            (&self.cell_shape_buffer, bytemuck::bytes_of(&cell_shape)),
+        ];
+        for (buffer, bytes) in uniforms {
+            Self::write_staged(
+                &mut self.staging_belt,
+                &self.device,
+                &mut encoder,
+                buffer,
+                bytes,
+            );
+        }
+
+        // Each pass sits in its own debug group, with a nested group per thing
+        // it draws, so frame captures read like the code below
+        encoder.push_debug_group("world");
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("WGPU World Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color_view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.frame_clear_color()),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: self.gpu_timer.as_ref().map(GpuTimer::timestamp_writes),
+            });
+
+            let (x, y, width, height) = world_viewport;
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+
+            let pipeline = match &self.wireframe_pipeline {
+                Some(wireframe_pipeline) if self.wireframe => wireframe_pipeline,
+                _ => &self.render_pipeline,
+            };
+            if self.show_checkerboard {
+                render_pass.push_debug_group("checkerboard");
+                self.checkerboard
+                    .render(&mut render_pass, &self.camera_bind_group, &mut stats);
+                render_pass.pop_debug_group();
+            }
+
+            render_pass.push_debug_group("cells");
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &self.clear_color_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.diffuse_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(3, &self.cell_shape_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            self.sprites
+                .render(&mut render_pass, self.num_indices, &mut stats);
+            render_pass.pop_debug_group();
+        }
+        encoder.pop_debug_group();
+
+        // Grid lines and world-space UI are blended over the resolved world,
+        // within the same viewport so they line up with the cells
+        encoder.push_debug_group("ui");
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("WGPU UI Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: world_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            let (x, y, width, height) = world_viewport;
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+
+            render_pass.push_debug_group("grid lines");
+            self.grid_lines
+                .render(&mut render_pass, &self.camera_bind_group, &mut stats);
+            render_pass.pop_debug_group();
+            if !self.ui_instance_buffer.is_empty() {
+                render_pass.push_debug_group("highlights");
+                render_pass.set_pipeline(&self.ui_pipeline);
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                render_pass.set_vertex_buffer(1, self.ui_instance_buffer.slice());
+                render_pass
+                    .set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                render_pass.draw_indexed(0..self.num_indices, 0, 0..self.ui_instance_buffer.len());
+                stats.record_draw(self.num_indices, self.ui_instance_buffer.len());
+                render_pass.pop_debug_group();
+            }
+        }
+        encoder.pop_debug_group();
+
+        // Stretches the low resolution world over the viewport, clearing the
+        // letterbox bars around it
+        if low_res.is_some() {
+            encoder.push_debug_group("upscale");
+            {
+                let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: Some("WGPU Upscale Render Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(self.frame_clear_color()),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    occlusion_query_set: None,
+                    timestamp_writes: None,
+                });
+
+                let (x, y, width, height) = self.viewport();
+                render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                self.upscaler.render(&mut render_pass, &mut stats);
+            }
+            encoder.pop_debug_group();
+        }
+
+        // Overlays and text go on top of the finished frame, after any MSAA
+        // resolve, and ignore the letterbox viewport
+        encoder.push_debug_group("overlay");
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("WGPU Overlay Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.push_debug_group("rects");
+            let surface_size = (self.config.width, self.config.height);
+            self.overlay
+                .render(&mut render_pass, surface_size, &mut stats);
+            render_pass.pop_debug_group();
+            render_pass.push_debug_group("text");
+            self.text.render(&mut render_pass, surface_size, &mut stats);
+            render_pass.pop_debug_group();
+            if let Some(rect) = minimap_rect {
+                render_pass.push_debug_group("minimap");
+                self.minimap
+                    .render(&mut render_pass, rect, surface_size, &mut stats);
+                render_pass.pop_debug_group();
+            }
+        }
+        encoder.pop_debug_group();
+
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.resolve(&mut encoder);
+        }
+
+        // Chunks are unmapped for the copies, then mapped again once the GPU
+        // is done with them so the next frames can reuse them
+        self.staging_belt.finish();
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.staging_belt.recall();
+        stats.encode_time = encode_time + acquired.elapsed();
+        self.render_stats = stats;
+
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.map();
+        }
+
+        let mut screenshot = Ok(());
+        if std::mem::take(&mut self.screenshot_requested) {
+            let texture = match &self.target {
+                RenderTarget::Surface(_) => output.as_ref().map(|output| &output.texture),
+                RenderTarget::Offscreen(texture) => Some(&texture.texture),
+            };
+            if let Some(texture) = texture {
+                screenshot = self.save_screenshot(texture);
+            }
+        }
+
+        if let Some(output) = output {
+            output.present();
+        }
+        screenshot
+            .map(|()| stats)
+            .map_err(RenderError::ScreenshotReadback)
+    }
+
+    // Only a failed readback is returned, as it's part of rendering the frame.
+    // Failing to write the file is logged instead.
+    fn save_screenshot(&self, texture: &wgpu::Texture) -> Result<(), wgpu::BufferAsyncError> {
+        if !self.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            log::warn!("The surface doesn't support screenshots");
+            return Ok(());
+        }
+
+        let mut pixels = texture::read_texture(&self.device, &self.queue, texture)?;
+
+        // Surfaces are commonly BGRA, while PNGs are always RGBA
+        if matches!(
+            self.config.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            pixels
+                .chunks_exact_mut(4)
+                .for_each(|pixel| pixel.swap(0, 2));
+        }
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = format!("screenshot-{}.png", timestamp);
+
+        match image::save_buffer(
+            &path,
+            &pixels,
+            texture.width(),
+            texture.height(),
+            image::ColorType::Rgba8,
+        ) {
+            Ok(()) => log::info!("Saved a screenshot to {}", path),
+            Err(e) => log::error!("An error occured while saving the screenshot: {}", e),
+        }
+        Ok(())
+    }
+
+    // Reads back the last rendered frame as tightly packed RGBA8 rows. Only
+    // headless states keep their frames around, so this is `None` otherwise.
+    #[allow(dead_code)]
+    pub fn read_pixels(&self) -> Option<Vec<u8>> {
+        let RenderTarget::Offscreen(texture) = &self.target else {
+            return None;
+        };
+
+        texture::read_texture(&self.device, &self.queue, &texture.texture)
+            .map_err(|e| log::error!("An error occured while reading back pixels: {}", e))
+            .ok()
+    }
+}
+
+// Waits for submitted work, and the thumbnail being saved, to finish before
+// the device and surface go away, so nothing is abandoned mid-frame on exit
+impl Drop for State<'_> {
+    fn drop(&mut self) {
+        self.device.poll(wgpu::Maintain::Wait);
+        self.join_thumbnail_thread();
+        log::info!("Shut down cleanly");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pollster::FutureExt;
+    use winit::{dpi::PhysicalPosition, event::DeviceId};
+
+    use super::*;
+
+    // Machines without any adapter (not even a software one) can't run the
+    // tests that render, which skip themselves when this is `None`
+    fn headless(width: u32, height: u32) -> Option<State<'static>> {
+        let _ = env_logger::builder().is_test(true).try_init();
+        match State::new_headless(width, height).block_on() {
+            Ok(state) => Some(state),
+            Err(StateError::AdapterRequest) => {
+                log::warn!("Skipping a test that needs a GPU adapter, as there isn't one");
+                None
+            }
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn headless_render_draws_the_grid() {
+        let Some(mut state) = headless(64, 64) else {
+            return;
+        };
+
+        let mut game = Game::default();
+        state.fit_grid(&game);
+        state.update(&mut game);
+        let stats = state.render(&game).unwrap();
+        assert!(stats.draw_calls > 0 && stats.instances > 0);
+        assert!(stats.triangles >= stats.instances);
+
+        let pixels = state.read_pixels().unwrap();
+        assert_eq!(pixels.len(), 64 * 64 * 4);
+
+        // The corner is outside the grid and shows the clear color, while the
+        // center is covered by a cell
+        let pixel = |x: usize, y: usize| &pixels[(y * 64 + x) * 4..][..4];
+        assert_ne!(pixel(0, 0), pixel(32, 32));
+    }
+
+    #[test]
+    fn sample_counts_fall_back_to_the_nearest_supported_one() {
+        let supported = [1, 2, 4];
+        assert_eq!(State::nearest_sample_count(&supported, 4), 4);
+        assert_eq!(State::nearest_sample_count(&supported, 8), 4);
+        assert_eq!(State::nearest_sample_count(&supported, 3), 2);
+        assert_eq!(State::nearest_sample_count(&supported, 0), 1);
+
+        let Some(mut state) = headless(64, 64) else {
+            return;
+        };
+        // More than any adapter supports, which would otherwise fail
+        // validation when creating the targets
+        state.set_sample_count(64);
+        assert!(state.supported_sample_counts.contains(&state.sample_count));
+        let game = Game::default();
+        state.render(&game).unwrap();
+
+        state.set_sample_count(1);
+        assert_eq!(state.sample_count, 1);
+        state.render(&game).unwrap();
+    }
+
+    #[test]
+    fn the_frame_latency_key_cycles_from_one_to_three() {
+        let Some(mut state) = headless(64, 64) else {
+            return;
+        };
+        assert_eq!(state.frame_latency(), 2);
+        let mut latencies = Vec::new();
+        for _ in 0..3 {
+            state.run_action(Action::FrameLatency);
+            latencies.push(state.frame_latency());
+        }
+        assert_eq!(latencies, [3, 1, 2]);
+        assert!(state.take_reconfigured());
+
+        state.set_frame_latency(0);
+        assert_eq!(state.frame_latency(), 1);
+        state.render(&Game::default()).unwrap();
+    }
+
+    #[test]
+    fn internal_resolutions_are_scaled_up_in_blocks() {
+        let Some(mut state) = headless(64, 64) else {
+            return;
+        };
+        state.set_internal_resolution(Some((4, 4)));
+
+        let mut game = Game::default();
+        state.fit_grid(&game);
+        state.update(&mut game);
+        state.render(&game).unwrap();
+
+        // Every 16x16 block is a single texel, apart from the top row where
+        // the score is drawn at full resolution
+        let pixels = state.read_pixels().unwrap();
+        let pixel = |x: usize, y: usize| &pixels[(y * 64 + x) * 4..][..4];
+        for (block_x, block_y) in [(1, 1), (2, 2), (3, 3)] {
+            let (x, y) = (block_x * 16, block_y * 16);
+            assert_eq!(pixel(x, y), pixel(x + 15, y + 15));
+        }
+        assert_ne!(pixel(16, 16), pixel(48, 48));
+    }
+
+    #[test]
+    fn clicking_play_starts_the_game() {
+        let Some(mut state) = headless(200, 200) else {
+            return;
+        };
+        state.set_app_state(AppState::Menu);
+
+        let play = state.menu_layout()[0];
+        assert_eq!(play.item, MenuItem::Play);
+        let [x, y] = play.position;
+        let [width, height] = play.size;
+        state.cursor = ((x + width / 2.0) as f64, (y + height / 2.0) as f64);
+        state.cursor_in_window = true;
+        assert_eq!(state.hovered_menu_item(), Some(MenuItem::Play));
+
+        // Clicking outside of every item does nothing
+        state.cursor = (0.0, 0.0);
+        state.select_hovered_menu_item();
+        assert_eq!(state.app_state, AppState::Menu);
+
+        state.cursor = ((x + width / 2.0) as f64, (y + height / 2.0) as f64);
+        state.select_hovered_menu_item();
+        assert_eq!(state.app_state, AppState::Playing);
+        assert!(!state.quit_requested());
+    }
+
+    #[test]
+    fn tooltips_describe_the_hovered_gate() {
+        let mut game = Game::new(3, 1);
+        game.grid.set(0, 0, Cell::Source);
+        game.grid.set(1, 0, Cell::Not);
+        game.grid.evaluate();
+
+        game.hovered_cell = Some((1, 0));
+        assert_eq!(State::tooltip_text(&game).as_deref(), Some("NOT: 1 -> 0"));
+        game.hovered_cell = Some((0, 0));
+        assert_eq!(State::tooltip_text(&game), None);
+    }
+
+    #[test]
+    fn inspectors_leave_the_game_alone() {
+        let Some(mut inspector) = headless(64, 64) else {
+            return;
+        };
+        let mut game = Game::new(8, 8);
+        game.selected_cell = Some((6, 1));
+        game.hovered_cell = Some((2, 2));
+        inspector.make_inspector(&game);
+        assert_eq!(inspector.camera.position, [6.5, 1.5]);
+
+        inspector.apply_game_input(
+            &mut game,
+            GameInput::Click {
+                button: MouseButton::Left,
+                position: [6.5, 1.5],
+            },
+        );
+        std::thread::sleep(Game::FIXED_STEP * 2);
+        inspector.update(&mut game);
+        assert_eq!(game.grid.get(6, 1), Some(Cell::Empty));
+        assert_eq!(game.ticks, 0);
+        assert_eq!(game.hovered_cell, Some((2, 2)));
+    }
+
+    #[test]
+    fn a_stopped_simulation_takes_no_steps() {
+        let Some(mut state) = headless(64, 64) else {
+            return;
+        };
+        let mut game = Game::new(2, 2);
+        // Two steps' worth of time since the last frame, without waiting
+        let two_steps_ago = || Instant::now() - Game::FIXED_STEP * 2;
+        game.sim_speed = 0.0;
+        state.last_frame = two_steps_ago();
+        state.update(&mut game);
+        assert_eq!(game.ticks, 0);
+
+        game.sim_speed = 1.0;
+        state.last_frame = two_steps_ago();
+        state.update(&mut game);
+        assert!(game.ticks >= 2);
+    }
+
+    #[test]
+    fn window_events_become_game_input() {
+        let Some(mut state) = headless(64, 64) else {
+            return;
+        };
+        let mut game = Game::new(3, 3);
+        state.fit_grid(&game);
+
+        // Only ever compared against itself, which is all `dummy` promises
+        let device_id = unsafe { DeviceId::dummy() };
+        let mouse = |state: ElementState| WindowEvent::MouseInput {
+            device_id,
+            state,
+            button: MouseButton::Left,
+        };
+        assert!(state.input(WindowEvent::CursorMoved {
+            device_id,
+            position: PhysicalPosition::new(32.0, 32.0),
+        }));
+        assert!(state.input(mouse(ElementState::Pressed)));
+        assert!(state.input(mouse(ElementState::Released)));
+        state.update(&mut game);
+        assert_eq!(game.hovered_cell, Some((1, 1)));
+        assert_eq!(state.cursor_icon, CursorIcon::Pointer);
+        assert_ne!(game.grid.get(1, 1), Some(Cell::Empty));
+
+        // Clicks are queued but dropped while paused, and the pause key still
+        // works to resume
+        let press_pause = |state: &mut State, received| {
+            let pause = state.key_bindings.keys(Action::Pause)[0];
+            let action = state.key_input(pause, ElementState::Pressed, false, received);
+            assert!(matches!(action, Some(InputAction::KeyPress(Action::Pause))));
+            state.input_actions.extend(action);
+            assert!(state
+                .key_input(pause, ElementState::Released, false, received)
+                .is_none());
+        };
+        let cell = game.grid.get(1, 1);
+        let paused_at = Instant::now();
+        press_pause(&mut state, paused_at);
+        state.update(&mut game);
+        assert!(state.paused);
+        assert_eq!(state.cursor_icon, CursorIcon::Default);
+        state.input(mouse(ElementState::Pressed));
+        state.input(mouse(ElementState::Released));
+        state.update(&mut game);
+        let resumed_at = paused_at + state.toggle_debounce.interval;
+        press_pause(&mut state, resumed_at);
+        state.update(&mut game);
+        assert!(!state.paused);
+        assert_eq!(game.grid.get(1, 1), cell);
+
+        // The console swallows clicks
+        state.console.toggle();
+        assert!(state.input(mouse(ElementState::Pressed)));
+        assert!(state.input(mouse(ElementState::Released)));
+        state.update(&mut game);
+        assert_eq!(game.grid.get(1, 1), cell);
+    }
+
+    #[test]
+    fn only_movement_keys_repeat_and_toggles_are_debounced() {
+        let Some(mut state) = headless(64, 64) else {
+            return;
+        };
+        let start = Instant::now();
+        let mut press = |action: Action, repeat: bool, received: Instant| {
+            let key = state.key_bindings.keys(action)[0];
+            let pressed = state.key_input(key, ElementState::Pressed, repeat, received);
+            state.key_input(key, ElementState::Released, false, received);
+            matches!(pressed, Some(InputAction::KeyPress(pressed)) if pressed == action)
+        };
+
+        assert!(press(Action::MoveUp, true, start));
+        assert!(press(Action::Place, false, start));
+        assert!(!press(Action::Place, true, start));
+        assert!(!press(Action::PanUp, true, start));
+
+        // A tap right after the last one bounced, and holding the key down
+        // doesn't flip it back and forth either
+        let interval = Settings::default().toggle_debounce();
+        assert!(press(Action::Pause, false, start));
+        assert!(!press(Action::Pause, false, start + interval / 2));
+        assert!(!press(Action::Pause, true, start + interval * 2));
+        assert!(press(Action::Pause, false, start + interval));
+    }
+
+    #[test]
+    fn events_are_handled_in_the_order_they_arrived() {
+        let Some(mut state) = headless(64, 64) else {
+            return;
+        };
+        let mut game = Game::new(3, 3);
+        state.fit_grid(&game);
+
+        // A click on the middle cell, then a move off it before the frame
+        let device_id = unsafe { DeviceId::dummy() };
+        let mouse = |state: ElementState| WindowEvent::MouseInput {
+            device_id,
+            state,
+            button: MouseButton::Left,
+        };
+        let move_to = |x: f64, y: f64| WindowEvent::CursorMoved {
+            device_id,
+            position: PhysicalPosition::new(x, y),
+        };
+        state.input(move_to(32.0, 32.0));
+        state.input(mouse(ElementState::Pressed));
+        state.input(mouse(ElementState::Released));
+        state.input(move_to(16.0, 32.0));
+        assert_eq!(state.input_events.len(), 4);
+        assert_eq!(game.grid.get(1, 1), Some(Cell::Empty));
+
+        // Only the clicked cell changes, and the cursor ends up where it moved
+        state.update(&mut game);
+        assert!(state.input_events.is_empty());
+        assert_ne!(game.grid.get(1, 1), Some(Cell::Empty));
+        assert_eq!(game.grid.get(0, 1), Some(Cell::Empty));
+        assert_eq!(game.hovered_cell, Some((0, 1)));
+    }
+
+    #[test]
+    fn clicking_the_minimap_recenters_the_camera() {
+        let Some(mut state) = headless(256, 256) else {
+            return;
+        };
+        let mut game = Game::new(40, 20);
+        state.update(&mut game);
+        state.run_action(Action::Minimap);
+        let (x, y, width, height) = state.minimap_rect().unwrap();
+
+        // A click on the minimap's top-left quarter moves the camera there
+        // without touching the grid, and dragging keeps it following
+        let device_id = unsafe { DeviceId::dummy() };
+        let mouse = |state: ElementState| WindowEvent::MouseInput {
+            device_id,
+            state,
+            button: MouseButton::Left,
+        };
+        let move_to = |x: f32, y: f32| WindowEvent::CursorMoved {
+            device_id,
+            position: PhysicalPosition::new(x as f64, y as f64),
+        };
+        state.input(move_to(x + width / 4.0, y + height / 4.0));
+        state.input(mouse(ElementState::Pressed));
+        state.update(&mut game);
+        assert_eq!(state.camera.target_position, [10.0, 15.0]);
+
+        state.input(move_to(x + width, y + height));
+        state.input(mouse(ElementState::Released));
+        state.update(&mut game);
+        assert_eq!(state.camera.target_position, [40.0, 0.0]);
+        assert!(game.grid.get(10, 15) == Some(Cell::Empty) && !state.minimap_held);
+        state.render(&game).unwrap();
+    }
+
+    #[test]
+    fn ui_panels_clip_what_is_queued_inside_them() {
+        let Some(mut state) = headless(64, 64) else {
+            return;
+        };
+        let mut game = Game::default();
+        state.update(&mut game);
+        let render = |state: &mut State, text: bool| {
+            // Reaching far past the surface, which would fail validation
+            // unless clamped
+            state.push_clip([-100.0, -100.0], [1000.0, 1000.0]);
+            state.push_clip([0.0, 0.0], [16.0, 64.0]);
+            if text {
+                state.draw_text("WWWWWWWW", 0.0, 32.0);
+            }
+            state.pop_clip();
+            state.pop_clip();
+            state.render(&game).unwrap();
+            state.read_pixels().unwrap()
+        };
+
+        let without_text = render(&mut state, false);
+        let with_text = render(&mut state, true);
+        let row = |pixels: &[u8], y: usize, xs: std::ops::Range<usize>| {
+            pixels[(y * 64 + xs.start) * 4..(y * 64 + xs.end) * 4].to_vec()
+        };
+        let mut text_rows = 32..56;
+        assert!(text_rows
+            .clone()
+            .any(|y| row(&with_text, y, 0..16) != row(&without_text, y, 0..16)));
+        assert!(text_rows.all(|y| row(&with_text, y, 16..64) == row(&without_text, y, 16..64)));
+    }
+
+    #[test]
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    fn a_broken_shader_keeps_the_previous_pipelines() {
+        let Some(mut state) = headless(64, 64) else {
+            return;
+        };
+
+        assert!(!state.rebuild_world_pipelines("fn vs_main( {"));
+        assert!(state.rebuild_world_pipelines(include_str!("shader.wgsl")));
+
+        let mut game = Game::default();
+        state.update(&mut game);
+        state.render(&game).unwrap();
+    }
+
+    #[test]
+    fn destroying_the_device_reports_it_lost() {
+        let Some(state) = headless(64, 64) else {
+            return;
+        };
+        assert!(!state.is_device_lost());
+
+        state.device.destroy();
+        state.device.poll(wgpu::Maintain::Wait);
+        assert!(state.is_device_lost());
+    }
+
+    #[test]
+    fn a_lost_device_skips_updates_and_frames() {
+        let Some(mut state) = headless(64, 64) else {
+            return;
+        };
+        let mut game = Game::default();
+        state.set_app_state(AppState::Playing);
+
+        state.device.destroy();
+        state.device.poll(wgpu::Maintain::Wait);
+        state.accumulator = Game::FIXED_STEP * 2;
+        state.update(&mut game);
+        assert_eq!(game.ticks, 0);
+        assert_eq!(state.render(&game).unwrap(), RenderStats::default());
+    }
+
+    #[test]
+    fn the_loading_frame_shows_text_over_the_clear_color() {
+        let Some(mut state) = headless(128, 64) else {
+            return;
+        };
+
+        let RenderTarget::Offscreen(target) = &state.target else {
+            unreachable!("headless states render offscreen");
+        };
+        let view = target
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        loading::draw_frame(
+            &state.device,
+            &state.queue,
+            &view,
+            (128, 64),
+            &mut state.text,
+            wgpu::Color::BLACK,
+        );
+        let pixels = state.read_pixels().unwrap();
+        let pixel = |x: usize, y: usize| &pixels[(y * 128 + x) * 4..(y * 128 + x) * 4 + 4];
+
+        assert_eq!(pixel(0, 0), [0, 0, 0, 255]);
+        assert!((0..64).any(|y| (0..128).any(|x| pixel(x, y)[0] > 128)));
+    }
+}
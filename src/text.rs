@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    dynamic_buffer::DynamicBuffer,
+    render_stats::RenderStats,
+    scissor::{ClipRuns, ScissorRect},
+    uniform::ScreenUniform,
+};
+
+const FONT: &[u8] = include_bytes!("../assets/DejaVuSansMono.ttf");
+// In logical pixels; the atlas is rasterized at this times the scale factor
+const FONT_SIZE: f32 = 20.0;
+const ATLAS_WIDTH: u32 = 512;
+// Empty texels between glyphs so linear filtering doesn't bleed neighbors in
+const GLYPH_PADDING: u32 = 1;
+// Printable ASCII is rasterized up front; anything else is drawn as '?'
+const FALLBACK_GLYPH: char = '?';
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TextVertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl TextVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = [
+        wgpu::VertexAttribute {
+            offset: 0,
+            shader_location: 0,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+            shader_location: 1,
+            format: wgpu::VertexFormat::Float32x2,
+        },
+        wgpu::VertexAttribute {
+            offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            shader_location: 2,
+            format: wgpu::VertexFormat::Float32x4,
+        },
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TextVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Where a glyph's bitmap sits in the atlas, and where its quad goes relative
+// to the pen position at the top of the line
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    size: [f32; 2],
+    offset: [f32; 2],
+    advance: f32,
+}
+
+// Draws screen-space text from a glyph atlas, built at startup and again when
+// the scale factor changes. Text is queued during the frame and uploaded as
+// one vertex buffer before its pass.
+pub struct TextRenderer {
+    font: fontdue::Font,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    screen_buffer: wgpu::Buffer,
+    vertex_buffer: DynamicBuffer<TextVertex>,
+    vertices: Vec<TextVertex>,
+    clips: ClipRuns,
+    glyphs: HashMap<char, Glyph>,
+    line_height: f32,
+}
+
+impl TextRenderer {
+    const INITIAL_VERTEX_CAPACITY: usize = 6 * 256;
+
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        scale_factor: f32,
+    ) -> Result<Self, &'static str> {
+        let font = fontdue::Font::from_bytes(FONT, fontdue::FontSettings::default())?;
+
+        let screen_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("WGPU Text Screen Buffer"),
+            contents: bytemuck::cast_slice(&[ScreenUniform::new(width, height)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("WGPU Text Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("text.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WGPU Text Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("WGPU Text Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[TextVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            // Text is drawn in its own pass over the resolved frame, so it has
+            // no depth buffer and is never multisampled
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let (bind_group, glyphs, line_height) = Self::build_atlas(
+            device,
+            queue,
+            &font,
+            &bind_group_layout,
+            &screen_buffer,
+            FONT_SIZE * scale_factor,
+        )?;
+        let vertex_buffer = DynamicBuffer::new(
+            device,
+            "WGPU Text Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            Self::INITIAL_VERTEX_CAPACITY,
+        );
+
+        Ok(Self {
+            font,
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            screen_buffer,
+            vertex_buffer,
+            vertices: Vec::new(),
+            clips: ClipRuns::default(),
+            glyphs,
+            line_height,
+        })
+    }
+
+    // Rasterizes printable ASCII at `font_size` pixels into a new atlas and
+    // returns the bind group for it along with each glyph's placement
+    fn build_atlas(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        font: &fontdue::Font,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        screen_buffer: &wgpu::Buffer,
+        font_size: f32,
+    ) -> Result<(wgpu::BindGroup, HashMap<char, Glyph>, f32), &'static str> {
+        let line_metrics = font
+            .horizontal_line_metrics(font_size)
+            .ok_or("The font has no horizontal line metrics")?;
+
+        // Packs the glyphs into rows, left to right
+        let rasterized: Vec<_> = (' '..='~')
+            .map(|c| (c, font.rasterize(c, font_size)))
+            .collect();
+        let mut placements = Vec::with_capacity(rasterized.len());
+        let (mut x, mut y, mut row_height) = (GLYPH_PADDING, GLYPH_PADDING, 0);
+        for (_, (metrics, _)) in &rasterized {
+            let (width, height) = (metrics.width as u32, metrics.height as u32);
+            if x + width + GLYPH_PADDING > ATLAS_WIDTH {
+                x = GLYPH_PADDING;
+                y += row_height + GLYPH_PADDING;
+                row_height = 0;
+            }
+            placements.push((x, y));
+            x += width + GLYPH_PADDING;
+            row_height = row_height.max(height);
+        }
+        let atlas_height = y + row_height + GLYPH_PADDING;
+
+        let mut pixels = vec![0; (ATLAS_WIDTH * atlas_height) as usize];
+        let mut glyphs = HashMap::with_capacity(rasterized.len());
+        for ((c, (metrics, bitmap)), (x, y)) in rasterized.iter().zip(placements) {
+            for (row, line) in bitmap.chunks(metrics.width.max(1)).enumerate() {
+                let start = ((y as usize + row) * ATLAS_WIDTH as usize) + x as usize;
+                pixels[start..start + line.len()].copy_from_slice(line);
+            }
+
+            let (width, height) = (metrics.width as f32, metrics.height as f32);
+            glyphs.insert(
+                *c,
+                Glyph {
+                    uv_min: [
+                        x as f32 / ATLAS_WIDTH as f32,
+                        y as f32 / atlas_height as f32,
+                    ],
+                    uv_max: [
+                        (x as f32 + width) / ATLAS_WIDTH as f32,
+                        (y as f32 + height) / atlas_height as f32,
+                    ],
+                    size: [width, height],
+                    // fontdue measures the bitmap's bottom edge up from the baseline
+                    offset: [
+                        metrics.xmin as f32,
+                        line_metrics.ascent - (metrics.ymin as f32 + height),
+                    ],
+                    advance: metrics.advance_width,
+                },
+            );
+        }
+
+        let atlas_size = wgpu::Extent3d {
+            width: ATLAS_WIDTH,
+            height: atlas_height,
+            depth_or_array_layers: 1,
+        };
+        let atlas = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("WGPU Glyph Atlas Texture"),
+            size: atlas_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &atlas,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(ATLAS_WIDTH),
+                rows_per_image: Some(atlas_height),
+            },
+            atlas_size,
+        );
+        let atlas_view = atlas.create_view(&wgpu::TextureViewDescriptor::default());
+        let atlas_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("WGPU Glyph Atlas Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("WGPU Text Bind Group"),
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: screen_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&atlas_sampler),
+                },
+            ],
+        });
+
+        Ok((bind_group, glyphs, line_metrics.new_line_size))
+    }
+
+    // Rebuilds the atlas so text keeps the same size on screen after moving
+    // to a display with a different scale factor
+    pub fn set_scale_factor(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scale_factor: f32,
+    ) -> Result<(), &'static str> {
+        let (bind_group, glyphs, line_height) = Self::build_atlas(
+            device,
+            queue,
+            &self.font,
+            &self.bind_group_layout,
+            &self.screen_buffer,
+            FONT_SIZE * scale_factor,
+        )?;
+        self.bind_group = bind_group;
+        self.glyphs = glyphs;
+        self.line_height = line_height;
+        Ok(())
+    }
+
+    pub fn resize(&self, queue: &wgpu::Queue, width: u32, height: u32) {
+        queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&[ScreenUniform::new(width, height)]),
+        );
+    }
+
+    // Queues `text` with its top-left corner at (x, y) in pixels from the
+    // top-left of the target. Newlines start a new line below the first.
+    pub fn queue(&mut self, text: &str, x: f32, y: f32, color: [f32; 4]) {
+        // Quads are snapped to whole pixels so glyphs sample the atlas 1:1
+        let (mut pen_x, mut pen_y) = (x.round(), y.round());
+
+        for c in text.chars() {
+            if c == '\n' {
+                pen_x = x.round();
+                pen_y += self.line_height.round();
+                continue;
+            }
+
+            let Some(glyph) = self
+                .glyphs
+                .get(&c)
+                .or_else(|| self.glyphs.get(&FALLBACK_GLYPH))
+            else {
+                continue;
+            };
+
+            let left = pen_x + glyph.offset[0];
+            let top = pen_y + glyph.offset[1];
+            let (right, bottom) = (left + glyph.size[0], top + glyph.size[1]);
+            let [u0, v0] = glyph.uv_min;
+            let [u1, v1] = glyph.uv_max;
+
+            let vertex = |position, tex_coords| TextVertex {
+                position,
+                tex_coords,
+                color,
+            };
+            self.vertices.extend_from_slice(&[
+                vertex([left, top], [u0, v0]),
+                vertex([left, bottom], [u0, v1]),
+                vertex([right, bottom], [u1, v1]),
+                vertex([left, top], [u0, v0]),
+                vertex([right, bottom], [u1, v1]),
+                vertex([right, top], [u1, v0]),
+            ]);
+
+            pen_x += glyph.advance.round();
+        }
+    }
+
+    // Clips the text queued from now on, or stops clipping it with `None`
+    pub fn set_clip(&mut self, rect: Option<ScissorRect>) {
+        self.clips.set(self.vertices.len() as u32, rect);
+    }
+
+    // The size in pixels of the box `queue` would fill with `text`, using
+    // the same rounding
+    pub fn measure(&self, text: &str) -> [f32; 2] {
+        let line_height = self.line_height.round();
+        let width = text
+            .split('\n')
+            .map(|line| {
+                line.chars()
+                    .filter_map(|c| {
+                        self.glyphs
+                            .get(&c)
+                            .or_else(|| self.glyphs.get(&FALLBACK_GLYPH))
+                    })
+                    .map(|glyph| glyph.advance.round())
+                    .sum::<f32>()
+            })
+            .fold(0.0, f32::max);
+        [width, text.split('\n').count() as f32 * line_height]
+    }
+
+    // Uploads everything queued since the last call, growing the vertex
+    // buffer if needed, and clears the queue for the next frame
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.vertex_buffer.write(device, queue, &self.vertices);
+        self.clips.prepare(self.vertex_buffer.len());
+        self.vertices.clear();
+    }
+
+    pub fn allocations(&self) -> u64 {
+        self.vertex_buffer.allocations()
+    }
+
+    pub fn render<'p>(
+        &'p self,
+        render_pass: &mut wgpu::RenderPass<'p>,
+        surface_size: (u32, u32),
+        stats: &mut RenderStats,
+    ) {
+        if self.vertex_buffer.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice());
+        self.clips
+            .render(render_pass, surface_size, |render_pass, vertices| {
+                stats.record_draw(vertices.len() as u32, 1);
+                render_pass.draw(vertices, 0..1)
+            });
+    }
+}
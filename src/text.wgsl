@@ -0,0 +1,37 @@
+@group(0) @binding(0)
+var<uniform> screen_size: vec2<f32>;
+@group(0) @binding(1)
+var t_atlas: texture_2d<f32>;
+@group(0) @binding(2)
+var s_atlas: sampler;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+// Positions are in pixels with the origin at the top-left corner
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    let ndc = in.position / screen_size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.tex_coords = in.tex_coords;
+    out.color = in.color;
+    return out;
+}
+
+// The atlas only stores coverage, so it scales the color's alpha
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(t_atlas, s_atlas, in.tex_coords).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
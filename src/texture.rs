@@ -0,0 +1,404 @@
+// How an image is prepared before it's uploaded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextureLoadOptions {
+    // Multiplies each pixel's color by its alpha, for pipelines that blend
+    // premultiplied colors. Without it, the colors hidden under transparent
+    // pixels bleed into the edges around them as dark halos.
+    pub premultiply: bool,
+}
+
+pub struct Texture {
+    #[allow(dead_code)]
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+}
+
+impl Texture {
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width.max(1),
+                height: config.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            // Multisampled textures can't be sampled on every backend (GL rejects them)
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    // A single-sampled color texture matching the configuration, used in place
+    // of a surface texture when rendering headless or at an internal resolution
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width.max(1),
+                height: config.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: render_format(config),
+            usage: config.usage,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    // Returns `None` when multisampling is disabled, since the surface texture
+    // is rendered to directly in that case
+    pub fn create_msaa_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) -> Option<Self> {
+        if sample_count <= 1 {
+            return None;
+        }
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("WGPU MSAA Texture"),
+            size: wgpu::Extent3d {
+                width: config.width.max(1),
+                height: config.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: render_format(config),
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("WGPU MSAA Sampler"),
+            ..Default::default()
+        });
+
+        Some(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+        options: TextureLoadOptions,
+    ) -> Result<Self, image::ImageError> {
+        let image = image::load_from_memory(bytes)?.to_rgba8();
+        Ok(Self::from_image(device, queue, &image, label, options))
+    }
+
+    // Uploads an already decoded image, e.g. one decoded off the main thread
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &image::RgbaImage,
+        label: &str,
+        options: TextureLoadOptions,
+    ) -> Self {
+        let premultiplied;
+        let image = if options.premultiply {
+            premultiplied = premultiply_alpha(image);
+            &premultiplied
+        } else {
+            image
+        };
+        let (width, height) = image.dimensions();
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            image,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+}
+
+// Premultiplied in linear space, where the GPU filters and blends texels
+// after decoding them from sRGB
+fn premultiply_alpha(image: &image::RgbaImage) -> image::RgbaImage {
+    let mut image = image.clone();
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as f32 / 255.0;
+        for channel in &mut pixel.0[..3] {
+            let linear = srgb_to_linear(*channel as f32 / 255.0) * alpha;
+            *channel = (linear_to_srgb(linear) * 255.0).round() as u8;
+        }
+    }
+    image
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// The format everything is rendered in: the surface's own, or the sRGB view
+// of it that `srgb_view_format` added when the surface itself isn't sRGB
+pub fn render_format(config: &wgpu::SurfaceConfiguration) -> wgpu::TextureFormat {
+    config
+        .view_formats
+        .first()
+        .copied()
+        .unwrap_or(config.format)
+}
+
+// The shaders output linear colors and rely on an sRGB target to encode
+// them, so a surface without an sRGB format is viewed through the sRGB
+// variant of its own. Returns `None` when the surface is already sRGB, or
+// when there's no such view and colors will come out too dark.
+pub fn srgb_view_format(
+    format: wgpu::TextureFormat,
+    downlevel_flags: wgpu::DownlevelFlags,
+) -> Option<wgpu::TextureFormat> {
+    if format.is_srgb() {
+        log::info!("Rendering to the sRGB surface format {:?}", format);
+        return None;
+    }
+
+    let view_format = format.add_srgb_suffix();
+    if view_format == format {
+        log::warn!(
+            "The surface format {:?} has no sRGB variant, so colors may look wrong",
+            format
+        );
+        None
+    } else if !downlevel_flags.contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS) {
+        log::warn!(
+            "The surface format {:?} isn't sRGB and can't be viewed as {:?}, so colors may look wrong",
+            format,
+            view_format
+        );
+        None
+    } else {
+        log::info!(
+            "The surface format {:?} isn't sRGB, rendering to it as {:?}",
+            format,
+            view_format
+        );
+        Some(view_format)
+    }
+}
+
+// Copies a 4-byte-per-pixel texture (which needs `COPY_SRC` usage) into a
+// mappable buffer and returns its pixels as tightly packed rows. Buffer rows
+// have to be aligned to `COPY_BYTES_PER_ROW_ALIGNMENT` (256 bytes), so the
+// padding is stripped from each row after mapping.
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+    let (width, height) = (texture.width(), texture.height());
+    let unpadded_bytes_per_row = 4 * width;
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(alignment) * alignment;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("WGPU Readback Buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("WGPU Readback Command Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+
+    let pixels = slice
+        .get_mapped_range()
+        .chunks(padded_bytes_per_row as usize)
+        .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+        .copied()
+        .collect();
+    buffer.unmap();
+
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    #[test]
+    fn premultiplying_hides_the_color_under_transparent_pixels() {
+        // An opaque white sprite edge next to transparent pixels that still
+        // hold a color, which is what bled into the edge as a halo
+        let image = image::RgbaImage::from_fn(3, 1, |x, _| match x {
+            0 => Rgba([255, 255, 255, 255]),
+            1 => Rgba([255, 255, 255, 128]),
+            _ => Rgba([255, 0, 0, 0]),
+        });
+        let premultiplied = premultiply_alpha(&image);
+
+        assert_eq!(premultiplied.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+        // Half of white's linear intensity, back in sRGB
+        assert_eq!(premultiplied.get_pixel(1, 0), &Rgba([188, 188, 188, 128]));
+        assert_eq!(premultiplied.get_pixel(2, 0), &Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn non_srgb_surfaces_are_viewed_as_srgb() {
+        use wgpu::{DownlevelFlags, TextureFormat};
+
+        let flags = DownlevelFlags::SURFACE_VIEW_FORMATS;
+        assert_eq!(srgb_view_format(TextureFormat::Bgra8UnormSrgb, flags), None);
+        assert_eq!(
+            srgb_view_format(TextureFormat::Bgra8Unorm, flags),
+            Some(TextureFormat::Bgra8UnormSrgb)
+        );
+        // Without an sRGB variant, or a way to view it as one
+        assert_eq!(srgb_view_format(TextureFormat::Rgb10a2Unorm, flags), None);
+        assert_eq!(
+            srgb_view_format(TextureFormat::Rgba8Unorm, DownlevelFlags::empty()),
+            None
+        );
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: TextureFormat::Rgba8Unorm,
+            width: 1,
+            height: 1,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![TextureFormat::Rgba8UnormSrgb],
+        };
+        assert_eq!(render_format(&config), TextureFormat::Rgba8UnormSrgb);
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+};
+
+use pollster::FutureExt;
+
+use crate::{
+    game::Game,
+    grid::Grid,
+    palette::Palette,
+    state::{RenderError, State, StateError},
+};
+
+// Thumbnails are square, and this many pixels across
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+#[derive(Debug)]
+pub enum ThumbnailError {
+    State(StateError),
+    Render(RenderError),
+    // The frame was rendered but couldn't be read back
+    Readback,
+    Save(image::ImageError),
+}
+
+impl fmt::Display for ThumbnailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThumbnailError::State(e) => write!(
+                f,
+                "An error occured while setting up the thumbnail's renderer: {}",
+                e
+            ),
+            ThumbnailError::Render(e) => {
+                write!(f, "An error occured while rendering the thumbnail: {}", e)
+            }
+            ThumbnailError::Readback => {
+                write!(f, "An error occured while reading the thumbnail back")
+            }
+            ThumbnailError::Save(e) => {
+                write!(f, "An error occured while saving the thumbnail: {}", e)
+            }
+        }
+    }
+}
+
+impl Error for ThumbnailError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ThumbnailError::State(e) => Some(e),
+            ThumbnailError::Render(e) => Some(e),
+            ThumbnailError::Readback => None,
+            ThumbnailError::Save(e) => Some(e),
+        }
+    }
+}
+
+// Next to the circuit saved at `path`, with the same name
+pub fn thumbnail_path(path: impl AsRef<Path>) -> PathBuf {
+    path.as_ref().with_extension("png")
+}
+
+// Draws `grid` offscreen with the same renderer as the live view, so cells
+// and wires look just like they do in the window
+pub fn render(
+    grid: &Grid,
+    palette: Palette,
+    clear_color: wgpu::Color,
+) -> Result<image::RgbaImage, ThumbnailError> {
+    let mut state = State::new_headless(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .block_on()
+        .map_err(ThumbnailError::State)?;
+    state.palette = palette;
+    state.set_clear_color(clear_color);
+
+    let mut game = Game::new(grid.width(), grid.height());
+    game.grid = grid.clone();
+    state.make_thumbnail(&game);
+    state.render(&game).map_err(ThumbnailError::Render)?;
+
+    let pixels = state.read_pixels().ok_or(ThumbnailError::Readback)?;
+    Ok(
+        image::RgbaImage::from_raw(THUMBNAIL_SIZE, THUMBNAIL_SIZE, pixels)
+            .expect("read back rows are tightly packed"),
+    )
+}
+
+// Renders and saves the thumbnail on its own thread, with its own device, so
+// saving doesn't stall the window's frames
+pub fn spawn(
+    grid: Grid,
+    palette: Palette,
+    clear_color: wgpu::Color,
+    path: PathBuf,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let saved = render(&grid, palette, clear_color)
+            .and_then(|image| image.save(&path).map_err(ThumbnailError::Save));
+        match saved {
+            Ok(()) => log::info!("Saved a thumbnail to {}", path.display()),
+            Err(e) => log::error!("{}", e),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Cell;
+
+    #[test]
+    fn thumbnails_show_the_whole_grid() {
+        let _ = env_logger::builder().is_test(true).try_init();
+        assert_eq!(
+            thumbnail_path("saves/and.circuit"),
+            Path::new("saves/and.png")
+        );
+
+        let mut grid = Grid::new(3, 1);
+        grid.set(0, 0, Cell::Source);
+        grid.set(1, 0, Cell::Wire);
+        grid.evaluate();
+        let clear_color = wgpu::Color::BLACK;
+        let image = match render(&grid, Palette::default(), clear_color) {
+            Ok(image) => image,
+            Err(ThumbnailError::State(StateError::AdapterRequest)) => return,
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(image.dimensions(), (THUMBNAIL_SIZE, THUMBNAIL_SIZE));
+
+        // The corner is the clear color around the grid, and the grid's
+        // cells are all in view along the middle
+        let middle = THUMBNAIL_SIZE / 2;
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        let lit_wire = image.get_pixel(middle, middle);
+        let empty_cell = image.get_pixel(middle + THUMBNAIL_SIZE / 4, middle);
+        assert_ne!(lit_wire, empty_cell);
+    }
+}
@@ -0,0 +1,33 @@
+@group(0) @binding(0)
+var<uniform> view_proj: mat4x4<f32>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(3) offset: vec2<f32>,
+    @location(4) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+// Shares the world's quad mesh and instance layout, but draws flat,
+// translucent colors on top of the world instead of textured tiles
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let position = model.position + vec3<f32>(instance.offset, 0.0);
+
+    var out: VertexOutput;
+    out.clip_position = view_proj * vec4<f32>(position, 1.0);
+    out.color = instance.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
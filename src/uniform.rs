@@ -0,0 +1,62 @@
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ClearColorUniform {
+    pub color: [f32; 4],
+}
+
+impl From<wgpu::Color> for ClearColorUniform {
+    fn from(color: wgpu::Color) -> Self {
+        ClearColorUniform {
+            color: [
+                color.r as f32,
+                color.g as f32,
+                color.b as f32,
+                color.a as f32,
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view_proj: [[f32; 4]; 4],
+}
+
+// The render target size in pixels, padded to 16 bytes since uniform buffers
+// are laid out in vec4-sized chunks on some backends
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ScreenUniform {
+    pub size: [f32; 2],
+    pub _padding: [f32; 2],
+}
+
+impl ScreenUniform {
+    pub fn new(width: u32, height: u32) -> Self {
+        ScreenUniform {
+            size: [width as f32, height as f32],
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+// How cells are rounded off, in world units where a cell is 1 wide. Edges are
+// always smoothed over about a pixel, and `edge_softness` blurs them further.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CellShapeUniform {
+    pub corner_radius: f32,
+    pub edge_softness: f32,
+    pub _padding: [f32; 2],
+}
+
+impl CellShapeUniform {
+    pub fn new(corner_radius: f32, edge_softness: f32) -> Self {
+        CellShapeUniform {
+            corner_radius,
+            edge_softness,
+            _padding: [0.0; 2],
+        }
+    }
+}
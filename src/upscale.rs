@@ -0,0 +1,164 @@
+use crate::{render_stats::RenderStats, texture::Texture};
+
+// The textures the world is drawn into at the internal resolution. The
+// color texture is what gets scaled up, and the others match the world
+// pass's sample count.
+pub struct LowResTarget {
+    pub size: (u32, u32),
+    sample_count: u32,
+    pub color: Texture,
+    pub msaa: Option<Texture>,
+    pub depth: Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+// Draws the world at a fixed resolution and stretches it over the viewport
+// with nearest-neighbor sampling, for a pixelated look whose cost doesn't
+// depend on the window size
+pub struct Upscaler {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    target: Option<LowResTarget>,
+}
+
+impl Upscaler {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("WGPU Upscale Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("upscale.wgsl"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("WGPU Upscale Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("WGPU Upscale Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            bind_group_layout,
+            pipeline,
+            target: None,
+        }
+    }
+
+    // Only recreates the textures when the resolution or sample count changes,
+    // so resizing the window doesn't. `None` frees them.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        size: Option<(u32, u32)>,
+    ) {
+        let Some(size) = size else {
+            self.target = None;
+            return;
+        };
+        if self
+            .target
+            .as_ref()
+            .is_some_and(|target| target.size == size && target.sample_count == sample_count)
+        {
+            return;
+        }
+
+        log::debug!("Creating a {}x{} internal render target", size.0, size.1);
+        let config = wgpu::SurfaceConfiguration {
+            width: size.0,
+            height: size.1,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            ..config.clone()
+        };
+        // The default sampler filters with the nearest texel
+        let color = Texture::create_render_target(device, &config, "WGPU Low Res Texture");
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("WGPU Upscale Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&color.sampler),
+                },
+            ],
+        });
+
+        self.target = Some(LowResTarget {
+            size,
+            sample_count,
+            color,
+            msaa: Texture::create_msaa_texture(device, &config, sample_count),
+            depth: Texture::create_depth_texture(
+                device,
+                &config,
+                sample_count,
+                "WGPU Low Res Depth Texture",
+            ),
+            bind_group,
+        });
+    }
+
+    pub fn target(&self) -> Option<&LowResTarget> {
+        self.target.as_ref()
+    }
+
+    // Fills the pass's viewport with the low resolution world
+    pub fn render<'p>(&'p self, render_pass: &mut wgpu::RenderPass<'p>, stats: &mut RenderStats) {
+        let Some(target) = &self.target else {
+            return;
+        };
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &target.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        stats.record_draw(3, 1);
+    }
+}
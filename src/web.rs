@@ -0,0 +1,42 @@
+use web_sys::{console, wasm_bindgen::JsValue};
+
+// Browsers have nowhere for stderr to go, so logs, and panics, are written to
+// the page's console instead
+struct ConsoleLogger;
+
+impl log::Log for ConsoleLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = JsValue::from_str(&format!(
+            "[{} {}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        ));
+        match record.level() {
+            log::Level::Error => console::error_1(&message),
+            log::Level::Warn => console::warn_1(&message),
+            log::Level::Info => console::info_1(&message),
+            log::Level::Debug | log::Level::Trace => console::debug_1(&message),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+pub fn init_logger() {
+    static LOGGER: ConsoleLogger = ConsoleLogger;
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+
+    // Otherwise a panic only shows up as an unreachable instruction
+    std::panic::set_hook(Box::new(|info| log::error!("{}", info)));
+}